@@ -1,31 +1,43 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult,
-    SubMsg, Timestamp, Uint128, WasmMsg,
+    from_json, to_json_binary, Addr, Binary, Coin, Deps, DepsMut, Empty, Env, Event, MessageInfo,
+    Order, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128,
 };
 
 use cw2::set_contract_version;
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
 
+use crate::asset::{is_cw20_denom, is_cw721_denom, transfer_msgs, Asset};
 use crate::error::ContractError;
 use crate::msg::VestingExecuteMsg::StartVesting;
 use crate::msg::{
-    BidOffset, BidOffsetTime, BidsResponse, CancelBidMsg, CancelSwapMsg, DetailsResponse, ExecuteMsg, InstantiateMsg, ListResponse, MakeBidMsg, MakeSwapMsg, MigrateMsg, QueryMsg, TakeBidMsg, TakeSwapMsg, UpdateBidMsg, VestingDetails
+    BidOffset, BidOffsetTime, BidsResponse, CancelBidMsg, CancelSwapMsg, CommitBidMsg, CounterOfferActionMsg, CounterOfferMsg, CounterOffersResponse, CurrentPriceResponse, DetailsResponse, FillsResponse, ExecuteMsg, InstantiateMsg, ListResponse, MakeBidMsg, MakeSwapMsg, MigrateMsg, QueryMsg, ReceiveMsg, RejectBidMsg, RevealBidMsg, TakeBidMsg, TakeSwapMsg, UpdateBidMsg, UpdateSwapMsg, VestingDetails
 };
 use crate::query_reverse::{
     query_list_by_desired_taker_reverse, query_list_by_maker_reverse, query_list_by_taker_reverse,
     query_list_reverse,
 };
 use crate::state::{
-    append_atomic_order, bid_key, bids, get_atomic_order, move_order_to_bottom, set_atomic_order, AtomicSwapOrder, Bid, BidKey, BidStatus, Config, FeeInfo, MarketState, Status, CONFIG, COUNT, FEE_INFO, INACTIVE_COUNT, INACTIVE_SWAP_ORDERS, ORDER_TO_COUNT, SWAP_ORDERS, SWAP_SEQUENCE
+    append_atomic_order, bid_key, bids, counter_offers, get_atomic_order, move_order_to_bottom, set_atomic_order, top_bid, AtomicSwapOrder, Bid, BidKey, BidStatus, Config, CounterOffer, CounterOfferStatus, FeeInfo, Fill, MarketState, Status, BID_REFUNDS, CONFIG, COUNT, FEE_INFO, FILLS, INACTIVE_COUNT, INACTIVE_SWAP_ORDERS, KEEPER_REWARDS, ORDER_TO_COUNT, SWAP_ORDERS, SWAP_SEQUENCE
+};
+use crate::utils::{
+    basket_payouts, ensure_no_auction_bids, ensure_not_auction, ensure_not_sealed, is_exact_funds, keeper_fee, maker_fee, partial_fill, place_auction_bid, sealed_bid_commitment, taker_fee, update_order, validate_auction, validate_basket, validate_dutch_auction, validate_sealed_bid, FEE_BASIS_POINT
 };
-use crate::utils::{maker_fee, send_tokens, taker_fee};
 use cw_storage_plus::Bound;
 
 // Version info, for migration info
 const CONTRACT_NAME: &str = "ics100-swap-inchain";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Upper bound on orders reclaimed in one call
+const MAX_RECLAIM_ORDERS: usize = 30;
+// Upper bound on bids refunded in one call
+const MAX_BID_REFUNDS: u32 = 30;
+// Longest reason accepted by RejectBid
+const MAX_REASON_LENGTH: usize = 256;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -35,6 +47,12 @@ pub fn instantiate(
 ) -> StdResult<Response> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     let admin_addr = deps.api.addr_validate(&msg.admin)?;
+    if msg.keeper_fee > FEE_BASIS_POINT {
+        return Err(StdError::generic_err(format!(
+            "Keeper fee can't be more than {} basis points",
+            FEE_BASIS_POINT
+        )));
+    }
     COUNT.save(deps.storage, &0u64)?;
     INACTIVE_COUNT.save(deps.storage, &0u64)?;
     SWAP_SEQUENCE.save(deps.storage, &0u64)?;
@@ -51,6 +69,7 @@ pub fn instantiate(
         maker_fee: msg.maker_fee,
         taker_fee: msg.taker_fee,
         treasury: msg.treasury,
+        keeper_fee: msg.keeper_fee,
     };
     FEE_INFO.save(deps.storage, &fee)?;
 
@@ -64,17 +83,91 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if info.funds.iter().any(|coin| is_cw20_denom(&coin.denom)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cw20 tokens must be sent through Receive".to_string(),
+        )));
+    }
+    if info.funds.iter().any(|coin| is_cw721_denom(&coin.denom)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs must be sent through ReceiveNft".to_string(),
+        )));
+    }
     match msg {
         ExecuteMsg::MakeSwap(msg) => execute_make_swap(deps, env, info, msg),
         ExecuteMsg::TakeSwap(msg) => execute_take_swap(deps, env, info, msg),
         ExecuteMsg::CancelSwap(msg) => execute_cancel_swap(deps, env, info, msg),
+        ExecuteMsg::UpdateSwap(msg) => execute_update_swap(deps, env, info, msg),
         ExecuteMsg::MakeBid(msg) => execute_make_bid(deps, env, info, msg),
         ExecuteMsg::TakeBid(msg) => execute_take_bid(deps, env, info, msg),
         ExecuteMsg::CancelBid(msg) => execute_cancel_bid(deps, env, info, msg),
         ExecuteMsg::UpdateBid(msg) => execute_update_bid(deps, env, info, msg),
+        ExecuteMsg::CommitBid(msg) => execute_commit_bid(deps, env, info, msg),
+        ExecuteMsg::RevealBid(msg) => execute_reveal_bid(deps, env, info, msg),
+        ExecuteMsg::RejectBid(msg) => execute_reject_bid(deps, env, info, msg),
+        ExecuteMsg::CounterOffer(msg) => execute_counter_offer(deps, env, info, msg),
+        ExecuteMsg::AcceptCounterOffer(msg) => execute_accept_counter_offer(deps, env, info, msg),
+        ExecuteMsg::DeclineCounterOffer(msg) => execute_decline_counter_offer(deps, env, info, msg),
+        ExecuteMsg::ReclaimExpired { order_ids } => execute_reclaim_expired(deps, env, info, order_ids),
+        ExecuteMsg::FundKeeperRewards => execute_fund_keeper_rewards(deps, env, info),
+        ExecuteMsg::SettleAuction { order_id } => execute_settle_auction(deps, env, info, order_id),
+        ExecuteMsg::RefundBids { order_id, limit } => execute_refund_bids(deps, env, info, order_id, limit),
         ExecuteMsg::PauseMarket => execute_pause_market(deps, env, info),
         ExecuteMsg::UnpauseMarket => execute_unpause_market(deps, env, info),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
+    }
+}
+
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Receive doesn't accept native funds".to_string(),
+        )));
+    }
+    let msg: ReceiveMsg = from_json(&wrapper.msg)?;
+    // The calling cw20 contract is the token, the wrapped message runs as the cw20 sender
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Asset::cw20(info.sender, wrapper.amount).to_coin()],
+    };
+    match msg {
+        ReceiveMsg::MakeSwap(msg) => execute_make_swap(deps, env, info, msg),
+        ReceiveMsg::TakeSwap(msg) => execute_take_swap(deps, env, info, msg),
+        ReceiveMsg::MakeBid(msg) => execute_make_bid(deps, env, info, msg),
+        ReceiveMsg::UpdateBid(msg) => execute_update_bid(deps, env, info, msg),
+        ReceiveMsg::CommitBid(msg) => execute_commit_bid(deps, env, info, msg),
+        ReceiveMsg::FundKeeperRewards => execute_fund_keeper_rewards(deps, env, info),
+    }
+}
+
+pub fn execute_receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "ReceiveNft doesn't accept native funds".to_string(),
+        )));
     }
+    let ReceiveMsg::MakeSwap(msg) = from_json(&wrapper.msg)? else {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can only be sold with MakeSwap".to_string(),
+        )));
+    };
+    // The calling cw721 contract holds the NFT, the order is made by its previous owner
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Asset::cw721(info.sender, wrapper.token_id).to_coin()],
+    };
+    execute_make_swap(deps, env, info, msg)
 }
 
 pub fn execute_pause_market(
@@ -120,21 +213,21 @@ pub fn execute_make_swap(
         return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
     }
 
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == msg.sell_token.denom && msg.sell_token.amount == asset.amount {
-            ok = true;
-        }
-    }
-    if !ok {
+    // Exactly the tokens sold must be sent, a basket's included
+    if !is_exact_funds(&info.funds, &msg.sell_tokens()) {
         return Err(ContractError::Std(StdError::generic_err(
             "Funds mismatch: Funds mismatched to with message and sent values: Make swap"
                 .to_string(),
         )));
     }
 
+    // An order born expired could be reclaimed right away for a keeper reward
+    if msg.expiration_timestamp <= env.block.time.seconds() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Expiration must be in the future".to_string(),
+        )));
+    }
+
     if let Some(val) = msg.vesting.clone() {
         let mut total_amount = Uint128::from(0u64);
         for schedule in val.schedules {
@@ -147,6 +240,37 @@ pub fn execute_make_swap(
         }
     }
 
+    // NFTs are only sold, as a whole and without vesting
+    if is_cw721_denom(&msg.buy_token.denom) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can only be sold".to_string(),
+        )));
+    }
+    if is_cw721_denom(&msg.sell_token.denom) && (msg.allow_partial || msg.vesting.is_some()) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can't be sold in parts or vested".to_string(),
+        )));
+    }
+
+    validate_basket(&msg)?;
+    validate_auction(&msg, env.block.time.seconds())?;
+    validate_dutch_auction(&msg)?;
+    validate_sealed_bid(&msg, env.block.time.seconds())?;
+
+    if msg.allow_partial {
+        // Bids and vesting are for the whole order
+        if msg.take_bids || msg.vesting.is_some() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Partial fills can't be combined with bids or vesting".to_string(),
+            )));
+        }
+        if msg.min_fill_amount.is_some_and(|min| min > msg.buy_token.amount) {
+            return Err(ContractError::InvalidFillAmount);
+        }
+    } else if msg.min_fill_amount.is_some() {
+        return Err(ContractError::InvalidFillAmount);
+    }
+
     let sequence = SWAP_SEQUENCE.load(deps.storage)?;
 
     let order_id = sequence.to_string();
@@ -160,6 +284,9 @@ pub fn execute_make_swap(
         create_timestamp: env.block.time.seconds(),
         min_bid_price: msg.min_bid_price,
         vesting_details: msg.vesting,
+        filled_sell_amount: Uint128::zero(),
+        filled_buy_amount: Uint128::zero(),
+        fill_count: 0,
     };
     append_atomic_order(deps.storage, &order_id, &new_order)?;
 
@@ -186,31 +313,48 @@ pub fn execute_take_swap(
         return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
     }
 
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == msg.sell_token.denom && msg.sell_token.amount == asset.amount {
-            ok = true;
-        }
-    }
-    if !ok {
+
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    // Exactly the tokens paid must be sent, the rest of a basket included
+    if !is_exact_funds(&info.funds, &order.taker_tokens(&msg.sell_token)) {
         return Err(ContractError::Std(StdError::generic_err(
             "Funds mismatch: Funds mismatched to with message and sent values: Make swap"
                 .to_string(),
         )));
     }
 
-    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
-
     if order.status != Status::Sync {
         return Err(ContractError::OrderTaken);
     }
+    ensure_not_sealed(&order)?;
 
-    // Make sure the maker's buy token matches the taker's sell token
-    if order.maker.buy_token != msg.sell_token {
-        return Err(ContractError::InvalidSellToken);
-    }
+    // Dutch auctions take anything from the current price up, the rest goes back
+    let paid = if order.maker.dutch_auction.is_some() {
+        let price = order.current_price(env.block.time.seconds());
+        if msg.sell_token.denom != price.denom {
+            return Err(ContractError::InvalidSellToken);
+        }
+        if msg.sell_token.amount < price.amount {
+            return Err(ContractError::BelowCurrentPrice { price: price.to_string() });
+        }
+        price
+    } else {
+        msg.sell_token.clone()
+    };
+
+    // Partial orders take a slice of the remaining buy token, others the whole of it
+    let sell_token = if order.maker.allow_partial {
+        partial_fill(&order, &msg.sell_token)?
+    } else if order.maker.dutch_auction.is_some() {
+        order.maker.sell_token.clone()
+    } else {
+        // Make sure the maker's buy token matches the taker's sell token
+        if order.maker.buy_token != msg.sell_token {
+            return Err(ContractError::InvalidSellToken);
+        }
+        order.maker.sell_token.clone()
+    };
 
     // Checks if the order has already been taken
     if let Some(_taker) = order.taker {
@@ -223,7 +367,6 @@ pub fn execute_take_swap(
     }
 
     if env.block.time.seconds() > order.maker.expiration_timestamp {
-        move_order_to_bottom(deps.storage, &msg.order_id)?;
         return Err(ContractError::Expired);
     }
 
@@ -233,18 +376,25 @@ pub fn execute_take_swap(
     // Maker fees
     let (maker_fee, maker_send, treasury) = maker_fee(
         deps.as_ref(),
-        &msg.sell_token.amount,
-        msg.sell_token.denom.clone(),
+        &paid.amount,
+        paid.denom.clone(),
     );
 
-    let mut submsg = vec![send_tokens(&make_address, maker_send)?];
-    submsg.push(send_tokens(&treasury, maker_fee)?);
+    let mut submsg = vec![Asset::from(maker_send).transfer_msg(&make_address)?];
+    submsg.push(Asset::from(maker_fee).transfer_msg(&treasury)?);
+    if msg.sell_token.amount > paid.amount {
+        let surplus = Coin {
+            denom: paid.denom.clone(),
+            amount: msg.sell_token.amount - paid.amount,
+        };
+        submsg.push(Asset::from(surplus).transfer_msg(&info.sender)?);
+    }
 
     // Taker fees
     let (taker_fee, taker_send, treasury) = taker_fee(
         deps.as_ref(),
-        &order.maker.sell_token.amount,
-        order.maker.sell_token.denom.clone(),
+        &sell_token.amount,
+        sell_token.denom.clone(),
     );
     if let Some(val) = order.vesting_details.clone() {
         let cfg = CONFIG.load(deps.storage)?;
@@ -263,28 +413,64 @@ pub fn execute_take_swap(
         };
 
         // log message
-        submsg.push(SubMsg::new(WasmMsg::Execute {
-            contract_addr: cfg.vesting_contract,
-            msg: to_json_binary(&vesting_msg)?,
-            funds: vec![taker_send],
-        }));
+        submsg.push(
+            Asset::from(taker_send).execute_msg(cfg.vesting_contract, to_json_binary(&vesting_msg)?)?,
+        );
     } else {
-        submsg.push(send_tokens(&taker_address, taker_send)?);
+        submsg.push(Asset::from(taker_send).transfer_msg(&taker_address)?);
+    }
+    if !taker_fee.amount.is_zero() {
+        submsg.push(Asset::from(taker_fee).transfer_msg(&treasury)?);
+    }
+    submsg.extend(basket_payouts(deps.as_ref(), &order, &make_address, &taker_address)?);
+
+    let mut res = Response::new();
+    if order.maker.allow_partial {
+        let fill = Fill {
+            taker: msg.taker_address.clone(),
+            sell_token: sell_token.clone(),
+            buy_token: msg.sell_token.clone(),
+            timestamp: env.block.time.seconds(),
+        };
+        FILLS.save(deps.storage, (&order.id, order.fill_count), &fill)?;
+        order.fill_count += 1;
+        order.filled_sell_amount += sell_token.amount;
+        order.filled_buy_amount += msg.sell_token.amount;
+
+        res = res
+            .add_attribute("fill", sell_token.to_string())
+            .add_attribute("remaining", order.remaining_sell().to_string());
+
+        // The order stays open until the last slice is taken
+        if !order.remaining_buy().amount.is_zero() {
+            set_atomic_order(deps.storage, &order.id, &order)?;
+            return Ok(res
+                .add_submessages(submsg)
+                .add_attribute("order_id", msg.order_id)
+                .add_attribute("action", "take_swap"));
+        }
     }
-    submsg.push(send_tokens(&treasury, taker_fee)?);
 
     order.status = Status::Complete;
-    order.taker = Some(msg.clone());
+    order.taker = Some(TakeSwapMsg {
+        sell_token: paid.clone(),
+        ..msg.clone()
+    });
     order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
 
     // Save order
     set_atomic_order(deps.storage, &order.id, &order)?;
     move_order_to_bottom(deps.storage, &msg.order_id)?;
 
-    let res = Response::new()
+    let (refunds, events) = refund_order_bids(deps, &msg.order_id, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+
+    let res = res
         .add_submessages(submsg)
+        .add_events(events)
         .add_attribute("order_id", msg.order_id)
-        .add_attribute("action", "take_swap");
+        .add_attribute("action", "take_swap")
+        .add_attribute("price", paid.to_string());
     Ok(res)
 }
 
@@ -322,22 +508,82 @@ pub fn execute_cancel_swap(
         return Err(ContractError::AlreadyTakenOrder);
     }
 
+    ensure_no_auction_bids(deps.storage, env.block.time.seconds(), &order)?;
+
     let maker_address = deps.api.addr_validate(&order.maker.maker_address)?;
-    let maker_msg = order.maker.clone();
 
-    let submsg = send_tokens(&maker_address, maker_msg.sell_token)?;
+    // Partially filled orders only refund what is left
+    let mut submsg = transfer_msgs(order.remaining_sell_tokens(), &maker_address)?;
 
     order.status = Status::Cancel;
     order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
     set_atomic_order(deps.storage, &msg.order_id, &order)?;
 
+    let (refunds, events) = refund_order_bids(deps, &msg.order_id, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+
     let res = Response::new()
-        .add_submessage(submsg)
+        .add_submessages(submsg)
+        .add_events(events)
         .add_attribute("order_id", msg.order_id)
         .add_attribute("action", "cancel_swap");
     Ok(res)
 }
 
+/// Update swap: changes the price, expiration or bid settings of an untaken order.
+/// The order keeps its id and bids, unless it stops taking them.
+pub fn execute_update_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: UpdateSwapMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    if info.sender != order.maker.maker_address {
+        return Err(ContractError::InvalidSender);
+    }
+
+    if order.status != Status::Sync && order.status != Status::Initial {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    if order.taker.is_some() {
+        return Err(ContractError::AlreadyTakenOrder);
+    }
+
+    if msg
+        .expiration_timestamp
+        .is_some_and(|expiration| expiration <= env.block.time.seconds())
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Expiration must be in the future".to_string(),
+        )));
+    }
+
+    let bids_closed = order.maker.take_bids && msg.take_bids == Some(false);
+    update_order(&mut order, &msg)?;
+    set_atomic_order(deps.storage, &msg.order_id, &order)?;
+
+    let (submsg, events) = if bids_closed {
+        refund_order_bids(deps, &msg.order_id, MAX_BID_REFUNDS)?
+    } else {
+        (vec![], vec![])
+    };
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "update_swap");
+    Ok(res)
+}
+
 /// Make bid: Use it to make bid from taker chain
 /// For each order each user can create atmost 1 bid(they can cancel and recreate it)
 /// Panics id bid is already taken
@@ -354,7 +600,7 @@ pub fn execute_make_bid(
     }
 
     let sender = info.sender.to_string();
-    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
     // check if given tokens are received here
     let mut ok = false;
     // First token in this chain only first token needs to be verified
@@ -383,6 +629,7 @@ pub fn execute_make_bid(
     if !order.maker.take_bids {
         return Err(ContractError::TakeBidNotAllowed);
     }
+    ensure_not_sealed(&order)?;
 
     // Make sure the maker's buy token matches the taker's sell token
     if order.maker.buy_token.denom != msg.sell_token.denom {
@@ -390,7 +637,7 @@ pub fn execute_make_bid(
     }
 
     // Checks if the order has already been taken
-    if let Some(_taker) = order.taker {
+    if order.taker.is_some() {
         return Err(ContractError::OrderTaken);
     }
 
@@ -405,6 +652,16 @@ pub fn execute_make_bid(
         }
     }
 
+    if place_auction_bid(
+        deps.storage,
+        env.block.time.seconds(),
+        &mut order,
+        &msg.taker_address,
+        msg.sell_token.amount,
+    )? {
+        set_atomic_order(deps.storage, &msg.order_id, &order)?;
+    }
+
     let bid: Bid = Bid {
         bid: msg.sell_token.clone(),
         order: msg.order_id.clone(),
@@ -412,6 +669,7 @@ pub fn execute_make_bid(
         bidder: msg.taker_address.clone(),
         receive_timestamp: env.block.time.seconds(),
         expire_timestamp: msg.expiration_timestamp,
+        commitment: None,
     };
 
     bids().save(deps.storage, key, &bid)?;
@@ -439,14 +697,15 @@ pub fn execute_take_bid(
     }
 
     let sender = info.sender.to_string();
-    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
 
     if !order.maker.take_bids {
         return Err(ContractError::TakeBidNotAllowed);
     }
+    ensure_not_auction(&order)?;
 
     // Checks if the order has already been taken
-    if let Some(_taker) = order.taker {
+    if order.taker.is_some() {
         return Err(ContractError::OrderTaken);
     }
 
@@ -463,7 +722,7 @@ pub fn execute_take_bid(
         return Err(ContractError::BidDoesntExist);
     }
 
-    let bid = bids().load(deps.storage, key)?;
+    let bid = bids().load(deps.storage, key.clone())?;
     if bid.status != BidStatus::Placed {
         return Err(ContractError::BidDoesntExist);
     }
@@ -472,6 +731,27 @@ pub fn execute_take_bid(
         return Err(ContractError::Expired);
     }
 
+    let (submsg, events) = sell_to_bid(deps, &env, order, key, bid)?;
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "take_bid");
+    Ok(res)
+}
+
+// Sells the order to a placed bid and refunds the other bids
+fn sell_to_bid(
+    deps: DepsMut,
+    env: &Env,
+    mut order: AtomicSwapOrder,
+    key: BidKey,
+    mut bid: Bid,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    bid.status = BidStatus::Executed;
+    bids().save(deps.storage, key, &bid)?;
+
     let maker_address = deps.api.addr_validate(&order.maker.maker_address)?;
     let taker_receiving_address = deps.api.addr_validate(&bid.bidder)?;
 
@@ -479,8 +759,8 @@ pub fn execute_take_bid(
     let (maker_fee, maker_send, treasury) =
         maker_fee(deps.as_ref(), &bid.bid.amount, bid.bid.denom.clone());
 
-    let mut submsg = vec![send_tokens(&maker_address, maker_send)?];
-    submsg.push(send_tokens(&treasury, maker_fee)?);
+    let mut submsg = vec![Asset::from(maker_send).transfer_msg(&maker_address)?];
+    submsg.push(Asset::from(maker_fee).transfer_msg(&treasury)?);
 
     // Taker fees
     let (taker_fee, taker_send, treasury) = taker_fee(
@@ -505,15 +785,15 @@ pub fn execute_take_bid(
         };
 
         // log message
-        submsg.push(SubMsg::new(WasmMsg::Execute {
-            contract_addr: cfg.vesting_contract,
-            msg: to_json_binary(&vesting_msg)?,
-            funds: vec![taker_send],
-        }));
+        submsg.push(
+            Asset::from(taker_send).execute_msg(cfg.vesting_contract, to_json_binary(&vesting_msg)?)?,
+        );
     } else {
-        submsg.push(send_tokens(&taker_receiving_address, taker_send)?);
+        submsg.push(Asset::from(taker_send).transfer_msg(&taker_receiving_address)?);
+    }
+    if !taker_fee.amount.is_zero() {
+        submsg.push(Asset::from(taker_fee).transfer_msg(&treasury)?);
     }
-    submsg.push(send_tokens(&treasury, taker_fee)?);
 
     let take_msg: TakeSwapMsg = TakeSwapMsg {
         order_id: order.id.clone(),
@@ -524,13 +804,179 @@ pub fn execute_take_bid(
     order.taker = Some(take_msg);
     order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
 
-    set_atomic_order(deps.storage, &msg.order_id, &order)?;
-    move_order_to_bottom(deps.storage, &msg.order_id)?;
+    set_atomic_order(deps.storage, &order.id, &order)?;
+    move_order_to_bottom(deps.storage, &order.id)?;
+
+    let (refunds, events) = refund_order_bids(deps, &order.id, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+
+    Ok((submsg, events))
+}
+
+/// Settle auction: sells an ended auction to its highest placed bid, the other bids are
+/// refunded. Anyone can call it.
+pub fn execute_settle_auction(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    order_id: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let order = get_atomic_order(deps.storage, &order_id)?;
+    let end_time = match (&order.maker.auction, &order.maker.sealed_bid) {
+        (Some(auction), _) => auction.end_time,
+        (_, Some(sealed)) => sealed.reveal_end,
+        _ => return Err(ContractError::NotAuction),
+    };
+
+    if order.status != Status::Sync || order.taker.is_some() {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    if env.block.time.seconds() < end_time {
+        return Err(ContractError::AuctionNotEnded);
+    }
+
+    // Bid expirations don't apply, auction bids stand until the auction settles
+    let (key, bid) = top_bid(deps.storage, &order_id, |bid| bid.status == BidStatus::Placed)?
+        .ok_or(ContractError::NoAuctionBids)?;
+    let bidder = bid.bidder.clone();
+    let (submsg, events) = sell_to_bid(deps, &env, order, key, bid)?;
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "settle_auction")
+        .add_attribute("bidder", bidder);
+    Ok(res)
+}
+
+/// Commit bid: escrows a deposit with the hash of a sealed bid, until bidding closes
+pub fn execute_commit_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CommitBidMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let sealed = order.maker.sealed_bid.clone().ok_or(ContractError::NotAuction)?;
+
+    if order.status != Status::Sync || order.taker.is_some() {
+        return Err(ContractError::InvalidStatus);
+    }
+    if env.block.time.seconds() >= sealed.commit_end {
+        return Err(ContractError::AuctionEnded);
+    }
+
+    // The deposit hides the bid, as long as it is above it
+    let deposit = match &info.funds[..] {
+        [deposit] if deposit.denom == order.maker.buy_token.denom && !deposit.amount.is_zero() => deposit.clone(),
+        _ => {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Funds mismatch: Send one deposit in the buy token: Commit bid".to_string(),
+            )))
+        }
+    };
+    if hex::decode(&msg.commitment).map_or(true, |hash| hash.len() != 32) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Commitment must be a hex encoded sha256 hash".to_string(),
+        )));
+    }
+
+    let bidder = info.sender.to_string();
+    let key = bid_key(&msg.order_id, &bidder);
+    if let Some(bid) = bids().may_load(deps.storage, key.clone())? {
+        if matches!(bid.status, BidStatus::Initial | BidStatus::Placed | BidStatus::Committed) {
+            return Err(ContractError::BidAlreadyExist {});
+        }
+    }
+
+    let bid = Bid {
+        bid: deposit,
+        order: msg.order_id.clone(),
+        status: BidStatus::Committed,
+        bidder: bidder.clone(),
+        receive_timestamp: env.block.time.seconds(),
+        expire_timestamp: sealed.reveal_end,
+        commitment: Some(msg.commitment),
+    };
+    bids().save(deps.storage, key, &bid)?;
+
+    let res = Response::new()
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "commit_bid")
+        .add_attribute("bidder", bidder);
+    Ok(res)
+}
+
+/// Reveal bid: opens a committed bid once bidding closes, the deposit above it is refunded.
+/// Bids left sealed at the end of the reveal are forfeited to the treasury.
+pub fn execute_reveal_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: RevealBidMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let sealed = order.maker.sealed_bid.clone().ok_or(ContractError::NotAuction)?;
+
+    let now = env.block.time.seconds();
+    if now < sealed.commit_end || now >= sealed.reveal_end {
+        return Err(ContractError::NotRevealPhase);
+    }
+
+    let bidder = info.sender.to_string();
+    let key = bid_key(&msg.order_id, &bidder);
+    let mut bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .filter(|bid| bid.status == BidStatus::Committed)
+        .ok_or(ContractError::BidDoesntExist)?;
+
+    let commitment = sealed_bid_commitment(&msg.order_id, &bidder, msg.amount, &msg.salt);
+    if bid.commitment.as_ref() != Some(&commitment) {
+        return Err(ContractError::InvalidCommitment);
+    }
+    if msg.amount.is_zero()
+        || msg.amount > bid.bid.amount
+        || order.min_bid_price.is_some_and(|min| msg.amount < min)
+    {
+        return Err(ContractError::InvalidBidAmount);
+    }
+
+    let mut submsg = vec![];
+    if bid.bid.amount > msg.amount {
+        let surplus = Coin {
+            denom: bid.bid.denom.clone(),
+            amount: bid.bid.amount - msg.amount,
+        };
+        submsg.push(Asset::from(surplus).transfer_msg(&info.sender)?);
+    }
+    bid.bid.amount = msg.amount;
+    bid.status = BidStatus::Placed;
+    bid.commitment = None;
+    bids().save(deps.storage, key, &bid)?;
 
     let res = Response::new()
         .add_submessages(submsg)
         .add_attribute("order_id", msg.order_id)
-        .add_attribute("action", "take_bid");
+        .add_attribute("action", "reveal_bid")
+        .add_attribute("bidder", bidder)
+        .add_attribute("amount", bid.bid.to_string());
     Ok(res)
 }
 
@@ -546,7 +992,8 @@ pub fn execute_update_bid(
     }
 
     let bidder = info.sender.to_string();
-    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+    ensure_not_sealed(&order)?;
 
     // check if given tokens are received here
     let mut ok = false;
@@ -581,16 +1028,23 @@ pub fn execute_update_bid(
         return Err(ContractError::InvalidBidAmount);
     }
 
-    if bid.bid.amount + msg.addition > order.maker.buy_token.amount {
+    // Auctions have no ceiling
+    if order.maker.auction.is_none() && bid.bid.amount + msg.addition > order.maker.buy_token.amount {
         return Err(ContractError::InvalidBidAmount);
     }
 
     bid.bid.amount += msg.addition;
+    if place_auction_bid(deps.storage, env.block.time.seconds(), &mut order, &bidder, bid.bid.amount)? {
+        set_atomic_order(deps.storage, &msg.order_id, &order)?;
+    }
     bids().save(deps.storage, key, &bid)?;
 
-    let res = Response::new()
+    let mut res = Response::new()
         .add_attribute("order_id", msg.order_id)
         .add_attribute("action", "update_bid");
+    if accept_counter_offer(deps.storage, &env, &bid)? {
+        res = res.add_attribute("counter_offer", "accepted");
+    }
     Ok(res)
 }
 
@@ -611,6 +1065,7 @@ pub fn execute_cancel_bid(
 
     let sender = info.sender.to_string();
     let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    ensure_not_sealed(&order)?;
 
     if !order.maker.take_bids {
         return Err(ContractError::TakeBidNotAllowed);
@@ -621,14 +1076,27 @@ pub fn execute_cancel_bid(
         return Err(ContractError::BidDoesntExist);
     }
     let mut bid = bids().load(deps.storage, key.clone())?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
 
     if sender != msg.bidder {
         return Err(ContractError::InvalidSender);
     }
 
+    // The highest bid of an auction stands until it is outbid or settled
+    if order.maker.auction.is_some() {
+        let highest = top_bid(deps.storage, &msg.order_id, |bid| bid.status == BidStatus::Placed)?;
+        if highest.is_some_and(|(highest, _)| highest == key) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The highest bid of an auction can't be cancelled".to_string(),
+            )));
+        }
+    }
+
     let taker_receiving_address = deps.api.addr_validate(&bid.bidder)?;
     // Refund amount
-    let submsg = send_tokens(&taker_receiving_address, bid.bid.clone())?;
+    let submsg = Asset::from(bid.bid.clone()).transfer_msg(&taker_receiving_address)?;
 
     bid.status = BidStatus::Cancelled;
     bids().save(deps.storage, key, &bid)?;
@@ -640,6 +1108,394 @@ pub fn execute_cancel_bid(
     Ok(res)
 }
 
+/// Reject Bid: Only the maker can reject a bid for their order
+/// The bid is refunded and closed
+pub fn execute_reject_bid(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: RejectBidMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    if info.sender != order.maker.maker_address {
+        return Err(ContractError::InvalidSender);
+    }
+    ensure_not_auction(&order)?;
+
+    let reason = msg.reason.unwrap_or_default();
+    if reason.len() > MAX_REASON_LENGTH {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Reason must not be longer than {} bytes",
+            MAX_REASON_LENGTH
+        ))));
+    }
+
+    let key = bid_key(&msg.order_id, &msg.bidder);
+    let mut bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+
+    let bidder = deps.api.addr_validate(&bid.bidder)?;
+    let submsg = Asset::from(bid.bid.clone()).transfer_msg(&bidder)?;
+
+    bid.status = BidStatus::Rejected;
+    bids().save(deps.storage, key, &bid)?;
+
+    let res = Response::new()
+        .add_submessage(submsg)
+        .add_event(
+            Event::new("bid_rejected")
+                .add_attribute("order_id", msg.order_id.clone())
+                .add_attribute("bidder", msg.bidder)
+                .add_attribute("amount", bid.bid.to_string())
+                .add_attribute("reason", reason),
+        )
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "reject_bid");
+    Ok(res)
+}
+
+/// Counter offer: the maker answers a placed bid with the total amount they would accept
+/// A new counter offer replaces the previous one for the same bid
+pub fn execute_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CounterOfferMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    if info.sender != order.maker.maker_address {
+        return Err(ContractError::InvalidSender);
+    }
+    ensure_not_auction(&order)?;
+
+    if order.taker.is_some() {
+        return Err(ContractError::OrderTaken);
+    }
+
+    let key = bid_key(&msg.order_id, &msg.bidder);
+    let bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+
+    // Accepting tops up the bid, so the price can only be above the bid
+    if msg.price <= bid.bid.amount || msg.price > order.maker.buy_token.amount {
+        return Err(ContractError::InvalidBidAmount);
+    }
+
+    if env.block.time.seconds() >= msg.expiration_timestamp {
+        return Err(ContractError::Expired);
+    }
+
+    let round = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .map_or(0, |offer| offer.round)
+        + 1;
+    let offer = CounterOffer {
+        order: msg.order_id.clone(),
+        bidder: msg.bidder.clone(),
+        price: msg.price,
+        expire_timestamp: msg.expiration_timestamp,
+        status: CounterOfferStatus::Open,
+        round,
+        update_timestamp: env.block.time.seconds(),
+    };
+    counter_offers().save(deps.storage, key, &offer)?;
+
+    let res = Response::new()
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("bidder", msg.bidder)
+        .add_attribute("price", msg.price)
+        .add_attribute("round", round.to_string())
+        .add_attribute("action", "counter_offer");
+    Ok(res)
+}
+
+/// Accept counter offer: the bidder tops up their bid to the counter offer price
+/// Funds must cover the difference, the maker can then take the bid
+pub fn execute_accept_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CounterOfferActionMsg,
+) -> Result<Response, ContractError> {
+    let key = bid_key(&msg.order_id, &info.sender.to_string());
+    let offer = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::CounterOfferDoesntExist)?;
+    if offer.status != CounterOfferStatus::Open {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    if env.block.time.seconds() > offer.expire_timestamp {
+        return Err(ContractError::Expired);
+    }
+
+    let bid = bids()
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::BidDoesntExist)?;
+    let addition = offer
+        .price
+        .checked_sub(bid.bid.amount)
+        .map_err(StdError::from)?;
+
+    let res = execute_update_bid(
+        deps,
+        env,
+        info,
+        UpdateBidMsg {
+            order_id: msg.order_id,
+            addition,
+        },
+    )?;
+    Ok(res.add_attribute("action", "accept_counter_offer"))
+}
+
+/// Decline counter offer: the bidder closes the counter offer, the bid stays placed
+pub fn execute_decline_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CounterOfferActionMsg,
+) -> Result<Response, ContractError> {
+    let key = bid_key(&msg.order_id, &info.sender.to_string());
+    let mut offer = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::CounterOfferDoesntExist)?;
+    if offer.status != CounterOfferStatus::Open {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    offer.status = CounterOfferStatus::Declined;
+    offer.update_timestamp = env.block.time.seconds();
+    counter_offers().save(deps.storage, key, &offer)?;
+
+    let res = Response::new()
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("bidder", info.sender)
+        .add_attribute("action", "decline_counter_offer");
+    Ok(res)
+}
+
+// Marks an open counter offer accepted once the bid reaches its price.
+fn accept_counter_offer(storage: &mut dyn Storage, env: &Env, bid: &Bid) -> StdResult<bool> {
+    let key = bid_key(&bid.order, &bid.bidder);
+    let Some(mut offer) = counter_offers().may_load(storage, key.clone())? else {
+        return Ok(false);
+    };
+    if offer.status != CounterOfferStatus::Open
+        || env.block.time.seconds() > offer.expire_timestamp
+        || bid.bid.amount < offer.price
+    {
+        return Ok(false);
+    }
+
+    offer.status = CounterOfferStatus::Accepted;
+    offer.update_timestamp = env.block.time.seconds();
+    counter_offers().save(storage, key, &offer)?;
+    Ok(true)
+}
+
+/// Reclaim expired: anyone can close expired orders
+/// Refunds the maker's sell token and every placed bid, then archives the order
+/// The caller gets `keeper_fee` basis points of the maker refund out of KEEPER_REWARDS
+pub fn execute_reclaim_expired(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
+    }
+
+    if order_ids.is_empty() || order_ids.len() > MAX_RECLAIM_ORDERS {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Number of orders must be between 1 and {}",
+            MAX_RECLAIM_ORDERS
+        ))));
+    }
+
+    let mut submsg: Vec<SubMsg> = vec![];
+    let mut events: Vec<Event> = vec![];
+    for order_id in order_ids {
+        let mut order = get_atomic_order(deps.storage, &order_id)?;
+
+        if order.status != Status::Sync || order.taker.is_some() {
+            return Err(ContractError::InvalidStatus);
+        }
+
+        if env.block.time.seconds() <= order.maker.expiration_timestamp {
+            return Err(ContractError::NotExpired);
+        }
+        // Expired auctions with bids are settled instead
+        ensure_no_auction_bids(deps.storage, env.block.time.seconds(), &order)?;
+
+        let maker_address = deps.api.addr_validate(&order.maker.maker_address)?;
+        for refund in order.remaining_sell_tokens() {
+            submsg.push(Asset::from(refund.clone()).transfer_msg(&maker_address)?);
+            events.push(reclaim_refund_event(&order_id, &maker_address, &refund));
+            // Makers reclaiming their own orders earn no reward
+            if info.sender == maker_address {
+                continue;
+            }
+            let reward = keeper_fee(deps.storage, &refund)?;
+            if !reward.amount.is_zero() {
+                submsg.push(Asset::from(reward.clone()).transfer_msg(&info.sender)?);
+                events.push(reclaim_refund_event(&order_id, &info.sender, &reward));
+            }
+        }
+
+        order.status = Status::Cancel;
+        order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+        set_atomic_order(deps.storage, &order_id, &order)?;
+        move_order_to_bottom(deps.storage, &order_id)?;
+
+        let (refunds, bid_events) = refund_order_bids(deps.branch(), &order_id, MAX_BID_REFUNDS)?;
+        submsg.extend(refunds);
+        events.extend(bid_events);
+    }
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("action", "reclaim_expired");
+    Ok(res)
+}
+
+/// Fund keeper rewards: the treasury pays in the rewards of ReclaimExpired
+pub fn execute_fund_keeper_rewards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let fee_info = FEE_INFO.load(deps.storage)?;
+    if info.sender != fee_info.treasury {
+        return Err(ContractError::Std(StdError::generic_err("only treasury allowed".to_string())));
+    }
+    if info.funds.is_empty() {
+        return Err(ContractError::EmptyBalance {});
+    }
+
+    for fund in info.funds {
+        KEEPER_REWARDS.update(deps.storage, &fund.denom, |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default() + fund.amount)
+        })?;
+    }
+
+    Ok(Response::new().add_attribute("action", "fund_keeper_rewards"))
+}
+
+fn reclaim_refund_event(order_id: &str, to: &Addr, amount: &Coin) -> Event {
+    Event::new("reclaim_refund")
+        .add_attribute("order_id", order_id)
+        .add_attribute("receiver", to.to_string())
+        .add_attribute("amount", amount.to_string())
+}
+
+/// Refund bids: anyone can continue refunding the bids of a closed order
+/// Only needed when the order had more placed bids than one call refunds
+pub fn execute_refund_bids(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    order_id: String,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    if !BID_REFUNDS.has(deps.storage, &order_id) {
+        return Err(ContractError::NoBidsToRefund);
+    }
+
+    let limit = limit.unwrap_or(MAX_BID_REFUNDS).clamp(1, MAX_BID_REFUNDS);
+    let (submsg, events) = refund_order_bids(deps, &order_id, limit)?;
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "refund_bids");
+    Ok(res)
+}
+
+// Refunds up to `limit` placed bids of a closed order, or one that stopped taking bids.
+// The rest are queued in BID_REFUNDS for RefundBids.
+fn refund_order_bids(
+    deps: DepsMut,
+    order_id: &str,
+    limit: u32,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let limit = limit as usize;
+    let open = |status: BidStatus| {
+        bids()
+            .idx
+            .order_status
+            .prefix((order_id.to_string(), status as u8))
+            .range(deps.storage, None, None, Order::Ascending)
+    };
+    let mut placed = open(BidStatus::Placed)
+        .chain(open(BidStatus::Committed))
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut events: Vec<Event> = vec![];
+    if placed.len() > limit {
+        placed.truncate(limit);
+        BID_REFUNDS.save(deps.storage, order_id, &Empty {})?;
+        events.push(Event::new("bid_refunds_queued").add_attribute("order_id", order_id));
+    } else {
+        BID_REFUNDS.remove(deps.storage, order_id);
+    }
+
+    let mut submsg: Vec<SubMsg> = vec![];
+    for (key, mut bid) in placed {
+        // Sealed bids are only left when the reveal is over, see execute_cancel_swap
+        if bid.status == BidStatus::Committed {
+            let treasury = deps.api.addr_validate(&FEE_INFO.load(deps.storage)?.treasury)?;
+            submsg.push(Asset::from(bid.bid.clone()).transfer_msg(&treasury)?);
+            events.push(
+                Event::new("bid_forfeit")
+                    .add_attribute("order_id", order_id)
+                    .add_attribute("bidder", bid.bidder.clone())
+                    .add_attribute("amount", bid.bid.to_string()),
+            );
+            bid.status = BidStatus::Forfeited;
+            bids().save(deps.storage, key, &bid)?;
+            continue;
+        }
+        let bidder = deps.api.addr_validate(&bid.bidder)?;
+        submsg.push(Asset::from(bid.bid.clone()).transfer_msg(&bidder)?);
+        events.push(
+            Event::new("bid_refund")
+                .add_attribute("order_id", order_id)
+                .add_attribute("bidder", bid.bidder.clone())
+                .add_attribute("amount", bid.bid.to_string()),
+        );
+        bid.status = BidStatus::Refunded;
+        bids().save(deps.storage, key, &bid)?;
+    }
+
+    Ok((submsg, events))
+}
+
 #[entry_point]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     let ver = cw2::get_contract_version(deps.storage)?;
@@ -659,7 +1515,7 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, C
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::List {
             start_after,
@@ -749,6 +1605,25 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             start_after,
             limit,
         )?),
+        QueryMsg::CounterOfferDetails { order, bidder } => {
+            to_json_binary(&query_counter_offer(deps, order, bidder)?)
+        }
+        QueryMsg::CounterOffersByOrder {
+            order,
+            start_after,
+            limit,
+        } => to_json_binary(&query_counter_offers_by_order(deps, order, start_after, limit)?),
+        QueryMsg::CounterOffersByBidder {
+            bidder,
+            start_after,
+            limit,
+        } => to_json_binary(&query_counter_offers_by_bidder(deps, bidder, start_after, limit)?),
+        QueryMsg::Fills {
+            order_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_fills(deps, order_id, start_after, limit)?),
+        QueryMsg::CurrentPrice { order_id } => to_json_binary(&query_current_price(deps, env, order_id)?),
 
         // Inactive fields
         QueryMsg::InactiveList {
@@ -1118,6 +1993,83 @@ pub fn query_bids_by_bidder(
     Ok(BidsResponse { bids })
 }
 
+pub fn query_counter_offer(deps: Deps, order: String, bidder: String) -> StdResult<CounterOffersResponse> {
+    let offer = counter_offers().may_load(deps.storage, bid_key(&order, &bidder))?;
+
+    Ok(CounterOffersResponse {
+        offers: offer.into_iter().collect(),
+    })
+}
+
+pub fn query_counter_offers_by_order(
+    deps: Deps,
+    order: String,
+    start_after: Option<String>, // bidder
+    limit: Option<u32>,
+) -> StdResult<CounterOffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(Bound::exclusive);
+
+    let offers = counter_offers()
+        .prefix(order)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(CounterOffersResponse { offers })
+}
+
+pub fn query_counter_offers_by_bidder(
+    deps: Deps,
+    bidder: String,
+    start_after: Option<String>, // order
+    limit: Option<u32>,
+) -> StdResult<CounterOffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(|start| Bound::exclusive(bid_key(&start, &bidder)));
+
+    let offers = counter_offers()
+        .idx
+        .bidder
+        .prefix(bidder)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(CounterOffersResponse { offers })
+}
+
+pub fn query_fills(
+    deps: Deps,
+    order_id: String,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<FillsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(Bound::exclusive);
+
+    let fills = FILLS
+        .prefix(&order_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, fill)| fill))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(FillsResponse { fills })
+}
+
+pub fn query_current_price(deps: Deps, env: Env, order_id: String) -> StdResult<CurrentPriceResponse> {
+    let order = get_atomic_order(deps.storage, &order_id)?;
+    Ok(CurrentPriceResponse {
+        price: order.current_price(env.block.time.seconds()),
+    })
+}
+
 // Inactive fields
 
 fn query_inactive_list(
@@ -1263,10 +2215,13 @@ fn query_inactive_list_by_taker(
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, from_json, Coin, StdError, Uint128};
+    use cosmwasm_std::{attr, coin, from_json, Coin, StdError, Uint128, WasmMsg};
 
-    use crate::msg::TakeSwapMsgOutput;
-    use crate::utils::{generate_order_id, order_path};
+    use crate::msg::{
+        AuctionConfig, DutchAuctionConfig, SealedBidConfig, ReleaseInterval, TakeSwapMsgOutput,
+        VestingDetail,
+    };
+    use crate::utils::{generate_order_id, order_path, sealed_bid_commitment};
 
     use super::*;
 
@@ -1280,7 +2235,8 @@ mod tests {
             treasury: "tre".to_string(),
             vesting_contract: "vesting-address".to_string(),
             maker_fee: 100,
-            taker_fee: 100
+            taker_fee: 100,
+            keeper_fee: 0,
         };
         let info = mock_info("anyone", &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
@@ -1297,7 +2253,8 @@ mod tests {
             treasury: "tre".to_string(),
             vesting_contract: "vesting-address".to_string(),
             maker_fee: 100,
-            taker_fee: 100
+            taker_fee: 100,
+            keeper_fee: 0,
         };
         let info = mock_info("anyone", &[]);
         let res = instantiate(deps.as_mut(), mock_env(), info, instantiate_msg).unwrap();
@@ -1315,6 +2272,7 @@ mod tests {
             bidder: bidder.clone(),
             receive_timestamp: 10,
             expire_timestamp: 100,
+            commitment: None,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid)
@@ -1347,6 +2305,7 @@ mod tests {
             bidder: bidder.clone(),
             receive_timestamp: 20,
             expire_timestamp: 100,
+            commitment: None,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid1)
@@ -1378,6 +2337,7 @@ mod tests {
             bidder: bidder.clone(),
             receive_timestamp: 30,
             expire_timestamp: 100,
+            commitment: None,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid2)
@@ -1431,6 +2391,7 @@ mod tests {
             bidder: bidder.clone(),
             receive_timestamp: 40,
             expire_timestamp: 100,
+            commitment: None,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid3)
@@ -1467,7 +2428,8 @@ mod tests {
                 treasury: "tre".to_string(),
                 vesting_contract: "vesting-address".to_string(),
                 maker_fee: 100,
-                taker_fee: 100
+                taker_fee: 100,
+                keeper_fee: 0,
             },
         )
         .unwrap();
@@ -1488,6 +2450,13 @@ mod tests {
             take_bids: false,
             min_bid_price: None,
             vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
         };
         let err = execute(deps.as_mut(), env, info, ExecuteMsg::MakeSwap(create)).unwrap_err();
         assert_eq!(err, ContractError::EmptyBalance {});
@@ -1508,7 +2477,8 @@ mod tests {
                 treasury: "tre".to_string(),
                 vesting_contract: "vesting-address".to_string(),
                 maker_fee: 100,
-                taker_fee: 100
+                taker_fee: 100,
+                keeper_fee: 0,
             },
         )
         .unwrap();
@@ -1532,6 +2502,13 @@ mod tests {
             take_bids: false,
             min_bid_price: None,
             vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
         };
 
         let path = order_path(
@@ -1547,6 +2524,1115 @@ mod tests {
         println!("order_id is {:?}", &order_id);
     }
 
+    #[test]
+    fn test_reclaim_expired() {
+        let mut deps = mock_dependencies();
+
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 100,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        let expired = MakeSwapMsg { expiration_timestamp: env.block.time.seconds(), ..create.clone() };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::MakeSwap(expired)).unwrap_err();
+        assert_eq!(err, ContractError::Std(StdError::generic_err("Expiration must be in the future")));
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create.clone())).unwrap();
+
+        let bid = MakeBidMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(150, "token2"),
+            taker_address: "bidder0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+        };
+        let info = mock_info("bidder0001", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap();
+
+        let reclaim = ExecuteMsg::ReclaimExpired { order_ids: vec!["0".to_string()] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), reclaim.clone()).unwrap_err();
+        assert_eq!(err, ContractError::NotExpired);
+
+        // Rewards are funded by the treasury, up to what it funded
+        let fund = ExecuteMsg::FundKeeperRewards;
+        let info = mock_info("maker0001", &[coin(5, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, fund.clone()).unwrap_err();
+        let info = mock_info("tre", &[coin(5, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, fund).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), reclaim).unwrap();
+        let expected = vec![
+            Asset::from(coin(1000, "token1")).transfer_msg(&Addr::unchecked("maker0001")).unwrap(),
+            Asset::from(coin(5, "token1")).transfer_msg(&Addr::unchecked("keeper")).unwrap(),
+            Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap(),
+        ];
+        assert_eq!(res.messages, expected);
+
+        // The order is archived and the bid closed
+        assert!(get_atomic_order(&deps.storage, "0").is_err());
+        let order = INACTIVE_SWAP_ORDERS.load(&deps.storage, 0).unwrap();
+        assert_eq!(order.status, Status::Cancel);
+        let bid = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0001".to_string())).unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+        assert!(KEEPER_REWARDS.load(&deps.storage, "token1").unwrap().is_zero());
+
+        // A maker reclaiming their own order gets no reward
+        let info = mock_info("tre", &[coin(5, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::FundKeeperRewards).unwrap();
+        let create = MakeSwapMsg { expiration_timestamp: env.block.time.plus_seconds(100).seconds(), ..create };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+        env.block.time = env.block.time.plus_seconds(101);
+        let reclaim = ExecuteMsg::ReclaimExpired { order_ids: vec!["1".to_string()] };
+        let res = execute(deps.as_mut(), env, mock_info("maker0001", &[]), reclaim).unwrap();
+        let expected =
+            vec![Asset::from(coin(1000, "token1")).transfer_msg(&Addr::unchecked("maker0001")).unwrap()];
+        assert_eq!(res.messages, expected);
+        assert_eq!(KEEPER_REWARDS.load(&deps.storage, "token1").unwrap(), Uint128::new(5));
+
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 10001,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err, StdError::generic_err("Keeper fee can't be more than 10000 basis points"));
+    }
+
+    #[test]
+    fn test_partial_fill() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: true,
+            min_fill_amount: Some(Uint128::from(50u64)),
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let take = |amount: u128, taker: &str| {
+            ExecuteMsg::TakeSwap(TakeSwapMsg {
+                order_id: "0".to_string(),
+                sell_token: coin(amount, "token2"),
+                taker_address: taker.to_string(),
+            })
+        };
+        let info = mock_info("taker0001", &[coin(30, "token2")]);
+        let err = execute(deps.as_mut(), env.clone(), info, take(30, "taker0001")).unwrap_err();
+        assert_eq!(err, ContractError::InvalidFillAmount);
+
+        let info = mock_info("taker0001", &[coin(50, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, take(50, "taker0001")).unwrap();
+        let order = get_atomic_order(&deps.storage, "0").unwrap();
+        assert_eq!(order.status, Status::Sync);
+        assert_eq!(order.remaining_sell(), coin(750, "token1"));
+        assert_eq!(order.remaining_buy(), coin(150, "token2"));
+
+        // The last fill takes the rest
+        let info = mock_info("taker0002", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, take(150, "taker0002")).unwrap();
+        assert!(get_atomic_order(&deps.storage, "0").is_err());
+        let order = INACTIVE_SWAP_ORDERS.load(&deps.storage, 0).unwrap();
+        assert_eq!(order.status, Status::Complete);
+
+        let res: FillsResponse = from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::Fills {
+                    order_id: "0".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.fills.len(), 2);
+        assert_eq!(res.fills[0].sell_token, coin(250, "token1"));
+        assert_eq!(res.fills[1].sell_token, coin(750, "token1"));
+        assert_eq!(res.fills[1].taker, "taker0002");
+    }
+
+    #[test]
+    fn test_cw20_swap() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 0,
+                taker_fee: 0,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "cw20:token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+
+        // Cw20 denoms can't be attached as native funds
+        let info = mock_info("maker0001", &[coin(1000, "cw20:token1")]);
+        let err = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create.clone())).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("Cw20 tokens must be sent through Receive"))
+        );
+
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "maker0001".to_string(),
+            amount: Uint128::from(1000u64),
+            msg: to_json_binary(&ReceiveMsg::MakeSwap(create)).unwrap(),
+        });
+        execute(deps.as_mut(), env.clone(), mock_info("token1", &[]), receive).unwrap();
+        let order = get_atomic_order(&deps.storage, "0").unwrap();
+        assert_eq!(order.maker.maker_address, "maker0001");
+
+        let info = mock_info("taker0001", &[coin(200, "token2")]);
+        let take = ExecuteMsg::TakeSwap(TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(200, "token2"),
+            taker_address: "taker0001".to_string(),
+        });
+        let res = execute(deps.as_mut(), env, info, take).unwrap();
+        // The taker is paid and the fee is taken in the cw20 token
+        let transfer = |recipient: &str, amount: u128| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token1".to_string(),
+                msg: to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::from(amount),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+        assert!(res.messages.contains(&transfer("taker0001", 999)));
+        assert!(res.messages.contains(&transfer("tre", 1)));
+    }
+
+    #[test]
+    fn test_nft_swap() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1, "cw721:nft/7"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let receive = |msg: &MakeSwapMsg| {
+            ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: "maker0001".to_string(),
+                token_id: "7".to_string(),
+                msg: to_json_binary(&ReceiveMsg::MakeSwap(msg.clone())).unwrap(),
+            })
+        };
+
+        // NFTs can't be vested
+        let vested = MakeSwapMsg {
+            vesting: Some(VestingDetail {
+                schedules: vec![ReleaseInterval {
+                    interval: 10,
+                    amount: Uint128::from(10000u64),
+                }],
+            }),
+            ..create.clone()
+        };
+        let info = mock_info("nft", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, receive(&vested)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("NFTs can't be sold in parts or vested"))
+        );
+
+        execute(deps.as_mut(), env.clone(), mock_info("nft", &[]), receive(&create)).unwrap();
+
+        let info = mock_info("taker0001", &[coin(200, "token2")]);
+        let take = ExecuteMsg::TakeSwap(TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(200, "token2"),
+            taker_address: "taker0001".to_string(),
+        });
+        let res = execute(deps.as_mut(), env, info, take).unwrap();
+
+        // Fees are paid in the buy token, the NFT goes to the taker whole
+        let transfer_nft = SubMsg::new(WasmMsg::Execute {
+            contract_addr: "nft".to_string(),
+            msg: to_json_binary(&cw721::Cw721ExecuteMsg::TransferNft {
+                recipient: "taker0001".to_string(),
+                token_id: "7".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        });
+        assert_eq!(
+            res.messages,
+            vec![
+                Asset::from(coin(198, "token2")).transfer_msg(&Addr::unchecked("maker0001")).unwrap(),
+                Asset::from(coin(2, "token2")).transfer_msg(&Addr::unchecked("tre")).unwrap(),
+                transfer_nft,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_basket_order() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![coin(500, "token3")],
+            extra_buy_tokens: vec![coin(100, "token4")],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+
+        // The whole basket has to be sent
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create.clone())).unwrap_err();
+        let info = mock_info("maker0001", &[coin(1000, "token1"), coin(500, "token3")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let take = ExecuteMsg::TakeSwap(TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(200, "token2"),
+            taker_address: "taker0001".to_string(),
+        });
+        let info = mock_info("taker0001", &[coin(200, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, take.clone()).unwrap_err();
+
+        let info = mock_info("taker0001", &[coin(200, "token2"), coin(100, "token4")]);
+        let res = execute(deps.as_mut(), env, info, take).unwrap();
+        let send = |to: &str, amount: u128, denom: &str| {
+            Asset::from(coin(amount, denom)).transfer_msg(&Addr::unchecked(to)).unwrap()
+        };
+        // Each token is paid less its own fee
+        assert_eq!(
+            res.messages,
+            vec![
+                send("maker0001", 198, "token2"),
+                send("tre", 2, "token2"),
+                send("taker0001", 990, "token1"),
+                send("tre", 10, "token1"),
+                send("maker0001", 99, "token4"),
+                send("tre", 1, "token4"),
+                send("taker0001", 495, "token3"),
+                send("tre", 5, "token3"),
+            ]
+        );
+        let order = INACTIVE_SWAP_ORDERS.load(&deps.storage, 0).unwrap();
+        assert_eq!(order.status, Status::Complete);
+    }
+
+    #[test]
+    fn test_take_bid_refunds_losing_bids() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        for (bidder, amount) in [("bidder0001", 150), ("bidder0002", 180)] {
+            let bid = MakeBidMsg {
+                order_id: "0".to_string(),
+                sell_token: coin(amount, "token2"),
+                taker_address: bidder.to_string(),
+                expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            };
+            let info = mock_info(bidder, &[coin(amount, "token2")]);
+            execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap();
+        }
+
+        let take = TakeBidMsg {
+            order_id: "0".to_string(),
+            bidder: "bidder0002".to_string(),
+        };
+        let info = mock_info("maker0001", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeBid(take)).unwrap();
+        let refund = Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap();
+        assert_eq!(res.messages.last(), Some(&refund));
+        assert_eq!(res.events.len(), 1);
+
+        let winner = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0002".to_string())).unwrap();
+        assert_eq!(winner.status, BidStatus::Executed);
+        let loser = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0001".to_string())).unwrap();
+        assert_eq!(loser.status, BidStatus::Refunded);
+
+        // Nothing is left to refund
+        let refund_bids = ExecuteMsg::RefundBids { order_id: "0".to_string(), limit: None };
+        let err = execute(deps.as_mut(), env, mock_info("anyone", &[]), refund_bids).unwrap_err();
+        assert_eq!(err, ContractError::NoBidsToRefund);
+    }
+
+    #[test]
+    fn test_update_swap() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let bid = MakeBidMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(150, "token2"),
+            taker_address: "bidder0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+        };
+        let info = mock_info("bidder0001", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap();
+
+        let update = UpdateSwapMsg {
+            order_id: "0".to_string(),
+            buy_token: Some(coin(300, "token2")),
+            expiration_timestamp: Some(env.block.time.plus_seconds(500).seconds()),
+            min_bid_price: None,
+            take_bids: Some(false),
+            desired_taker: Some("taker0001".to_string()),
+        };
+
+        // Only the maker updates the order, and only the amount of its price
+        let info = mock_info("anyone", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::UpdateSwap(update.clone())).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+        let other_denom = UpdateSwapMsg { buy_token: Some(coin(300, "token3")), ..update.clone() };
+        let info = mock_info("maker0001", &[]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::UpdateSwap(other_denom)).unwrap_err();
+
+        // The placed bid is refunded as the order stops taking bids
+        let info = mock_info("maker0001", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::UpdateSwap(update)).unwrap();
+        let refund = Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap();
+        assert_eq!(res.messages, vec![refund]);
+
+        let order = get_atomic_order(&deps.storage, "0").unwrap();
+        assert_eq!(order.maker.buy_token, coin(300, "token2"));
+        assert_eq!(order.maker.expiration_timestamp, env.block.time.plus_seconds(500).seconds());
+        assert!(!order.maker.take_bids);
+        assert_eq!(order.maker.desired_taker, "taker0001");
+
+        // Takers pay the new price
+        let take = TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(200, "token2"),
+            taker_address: "taker0001".to_string(),
+        };
+        let info = mock_info("taker0001", &[coin(200, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeSwap(take)).unwrap_err();
+        let take = TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(300, "token2"),
+            taker_address: "taker0001".to_string(),
+        };
+        let info = mock_info("taker0001", &[coin(300, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeSwap(take)).unwrap();
+
+        // A taken order can't be updated anymore
+        let update = UpdateSwapMsg {
+            order_id: "0".to_string(),
+            buy_token: None,
+            expiration_timestamp: None,
+            min_bid_price: Some(Uint128::new(10)),
+            take_bids: None,
+            desired_taker: None,
+        };
+        let info = mock_info("maker0001", &[]);
+        execute(deps.as_mut(), env, info, ExecuteMsg::UpdateSwap(update)).unwrap_err();
+    }
+
+    #[test]
+    fn test_english_auction() {
+        let mut deps = mock_dependencies();
+
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let end_time = env.block.time.plus_seconds(100).seconds();
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(1000).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: Some(AuctionConfig {
+                reserve_price: Uint128::new(100),
+                end_time,
+                min_increment: Uint128::new(10),
+                extension_window: 20,
+            }),
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let bid = |deps: DepsMut, env: &Env, bidder: &str, amount: u128| {
+            let msg = MakeBidMsg {
+                order_id: "0".to_string(),
+                sell_token: coin(amount, "token2"),
+                taker_address: bidder.to_string(),
+                expiration_timestamp: env.block.time.plus_seconds(10).seconds(),
+            };
+            let info = mock_info(bidder, &[coin(amount, "token2")]);
+            execute(deps, env.clone(), info, ExecuteMsg::MakeBid(msg))
+        };
+
+        // Bids reach the reserve and beat the highest one by the increment
+        let err = bid(deps.as_mut(), &env, "bidder0001", 90).unwrap_err();
+        assert_eq!(err, ContractError::InvalidBidAmount);
+        bid(deps.as_mut(), &env, "bidder0001", 100).unwrap();
+        let err = bid(deps.as_mut(), &env, "bidder0002", 105).unwrap_err();
+        assert_eq!(err, ContractError::InvalidBidAmount);
+        bid(deps.as_mut(), &env, "bidder0002", 110).unwrap();
+
+        // The highest bid stands, the maker can't pick another one
+        let cancel = CancelBidMsg {
+            order_id: "0".to_string(),
+            bidder: "bidder0002".to_string(),
+        };
+        let info = mock_info("bidder0002", &[]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CancelBid(cancel)).unwrap_err();
+        let take = TakeBidMsg {
+            order_id: "0".to_string(),
+            bidder: "bidder0001".to_string(),
+        };
+        let info = mock_info("maker0001", &[]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeBid(take)).unwrap_err();
+
+        let settle = ExecuteMsg::SettleAuction { order_id: "0".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AuctionNotEnded);
+
+        // A bid in the last seconds moves the end back
+        env.block.time = env.block.time.plus_seconds(95);
+        bid(deps.as_mut(), &env, "bidder0003", 130).unwrap();
+        let order = get_atomic_order(&deps.storage, "0").unwrap();
+        assert_eq!(order.maker.auction.unwrap().end_time, env.block.time.plus_seconds(20).seconds());
+
+        env.block.time = env.block.time.plus_seconds(10);
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AuctionNotEnded);
+
+        // Anyone settles, even after the order expired, and bid expirations don't apply
+        env.block.time = env.block.time.plus_seconds(1000);
+        let reclaim = ExecuteMsg::ReclaimExpired { order_ids: vec!["0".to_string()] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), reclaim).unwrap_err();
+        assert_eq!(err, ContractError::Std(StdError::generic_err("An auction with bids can't be cancelled")));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(res.attributes[2], attr("bidder", "bidder0003"));
+        let refunds: Vec<_> = ["bidder0001", "bidder0002"]
+            .iter()
+            .zip([100, 110])
+            .map(|(bidder, amount)| Asset::from(coin(amount, "token2")).transfer_msg(&Addr::unchecked(*bidder)).unwrap())
+            .collect();
+        assert_eq!(res.messages[res.messages.len() - 2..], refunds[..]);
+
+        let winner = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0003".to_string())).unwrap();
+        assert_eq!(winner.status, BidStatus::Executed);
+    }
+
+    #[test]
+    fn test_dutch_auction() {
+        let mut deps = mock_dependencies();
+
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(1000, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(1000).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: Some(DutchAuctionConfig {
+                floor_price: Uint128::new(400),
+                start_time: env.block.time.seconds(),
+                end_time: env.block.time.plus_seconds(100).seconds(),
+                step: 10,
+            }),
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::MakeSwap(create.clone())).unwrap_err();
+        let create = MakeSwapMsg { take_bids: false, ..create };
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        // The price drops by 60 every 10 seconds
+        env.block.time = env.block.time.plus_seconds(25);
+        let price = |deps: Deps, env: &Env| {
+            let msg = QueryMsg::CurrentPrice { order_id: "0".to_string() };
+            from_json::<CurrentPriceResponse>(&query(deps, env.clone(), msg).unwrap()).unwrap().price
+        };
+        assert_eq!(price(deps.as_ref(), &env), coin(880, "token2"));
+
+        let take = |amount: u128| TakeSwapMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(amount, "token2"),
+            taker_address: "taker0001".to_string(),
+        };
+        let info = mock_info("taker0001", &[coin(850, "token2")]);
+        let err = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeSwap(take(850))).unwrap_err();
+        assert_eq!(err, ContractError::BelowCurrentPrice { price: "880token2".to_string() });
+
+        // Paying more than the price is fine, the surplus goes back
+        let info = mock_info("taker0001", &[coin(900, "token2")]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::TakeSwap(take(900))).unwrap();
+        let surplus = Asset::from(coin(20, "token2")).transfer_msg(&Addr::unchecked("taker0001")).unwrap();
+        assert!(res.messages.contains(&surplus));
+        assert_eq!(res.attributes.last(), Some(&attr("price", "880token2")));
+
+        let order = INACTIVE_SWAP_ORDERS.load(&deps.storage, 0).unwrap();
+        assert_eq!(order.taker.as_ref().unwrap().sell_token, coin(880, "token2"));
+
+        // The floor holds after the end
+        env.block.time = env.block.time.plus_seconds(100);
+        assert_eq!(order.current_price(env.block.time.seconds()), coin(400, "token2"));
+    }
+
+    #[test]
+    fn test_sealed_bid_auction() {
+        let mut deps = mock_dependencies();
+
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(1000).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: Some(SealedBidConfig {
+                commit_end: env.block.time.plus_seconds(100).seconds(),
+                reveal_end: env.block.time.plus_seconds(200).seconds(),
+            }),
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let commit = |deps: DepsMut, env: &Env, bidder: &str, deposit: u128, amount: u128| {
+            let msg = CommitBidMsg {
+                order_id: "0".to_string(),
+                commitment: sealed_bid_commitment("0", bidder, Uint128::new(amount), "salt"),
+            };
+            let info = mock_info(bidder, &[coin(deposit, "token2")]);
+            execute(deps, env.clone(), info, ExecuteMsg::CommitBid(msg))
+        };
+        let reveal = |deps: DepsMut, env: &Env, bidder: &str, amount: u128, salt: &str| {
+            let msg = RevealBidMsg {
+                order_id: "0".to_string(),
+                amount: Uint128::new(amount),
+                salt: salt.to_string(),
+            };
+            execute(deps, env.clone(), mock_info(bidder, &[]), ExecuteMsg::RevealBid(msg))
+        };
+
+        // Open bids are refused, the deposits hide the amounts
+        let bid = MakeBidMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(150, "token2"),
+            taker_address: "bidder0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(10).seconds(),
+        };
+        let info = mock_info("bidder0001", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap_err();
+        commit(deps.as_mut(), &env, "bidder0001", 300, 150).unwrap();
+        commit(deps.as_mut(), &env, "bidder0002", 300, 180).unwrap();
+        commit(deps.as_mut(), &env, "bidder0003", 100, 90).unwrap();
+        let err = commit(deps.as_mut(), &env, "bidder0001", 300, 160).unwrap_err();
+        assert_eq!(err, ContractError::BidAlreadyExist {});
+        let err = reveal(deps.as_mut(), &env, "bidder0001", 150, "salt").unwrap_err();
+        assert_eq!(err, ContractError::NotRevealPhase);
+
+        let cancel = CancelSwapMsg {
+            order_id: "0".to_string(),
+            maker_address: "maker0001".to_string(),
+        };
+        let info = mock_info("maker0001", &[]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CancelSwap(cancel)).unwrap_err();
+
+        // Bids are revealed once bidding closes, the deposit above them goes back
+        env.block.time = env.block.time.plus_seconds(100);
+        let err = commit(deps.as_mut(), &env, "bidder0004", 300, 200).unwrap_err();
+        assert_eq!(err, ContractError::AuctionEnded);
+        let err = reveal(deps.as_mut(), &env, "bidder0001", 150, "pepper").unwrap_err();
+        assert_eq!(err, ContractError::InvalidCommitment);
+        let res = reveal(deps.as_mut(), &env, "bidder0001", 150, "salt").unwrap();
+        let surplus = Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap();
+        assert_eq!(res.messages, vec![surplus]);
+        reveal(deps.as_mut(), &env, "bidder0002", 180, "salt").unwrap();
+
+        let settle = ExecuteMsg::SettleAuction { order_id: "0".to_string() };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle.clone()).unwrap_err();
+        assert_eq!(err, ContractError::AuctionNotEnded);
+
+        // The highest revealed bid wins, even after the order expired, the deposit left sealed
+        // goes to the treasury
+        env.block.time = env.block.time.plus_seconds(1000);
+        let reclaim = ExecuteMsg::ReclaimExpired { order_ids: vec!["0".to_string()] };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), reclaim).unwrap_err();
+        assert_eq!(err, ContractError::Std(StdError::generic_err("An auction with bids can't be cancelled")));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(res.attributes[2], attr("bidder", "bidder0002"));
+        let refund = Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap();
+        let forfeit = Asset::from(coin(100, "token2")).transfer_msg(&Addr::unchecked("tre")).unwrap();
+        assert!(res.messages.contains(&refund));
+        assert!(res.messages.contains(&forfeit));
+
+        let sealed = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0003".to_string())).unwrap();
+        assert_eq!(sealed.status, BidStatus::Forfeited);
+    }
+
+    #[test]
+    fn test_counter_offer() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let bid = MakeBidMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(150, "token2"),
+            taker_address: "bidder0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+        };
+        let info = mock_info("bidder0001", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap();
+
+        let counter = CounterOfferMsg {
+            order_id: "0".to_string(),
+            bidder: "bidder0001".to_string(),
+            price: Uint128::from(180u64),
+            expiration_timestamp: env.block.time.plus_seconds(50).seconds(),
+        };
+        let info = mock_info("bidder0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CounterOffer(counter.clone())).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+        let info = mock_info("maker0001", &[]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::CounterOffer(counter)).unwrap();
+
+        // The top up must match the difference to the price
+        let accept = ExecuteMsg::AcceptCounterOffer(CounterOfferActionMsg { order_id: "0".to_string() });
+        let info = mock_info("bidder0001", &[coin(20, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, accept.clone()).unwrap_err();
+        let info = mock_info("bidder0001", &[coin(30, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, accept).unwrap();
+
+        let bid = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0001".to_string())).unwrap();
+        assert_eq!(bid.bid, coin(180, "token2"));
+        let res: CounterOffersResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CounterOffersByBidder {
+                    bidder: "bidder0001".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.offers.len(), 1);
+        assert_eq!(res.offers[0].status, CounterOfferStatus::Accepted);
+        assert_eq!(res.offers[0].round, 1);
+
+        // An accepted offer can't be declined
+        let decline = ExecuteMsg::DeclineCounterOffer(CounterOfferActionMsg { order_id: "0".to_string() });
+        let err = execute(deps.as_mut(), env, mock_info("bidder0001", &[]), decline).unwrap_err();
+        assert_eq!(err, ContractError::InvalidStatus);
+    }
+
+    #[test]
+    fn test_reject_bid() {
+        let mut deps = mock_dependencies();
+
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                admin: "admin".to_string(),
+                treasury: "tre".to_string(),
+                vesting_contract: "vesting-address".to_string(),
+                maker_fee: 100,
+                taker_fee: 100,
+                keeper_fee: 0,
+            },
+        )
+        .unwrap();
+
+        let create = MakeSwapMsg {
+            sell_token: coin(1000, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+            sealed_bid: None,
+        };
+        let info = mock_info("maker0001", &[coin(1000, "token1")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeSwap(create)).unwrap();
+
+        let bid = MakeBidMsg {
+            order_id: "0".to_string(),
+            sell_token: coin(150, "token2"),
+            taker_address: "bidder0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+        };
+        let info = mock_info("bidder0001", &[coin(150, "token2")]);
+        execute(deps.as_mut(), env.clone(), info, ExecuteMsg::MakeBid(bid)).unwrap();
+
+        let reject = |bidder: &str, reason: Option<String>| {
+            ExecuteMsg::RejectBid(RejectBidMsg {
+                order_id: "0".to_string(),
+                bidder: bidder.to_string(),
+                reason,
+            })
+        };
+
+        // Only the maker rejects bids
+        let info = mock_info("bidder0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, reject("bidder0001", None)).unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        let info = mock_info("maker0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, reject("bidder0002", None)).unwrap_err();
+        assert_eq!(err, ContractError::BidDoesntExist);
+
+        let reason = "x".repeat(MAX_REASON_LENGTH + 1);
+        let info = mock_info("maker0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, reject("bidder0001", Some(reason))).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err(format!("Reason must not be longer than {} bytes", MAX_REASON_LENGTH)))
+        );
+
+        // The deposit goes back to the bidder
+        let info = mock_info("maker0001", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, reject("bidder0001", Some("too low".to_string()))).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![Asset::from(coin(150, "token2")).transfer_msg(&Addr::unchecked("bidder0001")).unwrap()]
+        );
+        assert_eq!(res.events[0].ty, "bid_rejected");
+        assert!(res.events[0].attributes.contains(&attr("reason", "too low")));
+        let bid = bids().load(&deps.storage, bid_key(&"0".to_string(), &"bidder0001".to_string())).unwrap();
+        assert_eq!(bid.status, BidStatus::Rejected);
+
+        // A bid that isn't placed any more can't be rejected
+        let info = mock_info("maker0001", &[]);
+        let err = execute(deps.as_mut(), env, info, reject("bidder0001", None)).unwrap_err();
+        assert_eq!(err, ContractError::BidDoesntExist);
+    }
+
     #[test]
     fn test_takeswap_msg_decode() {
         let mut deps = mock_dependencies();
@@ -1562,7 +3648,8 @@ mod tests {
                 treasury: "tre".to_string(),
                 vesting_contract: "vesting-address".to_string(),
                 maker_fee: 100,
-                taker_fee: 100
+                taker_fee: 100,
+                keeper_fee: 0,
             },
         )
         .unwrap();
@@ -74,4 +74,34 @@ pub enum ContractError {
 
     #[error("Invalid bid amount")]
     InvalidBidAmount,
+
+    #[error("No bids left to refund")]
+    NoBidsToRefund,
+
+    #[error("Counter offer doesn't exist")]
+    CounterOfferDoesntExist,
+
+    #[error("Invalid fill amount")]
+    InvalidFillAmount,
+
+    #[error("Order is not an auction")]
+    NotAuction,
+
+    #[error("Auction has not ended")]
+    AuctionNotEnded,
+
+    #[error("Auction has ended")]
+    AuctionEnded,
+
+    #[error("Auction has no bids")]
+    NoAuctionBids,
+
+    #[error("Pay at least the current price of {price}")]
+    BelowCurrentPrice { price: String },
+
+    #[error("Revealed bid doesn't match its commitment")]
+    InvalidCommitment,
+
+    #[error("Sealed bids are revealed between the end of bidding and the end of the reveal")]
+    NotRevealPhase,
 }
@@ -1,3 +1,4 @@
+pub mod asset;
 pub mod contract;
 mod error;
 pub mod msg;
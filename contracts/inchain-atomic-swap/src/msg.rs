@@ -3,11 +3,12 @@ use std::fmt;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cw20::Cw20Coin;
+use cw20::{Cw20Coin, Cw20ReceiveMsg};
+use cw721::Cw721ReceiveMsg;
 
 use cosmwasm_std::{Binary, Coin, Timestamp, Uint128};
 
-use crate::state::{AtomicSwapOrder, Bid, BidStatus, Status};
+use crate::state::{AtomicSwapOrder, Bid, BidStatus, CounterOffer, Fill, Status};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Height {
@@ -25,19 +26,64 @@ pub struct InstantiateMsg {
     pub taker_fee: u64,
     pub treasury: String,
     pub vesting_contract: String,
+    /// Share of an expired maker refund paid to whoever reclaims it, in basis points.
+    /// Paid out of the rewards the treasury funds with `FundKeeperRewards`.
+    #[serde(default)]
+    pub keeper_fee: u64,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum ExecuteMsg {
     MakeSwap(MakeSwapMsg),
     TakeSwap(TakeSwapMsg),
     CancelSwap(CancelSwapMsg),
+    /// Change the price, expiration or bid settings of an untaken order, maker only
+    UpdateSwap(UpdateSwapMsg),
     MakeBid(MakeBidMsg),
     TakeBid(TakeBidMsg),
     CancelBid(CancelBidMsg),
     UpdateBid(UpdateBidMsg),
+    /// Escrow a deposit with the hash of a sealed bid
+    CommitBid(CommitBidMsg),
+    /// Reveal a sealed bid once bidding closes, the deposit above it is refunded
+    RevealBid(RevealBidMsg),
+    /// Refund and close a placed bid, maker only
+    RejectBid(RejectBidMsg),
+    /// Answer a bid with a price, maker only
+    CounterOffer(CounterOfferMsg),
+    /// Top up the bid to the counter offer price, bidder only
+    AcceptCounterOffer(CounterOfferActionMsg),
+    /// Decline the counter offer, bidder only
+    DeclineCounterOffer(CounterOfferActionMsg),
+    /// Refund and archive expired orders, callable by anyone
+    ReclaimExpired { order_ids: Vec<String> },
+    /// Fund the rewards of ReclaimExpired with the funds sent, treasury only
+    FundKeeperRewards,
+    /// Sell an ended auction to its highest bid and refund the others, callable by anyone.
+    /// Deposits of sealed bids that weren't revealed go to the treasury.
+    SettleAuction { order_id: String },
+    /// Refund the bids left over when an order was closed, callable by anyone
+    RefundBids { order_id: String, limit: Option<u32> },
     PauseMarket,
     UnpauseMarket,
+    /// Cw20 tokens sent with one of the wrapped messages
+    Receive(Cw20ReceiveMsg),
+    /// NFT sent with a `ReceiveMsg::MakeSwap` selling it
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+/// Messages that can be paid with cw20 tokens through `ExecuteMsg::Receive`. An NFT sent
+/// through `ExecuteMsg::ReceiveNft` only takes `MakeSwap`.
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum ReceiveMsg {
+    MakeSwap(MakeSwapMsg),
+    TakeSwap(TakeSwapMsg),
+    MakeBid(MakeBidMsg),
+    UpdateBid(UpdateBidMsg),
+    CommitBid(CommitBidMsg),
+    FundKeeperRewards,
 }
 
 pub fn is_valid_name(name: &str) -> bool {
@@ -78,6 +124,60 @@ pub struct AtomicSwapPacketData {
     pub memo: String,
 }
 
+/// English auction, settled with `SettleAuction`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AuctionConfig {
+    /// Smallest bid accepted
+    pub reserve_price: Uint128,
+    /// Bidding closes at this time, in seconds. Moved back by late bids
+    pub end_time: u64,
+    /// A bid must beat the highest other bid by at least this amount
+    pub min_increment: Uint128,
+    /// A bid made less than this many seconds before the end moves the end to as many
+    /// seconds after it
+    pub extension_window: u64,
+}
+
+/// Sealed-bid auction, bids are committed as hashes and revealed once bidding closes.
+/// Settled with `SettleAuction`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SealedBidConfig {
+    /// Bids are committed until this time, in seconds
+    pub commit_end: u64,
+    /// Bids are revealed until this time, in seconds
+    pub reveal_end: u64,
+}
+
+/// Dutch auction, the price falls from the amount of `buy_token` to `floor_price`
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DutchAuctionConfig {
+    pub floor_price: Uint128,
+    /// The price starts falling at this time, in seconds
+    pub start_time: u64,
+    /// The price reaches the floor at this time, in seconds
+    pub end_time: u64,
+    /// The price drops every this many seconds, 0 for a linear decay
+    #[serde(default)]
+    pub step: u64,
+}
+
+impl DutchAuctionConfig {
+    /// Price at `now` of an auction starting at `start_price`
+    pub fn price_at(&self, start_price: Uint128, now: u64) -> Uint128 {
+        if now <= self.start_time {
+            return start_price;
+        }
+        if now >= self.end_time {
+            return self.floor_price;
+        }
+        let mut elapsed = now - self.start_time;
+        if self.step > 0 {
+            elapsed -= elapsed % self.step;
+        }
+        start_price - (start_price - self.floor_price).multiply_ratio(elapsed, self.end_time - self.start_time)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct VestingDetail {
     // List of intervals and %age, after each interval certain amount will be released
@@ -129,6 +229,47 @@ pub struct MakeSwapMsg {
     pub expiration_timestamp: u64,
     /// None if vesting is disabled
     pub vesting: Option<VestingDetail>,
+    /// Allow takers to fill a part of the order at its price
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Smallest buy_token amount accepted from a partial fill, except the one completing the order
+    #[serde(default)]
+    pub min_fill_amount: Option<Uint128>,
+    /// More tokens sold together with `sell_token` in a basket order
+    #[serde(default)]
+    pub extra_sell_tokens: Vec<Coin>,
+    /// More tokens wanted together with `buy_token` in a basket order
+    #[serde(default)]
+    pub extra_buy_tokens: Vec<Coin>,
+    /// Sell the order to the highest bid once the auction ends
+    #[serde(default)]
+    pub auction: Option<AuctionConfig>,
+    /// Lower the price over time until the order is taken
+    #[serde(default)]
+    pub dutch_auction: Option<DutchAuctionConfig>,
+    /// Sell the order to the highest sealed bid once bids are revealed
+    #[serde(default)]
+    pub sealed_bid: Option<SealedBidConfig>,
+}
+
+impl MakeSwapMsg {
+    /// All the tokens sold, a basket's included
+    pub fn sell_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.sell_token.clone()];
+        tokens.extend(self.extra_sell_tokens.iter().cloned());
+        tokens
+    }
+
+    /// All the tokens wanted, a basket's included
+    pub fn buy_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.buy_token.clone()];
+        tokens.extend(self.extra_buy_tokens.iter().cloned());
+        tokens
+    }
+
+    pub fn is_basket(&self) -> bool {
+        !self.extra_sell_tokens.is_empty() || !self.extra_buy_tokens.is_empty()
+    }
 }
 
 impl fmt::Display for MakeSwapMsg {
@@ -177,6 +318,20 @@ pub struct CancelSwapMsg {
     pub maker_address: String,
 }
 
+/// Fields left out keep their value
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct UpdateSwapMsg {
+    pub order_id: String,
+    /// New price, in the denom the order already asks for
+    pub buy_token: Option<Coin>,
+    pub expiration_timestamp: Option<u64>,
+    pub min_bid_price: Option<Uint128>,
+    /// Placed bids are refunded when the order stops taking bids
+    pub take_bids: Option<bool>,
+    /// Empty to let anyone take the order
+    pub desired_taker: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct MakeBidMsg {
     pub order_id: String,
@@ -185,6 +340,21 @@ pub struct MakeBidMsg {
     pub expiration_timestamp: u64,
 }
 
+/// The funds sent are the deposit, a bid can't be revealed above it
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct CommitBidMsg {
+    pub order_id: String,
+    /// Hex encoded sha256 of `<order_id>/<bidder>/<amount>/<salt>`
+    pub commitment: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct RevealBidMsg {
+    pub order_id: String,
+    pub amount: Uint128,
+    pub salt: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct TakeBidMsg {
     pub order_id: String,
@@ -197,6 +367,28 @@ pub struct CancelBidMsg {
     pub bidder: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct CounterOfferMsg {
+    pub order_id: String,
+    pub bidder: String,
+    /// Total bid amount the maker would accept
+    pub price: Uint128,
+    pub expiration_timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct CounterOfferActionMsg {
+    pub order_id: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct RejectBidMsg {
+    pub order_id: String,
+    pub bidder: String,
+    /// Shown to the bidder in the reject event
+    pub reason: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct UpdateBidMsg {
     pub order_id: String,
@@ -292,6 +484,30 @@ pub enum QueryMsg {
         start_after: Option<String>, // order
         limit: Option<u32>,
     },
+    CounterOfferDetails {
+        order: String,
+        bidder: String,
+    },
+    CounterOffersByOrder {
+        order: String,
+        start_after: Option<String>, // bidder
+        limit: Option<u32>,
+    },
+    CounterOffersByBidder {
+        bidder: String,
+        start_after: Option<String>, // order
+        limit: Option<u32>,
+    },
+    /// Partial fills of an order, oldest first. Return type is FillsResponse.
+    Fills {
+        order_id: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    /// Price a taker pays now, falling for Dutch auctions. Return type is CurrentPriceResponse.
+    CurrentPrice {
+        order_id: String,
+    },
     /// Inactive fields query
     InactiveList {
         start_after: Option<u64>,
@@ -356,3 +572,18 @@ pub struct ListResponse {
 pub struct BidsResponse {
     pub bids: Vec<Bid>,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterOffersResponse {
+    pub offers: Vec<CounterOffer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FillsResponse {
+    pub fills: Vec<Fill>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CurrentPriceResponse {
+    pub price: Coin,
+}
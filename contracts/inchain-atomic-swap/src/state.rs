@@ -2,7 +2,7 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::msg::{MakeSwapMsg, TakeSwapMsg, VestingDetail};
-use cosmwasm_std::{Coin, StdResult, Storage, Timestamp, Uint128};
+use cosmwasm_std::{Coin, Empty, Order, StdResult, Storage, Timestamp, Uint128};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
 pub const FEE_INFO: Item<FeeInfo> = Item::new("fee_info");
@@ -13,8 +13,14 @@ pub struct FeeInfo {
     pub maker_fee: u64,
     pub taker_fee: u64,
     pub treasury: String,
+    // Paid to the caller of ReclaimExpired out of KEEPER_REWARDS
+    #[serde(default)]
+    pub keeper_fee: u64,
 }
 
+// Rewards for ReclaimExpired by denom, funded by the treasury
+pub const KEEPER_REWARDS: Map<&str, Uint128> = Map::new("keeper_rewards");
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Status {
@@ -44,8 +50,70 @@ pub struct AtomicSwapOrder {
     pub complete_timestamp: Option<Timestamp>,
     pub min_bid_price: Option<Uint128>,
     pub vesting_details: Option<VestingDetail>,
+    // Taken so far by partial fills, in sell and buy token amounts
+    #[serde(default)]
+    pub filled_sell_amount: Uint128,
+    #[serde(default)]
+    pub filled_buy_amount: Uint128,
+    #[serde(default)]
+    pub fill_count: u32,
 }
 
+impl AtomicSwapOrder {
+    /// Sell token left for takers
+    pub fn remaining_sell(&self) -> Coin {
+        Coin {
+            denom: self.maker.sell_token.denom.clone(),
+            amount: self.maker.sell_token.amount - self.filled_sell_amount,
+        }
+    }
+
+    /// Buy token still wanted by the maker
+    pub fn remaining_buy(&self) -> Coin {
+        Coin {
+            denom: self.maker.buy_token.denom.clone(),
+            amount: self.maker.buy_token.amount - self.filled_buy_amount,
+        }
+    }
+
+    /// Sell tokens still escrowed for the maker, the rest of a basket included
+    pub fn remaining_sell_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.remaining_sell()];
+        tokens.extend(self.maker.extra_sell_tokens.iter().cloned());
+        tokens
+    }
+
+    /// Buy token a taker pays at `now` for the whole order
+    pub fn current_price(&self, now: u64) -> Coin {
+        let mut price = self.maker.buy_token.clone();
+        if let Some(dutch) = &self.maker.dutch_auction {
+            price.amount = dutch.price_at(price.amount, now);
+        }
+        price
+    }
+
+    /// Tokens a taker pays with `buy_token` of the order, the rest of a basket included
+    pub fn taker_tokens(&self, buy_token: &Coin) -> Vec<Coin> {
+        let mut tokens = vec![buy_token.clone()];
+        tokens.extend(self.maker.extra_buy_tokens.iter().cloned());
+        tokens
+    }
+}
+
+/// One partial take of an order
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Fill {
+    pub taker: String,
+    /// Slice of the maker's sell token sent to the taker
+    pub sell_token: Coin,
+    /// Paid by the taker in the maker's buy token
+    pub buy_token: Coin,
+    pub timestamp: u64,
+}
+
+// Fills history: (order_id, fill index)
+pub const FILLS: Map<(&str, u32), Fill> = Map::new("fills");
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Config {
     pub vesting_contract: String,
@@ -118,6 +186,9 @@ pub fn move_order_to_bottom(storage: &mut dyn Storage, order_id: &str) -> StdRes
     Ok(id)
 }
 
+// Closed orders with placed bids left to refund
+pub const BID_REFUNDS: Map<&str, Empty> = Map::new("bid_refunds");
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub enum MarketState {
     Paused,
@@ -131,6 +202,14 @@ pub enum BidStatus {
     Cancelled,
     Executed,
     Placed,
+    // refunded when the order was closed
+    Refunded,
+    // refunded when the maker rejected it
+    Rejected,
+    // sealed, the deposit is escrowed until the bid is revealed
+    Committed,
+    // sealed and never revealed, the deposit went to the treasury
+    Forfeited,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -141,6 +220,9 @@ pub struct Bid {
     pub bidder: String,
     pub receive_timestamp: u64,
     pub expire_timestamp: u64,
+    /// Hash of a committed sealed bid
+    #[serde(default)]
+    pub commitment: Option<String>,
 }
 
 /// Primary key for asks: (collection, token_id)
@@ -153,6 +235,8 @@ pub fn bid_key(order: &String, bidder: &String) -> BidKey {
 pub struct BidIndicies<'a> {
     pub order: MultiIndex<'a, String, Bid, BidKey>,
     pub order_price: MultiIndex<'a, (String, u128), Bid, BidKey>,
+    // (order, status as u8), to page over the open bids of an order
+    pub order_status: MultiIndex<'a, (String, u8), Bid, BidKey>,
     pub timestamp: MultiIndex<'a, (String, u64), Bid, BidKey>,
     pub bidder: MultiIndex<'a, String, Bid, BidKey>,
 }
@@ -162,6 +246,7 @@ impl<'a> IndexList<Bid> for BidIndicies<'a> {
         let v: Vec<&dyn Index<Bid>> = vec![
             &self.order,
             &self.order_price,
+            &self.order_status,
             &self.timestamp,
             &self.bidder,
         ];
@@ -177,6 +262,11 @@ pub fn bids<'a>() -> IndexedMap<'a, BidKey, Bid, BidIndicies<'a>> {
             "bids",
             "bids__order_price",
         ),
+        order_status: MultiIndex::new(
+            |_pk: &[u8], d: &Bid| (d.order.clone(), d.status.clone() as u8),
+            "bids",
+            "bids__order_status",
+        ),
         timestamp: MultiIndex::new(
             |_pk: &[u8], d: &Bid| (d.order.clone(), d.receive_timestamp),
             "bids",
@@ -190,3 +280,62 @@ pub fn bids<'a>() -> IndexedMap<'a, BidKey, Bid, BidIndicies<'a>> {
     };
     IndexedMap::new("bids", indexes)
 }
+
+/// Highest bid of an order that passes `filter`
+pub fn top_bid(
+    storage: &dyn Storage,
+    order_id: &str,
+    filter: impl Fn(&Bid) -> bool,
+) -> StdResult<Option<(BidKey, Bid)>> {
+    bids()
+        .idx
+        .order_price
+        .sub_prefix(order_id.to_string())
+        .range(storage, None, None, Order::Descending)
+        .find(|item| !matches!(item, Ok((_, bid)) if !filter(bid)))
+        .transpose()
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum CounterOfferStatus {
+    Open,
+    Accepted,
+    Declined,
+}
+
+/// The maker's answer to a bid, one negotiation thread per (order, bidder)
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterOffer {
+    pub order: String,
+    pub bidder: String,
+    /// Total bid amount asked by the maker, in the bid denom
+    pub price: Uint128,
+    pub expire_timestamp: u64,
+    pub status: CounterOfferStatus,
+    /// Number of counter offers the maker made in this thread
+    pub round: u32,
+    pub update_timestamp: u64,
+}
+
+pub struct CounterOfferIndicies<'a> {
+    pub bidder: MultiIndex<'a, String, CounterOffer, BidKey>,
+}
+
+impl<'a> IndexList<CounterOffer> for CounterOfferIndicies<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<CounterOffer>> + '_> {
+        let v: Vec<&dyn Index<CounterOffer>> = vec![&self.bidder];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Counter offers keyed like bids: (order, bidder)
+pub fn counter_offers<'a>() -> IndexedMap<'a, BidKey, CounterOffer, CounterOfferIndicies<'a>> {
+    let indexes = CounterOfferIndicies {
+        bidder: MultiIndex::new(
+            |_pk: &[u8], d: &CounterOffer| d.bidder.clone(),
+            "counter_offers",
+            "counter_offers__bidder",
+        ),
+    };
+    IndexedMap::new("counter_offers", indexes)
+}
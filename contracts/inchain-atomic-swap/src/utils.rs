@@ -1,9 +1,12 @@
-use cosmwasm_std::{Addr, BankMsg, Coin, Deps, StdError, StdResult, SubMsg, Uint128};
+use cosmwasm_std::{Addr, Coin, Deps, StdError, StdResult, Storage, SubMsg, Uint128};
 use sha2::{Digest, Sha256};
 
-use crate::state::FEE_INFO;
+use crate::asset::{is_cw20_denom, is_cw721_denom, Asset};
+use crate::msg::{MakeSwapMsg, UpdateSwapMsg};
+use crate::state::{top_bid, AtomicSwapOrder, BidStatus, FEE_INFO, KEEPER_REWARDS};
+use crate::ContractError;
 
-const FEE_BASIS_POINT: u64 = 10000;
+pub const FEE_BASIS_POINT: u64 = 10000;
 
 pub fn generate_order_id(order_path: &str) -> StdResult<String> {
     // Generate random bytes
@@ -39,19 +42,14 @@ pub fn extract_source_channel_for_taker_msg(path: &str) -> StdResult<String> {
     Ok(parts[5].to_string())
 }
 
-pub(crate) fn send_tokens(to: &Addr, amount: Coin) -> StdResult<SubMsg> {
-    let msg = BankMsg::Send {
-        to_address: to.into(),
-        amount: vec![amount],
-    };
-    Ok(SubMsg::new(msg))
-}
-
-/// Calculates taker fees and returns (fee, Value - fee)
+/// Calculates taker fees and returns (fee, Value - fee). NFTs are not charged, their
+/// fee is zero.
 pub fn taker_fee(deps: Deps, amount: &Uint128, denom: String) -> (Coin, Coin, Addr) {
     let fee_info = FEE_INFO.load(deps.storage).unwrap();
     let mut fee = (amount * Uint128::from(fee_info.taker_fee)) / Uint128::from(FEE_BASIS_POINT);
-    if fee.is_zero() {
+    if is_cw721_denom(&denom) {
+        fee = Uint128::zero();
+    } else if fee.is_zero() {
         fee = Uint128::from(1u64);
     }
     let treasury_address = deps.api.addr_validate(&fee_info.treasury).unwrap();
@@ -88,3 +86,285 @@ pub fn maker_fee(deps: Deps, amount: &Uint128, denom: String) -> (Coin, Coin, Ad
         treasury_address,
     )
 }
+
+/// Takes the keeper reward for a refund out of KEEPER_REWARDS, as much of it as the treasury funded
+pub fn keeper_fee(storage: &mut dyn Storage, refund: &Coin) -> StdResult<Coin> {
+    let fee_info = FEE_INFO.load(storage)?;
+    let balance = KEEPER_REWARDS.may_load(storage, &refund.denom)?.unwrap_or_default();
+    let fee = refund.amount.multiply_ratio(fee_info.keeper_fee, FEE_BASIS_POINT).min(balance);
+    if !fee.is_zero() {
+        KEEPER_REWARDS.save(storage, &refund.denom, &balance.checked_sub(fee)?)?;
+    }
+    Ok(Coin {
+        denom: refund.denom.clone(),
+        amount: fee,
+    })
+}
+
+/// Whether `funds` are exactly `expected`, in any order
+pub fn is_exact_funds(funds: &[Coin], expected: &[Coin]) -> bool {
+    funds.len() == expected.len() && expected.iter().all(|coin| funds.contains(coin))
+}
+
+/// Pays the rest of a basket order, each token less its own fee
+pub(crate) fn basket_payouts(
+    deps: Deps,
+    order: &AtomicSwapOrder,
+    maker: &Addr,
+    taker: &Addr,
+) -> StdResult<Vec<SubMsg>> {
+    let mut submsg = vec![];
+    for token in &order.maker.extra_buy_tokens {
+        let (fee, send, treasury) = maker_fee(deps, &token.amount, token.denom.clone());
+        submsg.push(Asset::from(send).transfer_msg(maker)?);
+        submsg.push(Asset::from(fee).transfer_msg(&treasury)?);
+    }
+    for token in &order.maker.extra_sell_tokens {
+        let (fee, send, treasury) = taker_fee(deps, &token.amount, token.denom.clone());
+        submsg.push(Asset::from(send).transfer_msg(taker)?);
+        submsg.push(Asset::from(fee).transfer_msg(&treasury)?);
+    }
+    Ok(submsg)
+}
+
+/// Slice of the sell token a partial fill pays for, at the order's price. The fill
+/// completing the order gets all the sell token left, so no dust stays behind.
+pub(crate) fn partial_fill(order: &AtomicSwapOrder, paid: &Coin) -> Result<Coin, ContractError> {
+    let remaining = order.remaining_buy();
+    if paid.denom != remaining.denom {
+        return Err(ContractError::InvalidSellToken);
+    }
+    if paid.amount.is_zero() || paid.amount > remaining.amount {
+        return Err(ContractError::InvalidFillAmount);
+    }
+    if paid.amount == remaining.amount {
+        return Ok(order.remaining_sell());
+    }
+    if order.maker.min_fill_amount.is_some_and(|min| paid.amount < min) {
+        return Err(ContractError::InvalidFillAmount);
+    }
+
+    let amount = order
+        .maker
+        .sell_token
+        .amount
+        .multiply_ratio(paid.amount, order.maker.buy_token.amount);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidFillAmount);
+    }
+    Ok(Coin {
+        denom: order.maker.sell_token.denom.clone(),
+        amount,
+    })
+}
+
+/// Basket orders settle whole, in native tokens with one coin per denom on each side
+pub fn validate_basket(msg: &MakeSwapMsg) -> StdResult<()> {
+    if !msg.is_basket() {
+        return Ok(());
+    }
+    if msg.allow_partial || msg.take_bids || msg.vesting.is_some() {
+        return Err(StdError::generic_err(
+            "Basket orders can't be filled in parts, take bids or vest",
+        ));
+    }
+    for tokens in [msg.sell_tokens(), msg.buy_tokens()] {
+        for (i, coin) in tokens.iter().enumerate() {
+            if coin.amount.is_zero()
+                || is_cw20_denom(&coin.denom)
+                || is_cw721_denom(&coin.denom)
+                || tokens[..i].iter().any(|other| other.denom == coin.denom)
+            {
+                return Err(StdError::generic_err(
+                    "Basket tokens must be native, not empty and of different denoms",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies an UpdateSwap to an order. The buy denom can't change, so placed bids stay
+/// valid, and a partly filled order keeps its price.
+pub fn update_order(order: &mut AtomicSwapOrder, msg: &UpdateSwapMsg) -> Result<(), ContractError> {
+    // Bidders and takers of an auction count on its terms
+    if order.maker.auction.is_some() || order.maker.dutch_auction.is_some() || order.maker.sealed_bid.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Auctions can't be updated".to_string(),
+        )));
+    }
+    let mut maker = order.maker.clone();
+    if let Some(buy_token) = &msg.buy_token {
+        if buy_token.denom != maker.buy_token.denom || buy_token.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Only the amount of the buy token can change".to_string(),
+            )));
+        }
+        if order.fill_count > 0 && buy_token.amount != maker.buy_token.amount {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The price of a partly filled order can't change".to_string(),
+            )));
+        }
+        maker.buy_token = buy_token.clone();
+    }
+    if let Some(expiration_timestamp) = msg.expiration_timestamp {
+        maker.expiration_timestamp = expiration_timestamp;
+    }
+    if let Some(min_bid_price) = msg.min_bid_price {
+        maker.min_bid_price = Some(min_bid_price);
+    }
+    if let Some(take_bids) = msg.take_bids {
+        maker.take_bids = take_bids;
+    }
+    if let Some(desired_taker) = &msg.desired_taker {
+        maker.desired_taker = desired_taker.clone();
+    }
+
+    validate_basket(&maker)?;
+    if maker.allow_partial {
+        if maker.take_bids {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Partial fills can't be combined with bids or vesting".to_string(),
+            )));
+        }
+        if maker.min_fill_amount.is_some_and(|min| min > maker.buy_token.amount) {
+            return Err(ContractError::InvalidFillAmount);
+        }
+    }
+
+    order.min_bid_price = maker.min_bid_price;
+    order.maker = maker;
+    Ok(())
+}
+
+/// Auctions sell the whole order to the highest bid from anyone, before the order expires
+pub fn validate_auction(msg: &MakeSwapMsg, now: u64) -> StdResult<()> {
+    let Some(auction) = &msg.auction else {
+        return Ok(());
+    };
+    if !msg.take_bids || !msg.desired_taker.is_empty() {
+        return Err(StdError::generic_err("Auctions must take bids from anyone"));
+    }
+    if auction.end_time <= now || auction.end_time > msg.expiration_timestamp {
+        return Err(StdError::generic_err(
+            "Auctions must end after now and before the order expires",
+        ));
+    }
+    Ok(())
+}
+
+/// Dutch auctions are taken whole, at a price falling from `buy_token` to the floor
+pub fn validate_dutch_auction(msg: &MakeSwapMsg) -> StdResult<()> {
+    let Some(dutch) = &msg.dutch_auction else {
+        return Ok(());
+    };
+    if msg.take_bids || msg.allow_partial || msg.auction.is_some() {
+        return Err(StdError::generic_err(
+            "Dutch auctions can't take bids or partial fills",
+        ));
+    }
+    if dutch.floor_price.is_zero() || dutch.floor_price > msg.buy_token.amount {
+        return Err(StdError::generic_err(
+            "The floor price must be between zero and the buy token amount",
+        ));
+    }
+    if dutch.start_time >= dutch.end_time {
+        return Err(StdError::generic_err(
+            "Dutch auctions must end after they start",
+        ));
+    }
+    Ok(())
+}
+
+/// Sealed bids are committed and revealed during their own phases, before the expiration
+pub fn validate_sealed_bid(msg: &MakeSwapMsg, now: u64) -> StdResult<()> {
+    let Some(sealed) = &msg.sealed_bid else {
+        return Ok(());
+    };
+    if !msg.take_bids || !msg.desired_taker.is_empty() || msg.auction.is_some() {
+        return Err(StdError::generic_err("Sealed-bid auctions must take bids from anyone"));
+    }
+    if sealed.commit_end <= now || sealed.reveal_end <= sealed.commit_end || sealed.reveal_end > msg.expiration_timestamp {
+        return Err(StdError::generic_err(
+            "Sealed bids must be committed, then revealed, before the order expires",
+        ));
+    }
+    Ok(())
+}
+
+/// Hash a sealed bid is committed with
+pub fn sealed_bid_commitment(order_id: &str, bidder: &str, amount: Uint128, salt: &str) -> String {
+    let hash = Sha256::digest(format!("{}/{}/{}/{}", order_id, bidder, amount, salt).as_bytes());
+    hex::encode(hash)
+}
+
+/// Sealed bids only go through CommitBid and RevealBid
+pub fn ensure_not_sealed(order: &AtomicSwapOrder) -> Result<(), ContractError> {
+    if order.maker.sealed_bid.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Sealed bids are made with CommitBid and RevealBid".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Auction bids are only taken by SettleAuction
+pub fn ensure_not_auction(order: &AtomicSwapOrder) -> Result<(), ContractError> {
+    if order.maker.auction.is_some() || order.maker.sealed_bid.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Auction bids are settled with SettleAuction".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Bidders of an auction are committed until it settles, so is the maker. Sealed bids
+/// left unrevealed are forfeited.
+pub fn ensure_no_auction_bids(storage: &dyn Storage, now: u64, order: &AtomicSwapOrder) -> Result<(), ContractError> {
+    let sealing = order.maker.sealed_bid.as_ref().is_some_and(|sealed| now < sealed.reveal_end);
+    if (order.maker.auction.is_some() || order.maker.sealed_bid.is_some())
+        && top_bid(storage, &order.id, |bid| {
+            bid.status == BidStatus::Placed || (sealing && bid.status == BidStatus::Committed)
+        })?
+        .is_some()
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "An auction with bids can't be cancelled".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a bid of `amount` on an auction against its reserve and the highest bid of the
+/// others. A bid in the extension window moves the end back, and the expiration with it.
+/// Returns whether the order is an auction and has to be saved.
+pub fn place_auction_bid(
+    storage: &dyn Storage,
+    now: u64,
+    order: &mut AtomicSwapOrder,
+    bidder: &str,
+    amount: Uint128,
+) -> Result<bool, ContractError> {
+    let Some(auction) = order.maker.auction.as_mut() else {
+        return Ok(false);
+    };
+    if now >= auction.end_time {
+        return Err(ContractError::AuctionEnded);
+    }
+    if amount < auction.reserve_price {
+        return Err(ContractError::InvalidBidAmount);
+    }
+    let highest = top_bid(storage, &order.id, |bid| {
+        bid.bidder != bidder && matches!(bid.status, BidStatus::Initial | BidStatus::Placed)
+    })?;
+    if let Some((_, highest)) = highest {
+        if amount <= highest.bid.amount || amount < highest.bid.amount + auction.min_increment {
+            return Err(ContractError::InvalidBidAmount);
+        }
+    }
+    if auction.end_time - now < auction.extension_window {
+        auction.end_time = now + auction.extension_window;
+        order.maker.expiration_timestamp = order.maker.expiration_timestamp.max(auction.end_time);
+    }
+    Ok(true)
+}
@@ -0,0 +1,164 @@
+//! Native coins, cw20 tokens and cw721 NFTs behind one type.
+//!
+//! Orders and bids keep amounts as `Coin`. A cw20 token uses the denom
+//! `cw20:<contract address>`, so prices, fees and bid indexes work the same for both.
+//! An NFT is the coin `1 cw721:<contract address>/<token id>`.
+
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Binary, Coin, StdResult, SubMsg, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use cw721::Cw721ExecuteMsg;
+
+pub const CW20_PREFIX: &str = "cw20:";
+pub const CW721_PREFIX: &str = "cw721:";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssetInfo {
+    Native(String),
+    Cw20(Addr),
+    Cw721 { contract: Addr, token_id: String },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Asset {
+    pub info: AssetInfo,
+    pub amount: Uint128,
+}
+
+pub fn is_cw20_denom(denom: &str) -> bool {
+    denom.starts_with(CW20_PREFIX)
+}
+
+pub fn is_cw721_denom(denom: &str) -> bool {
+    denom.starts_with(CW721_PREFIX)
+}
+
+impl Asset {
+    pub fn cw20(contract: Addr, amount: Uint128) -> Self {
+        Asset {
+            info: AssetInfo::Cw20(contract),
+            amount,
+        }
+    }
+
+    pub fn cw721(contract: Addr, token_id: String) -> Self {
+        Asset {
+            info: AssetInfo::Cw721 { contract, token_id },
+            amount: Uint128::new(1),
+        }
+    }
+
+    pub fn to_coin(&self) -> Coin {
+        let denom = match &self.info {
+            AssetInfo::Native(denom) => denom.clone(),
+            AssetInfo::Cw20(contract) => format!("{}{}", CW20_PREFIX, contract),
+            AssetInfo::Cw721 { contract, token_id } => {
+                format!("{}{}/{}", CW721_PREFIX, contract, token_id)
+            }
+        };
+        Coin {
+            denom,
+            amount: self.amount,
+        }
+    }
+
+    /// Pays the asset out of the contract
+    pub fn transfer_msg(&self, to: &Addr) -> StdResult<SubMsg> {
+        let msg = match &self.info {
+            AssetInfo::Native(denom) => SubMsg::new(BankMsg::Send {
+                to_address: to.into(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount: self.amount,
+                }],
+            }),
+            AssetInfo::Cw20(contract) => SubMsg::new(WasmMsg::Execute {
+                contract_addr: contract.into(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: to.into(),
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            }),
+            AssetInfo::Cw721 { contract, token_id } => SubMsg::new(WasmMsg::Execute {
+                contract_addr: contract.into(),
+                msg: to_json_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: to.into(),
+                    token_id: token_id.clone(),
+                })?,
+                funds: vec![],
+            }),
+        };
+        Ok(msg)
+    }
+
+    /// Executes `msg` on another contract with the asset attached. Cw20 tokens and NFTs are
+    /// sent with `msg` as the hook, so the contract receives it through its Receive message.
+    pub fn execute_msg(&self, contract: String, msg: Binary) -> StdResult<SubMsg> {
+        let msg = match &self.info {
+            AssetInfo::Native(denom) => SubMsg::new(WasmMsg::Execute {
+                contract_addr: contract,
+                msg,
+                funds: vec![Coin {
+                    denom: denom.clone(),
+                    amount: self.amount,
+                }],
+            }),
+            AssetInfo::Cw20(token) => SubMsg::new(WasmMsg::Execute {
+                contract_addr: token.into(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                    contract,
+                    amount: self.amount,
+                    msg,
+                })?,
+                funds: vec![],
+            }),
+            AssetInfo::Cw721 {
+                contract: nft,
+                token_id,
+            } => SubMsg::new(WasmMsg::Execute {
+                contract_addr: nft.into(),
+                msg: to_json_binary(&Cw721ExecuteMsg::SendNft {
+                    contract,
+                    token_id: token_id.clone(),
+                    msg,
+                })?,
+                funds: vec![],
+            }),
+        };
+        Ok(msg)
+    }
+}
+
+/// Pays each of `coins` out of the contract
+pub fn transfer_msgs(coins: Vec<Coin>, to: &Addr) -> StdResult<Vec<SubMsg>> {
+    coins
+        .into_iter()
+        .map(|coin| Asset::from(coin).transfer_msg(to))
+        .collect()
+}
+
+impl From<Coin> for Asset {
+    fn from(coin: Coin) -> Self {
+        // Contract addresses were validated when the tokens were received
+        let info = if let Some(contract) = coin.denom.strip_prefix(CW20_PREFIX) {
+            AssetInfo::Cw20(Addr::unchecked(contract))
+        } else if let Some((contract, token_id)) = coin
+            .denom
+            .strip_prefix(CW721_PREFIX)
+            .and_then(|nft| nft.split_once('/'))
+        {
+            AssetInfo::Cw721 {
+                contract: Addr::unchecked(contract),
+                token_id: token_id.to_string(),
+            }
+        } else {
+            AssetInfo::Native(coin.denom)
+        };
+        Asset {
+            info,
+            amount: coin.amount,
+        }
+    }
+}
@@ -2,21 +2,38 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    asset::{transfer_msgs, Asset},
+    codec::{
+        bid_operation, decode_packet, encode_ack_result, required_order_id, required_path,
+        PacketMsg,
+    },
     error::ContractError,
     msg::{
-        AtomicSwapPacketData, CancelBidMsg, CancelSwapMsg, Height, MakeBidMsg, MakeSwapMsg,
-        SwapMessageType, TakeBidMsg, TakeSwapMsg, UpdateBidMsg,
+        AtomicSwapPacketData, CancelBidMsg, CancelSwapMsg, ExpireSwapMsg, Height, MakeBidMsg,
+        MakeSwapMsg, RejectBidMsg, SwapMessageType, TakeBidMsg, TakeSwapMsg, UpdateBidMsg,
+        UpdateSwapMsg,
     },
     state::{
-        append_atomic_order, bid_key, bids, get_atomic_order, move_order_to_bottom,
-        set_atomic_order, AtomicSwapOrder, Bid, BidStatus, Side, Status, VestingDetails,
-        VestingExecuteMsg::StartVesting, CONFIG, ORDER_TO_COUNT,
+        append_atomic_order, bid_key, bids, counter_offers, failed_packet_key, get_atomic_order,
+        may_get_atomic_order, move_order_to_bottom, pending_packets, set_atomic_order, top_bid,
+        AtomicSwapOrder, Bid, BidStatus, CounterOfferStatus, FailedPacket, Fill, RemoteState, Side,
+        Status, VestingDetails, VestingExecuteMsg::StartVesting, BID_REFUNDS, CHANNEL_INFO,
+        CHANNEL_REFUNDS, CONFIG, FAILED_PACKETS, FILLS, MAX_BID_REFUNDS, MAX_CHANNEL_REFUNDS,
+        ORDER_TO_COUNT, SWAP_ORDERS,
+    },
+    utils::{
+        authenticate_packet, counterparty_prefix, enforce_counterparty_chain,
+        ensure_new_bid_operation, ensure_not_pending, local_channel_for_order, maker_fee,
+        partial_fill, record_bid_operation, taker_fee, try_get_ack_result, update_order,
+        validate_remote_address,
     },
-    utils::{decode_make_swap_msg, decode_take_swap_msg, maker_fee, send_tokens, taker_fee},
 };
 use cosmwasm_std::{
-    attr, from_json, to_json_binary, Addr, Binary, Coin, DepsMut, Env, IbcBasicResponse, IbcPacket, IbcReceiveResponse, SubMsg, Timestamp, Uint128, WasmMsg
+    attr, to_json_binary, Addr, Binary, Coin, Deps, DepsMut, Env, Event, IbcAcknowledgement,
+    IbcBasicResponse, IbcPacket, IbcReceiveResponse, Order, StdError, StdResult, Storage, SubMsg,
+    Timestamp, Uint128,
 };
+use cw_storage_plus::Bound;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -31,6 +48,34 @@ pub(crate) fn ack_success() -> Binary {
     to_json_binary(&res).unwrap()
 }
 
+// create a serialized success message carrying the state recorded on this chain
+pub(crate) fn ack_result(state: RemoteState) -> Binary {
+    let res = AtomicSwapPacketAcknowledgement::Result(encode_ack_result(&state).unwrap());
+    to_json_binary(&res).unwrap()
+}
+
+fn order_state(order_id: &str, status: Status, env: &Env) -> RemoteState {
+    RemoteState {
+        order_id: order_id.to_string(),
+        status: Some(status),
+        bid_status: None,
+        bid_amount: None,
+        price: None,
+        timestamp: env.block.time.seconds(),
+    }
+}
+
+fn bid_state(bid: &Bid, env: &Env) -> RemoteState {
+    RemoteState {
+        order_id: bid.order.clone(),
+        status: None,
+        bid_status: Some(bid.status.clone()),
+        bid_amount: Some(bid.bid.amount),
+        price: None,
+        timestamp: env.block.time.seconds(),
+    }
+}
+
 // create a serialized error message
 pub(crate) fn ack_fail(err: String) -> Binary {
     let res = AtomicSwapPacketAcknowledgement::Error(err);
@@ -38,11 +83,27 @@ pub(crate) fn ack_fail(err: String) -> Binary {
 }
 
 pub(crate) fn do_ibc_packet_receive(
+    mut deps: DepsMut,
+    env: Env,
+    packet: &IbcPacket,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let operation = bid_operation(&decode_packet(&packet.data)?)?;
+    if let Some((key, nonce)) = &operation {
+        ensure_new_bid_operation(deps.storage, key, *nonce)?;
+    }
+    let res = receive_packet(deps.branch(), env, packet)?;
+    if let Some((key, nonce)) = operation {
+        record_bid_operation(deps.storage, key, nonce)?;
+    }
+    Ok(res)
+}
+
+fn receive_packet(
     deps: DepsMut,
     env: Env,
     packet: &IbcPacket,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet_data: AtomicSwapPacketData = from_json(&packet.data)?;
+    let packet_data = decode_packet(&packet.data)?;
 
     match packet_data.r#type {
         SwapMessageType::Unspecified => {
@@ -53,34 +114,82 @@ pub(crate) fn do_ibc_packet_receive(
             Ok(res)
         }
         SwapMessageType::MakeSwap => {
-            let msg: MakeSwapMsg = decode_make_swap_msg(&packet_data.data);
+            let msg = MakeSwapMsg::decode(&packet_data.data)?;
+            // The order is created by this packet, so its own path is checked
+            let path = required_path(&packet_data)?;
+            authenticate_packet(deps.as_ref(), packet, &path, &Side::Remote)?;
+            // The maker's buy token leaves this chain
+            enforce_counterparty_chain(
+                deps.as_ref(),
+                &packet.dest.channel_id,
+                &msg.buy_token.denom,
+                &msg.sell_token.denom,
+            )?;
             on_received_make(deps, env, packet, msg)
         }
         SwapMessageType::TakeSwap => {
-            let msg: TakeSwapMsg = decode_take_swap_msg(&packet_data.data);
+            let msg = TakeSwapMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
             on_received_take(deps, env, packet, msg)
         }
         SwapMessageType::CancelSwap => {
-            let msg: CancelSwapMsg = from_json(&packet_data.data)?;
+            let msg = CancelSwapMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Remote)?;
             on_received_cancel(deps, env, packet, msg)
         }
+        SwapMessageType::UpdateSwap => {
+            let msg = UpdateSwapMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Remote)?;
+            on_received_update(deps, env, packet, msg)
+        }
         SwapMessageType::MakeBid => {
-            let msg: MakeBidMsg = from_json(&packet_data.data)?;
+            let msg = MakeBidMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
             on_received_make_bid(deps, env, packet, msg)
         }
         SwapMessageType::TakeBid => {
-            let msg: TakeBidMsg = from_json(&packet_data.data)?;
+            let msg = TakeBidMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
             on_received_take_bid(deps, env, packet, msg)
         }
         SwapMessageType::CancelBid => {
-            let msg: CancelBidMsg = from_json(&packet_data.data)?;
+            let msg = CancelBidMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
             on_received_cancel_bid(deps, env, packet, msg)
         }
         SwapMessageType::UpdateBid => {
-            let msg: UpdateBidMsg = from_json(&packet_data.data)?;
+            let msg = UpdateBidMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
             on_received_update_bid(deps, env, packet, msg)
         }
+        // Either chain can expire an order
+        SwapMessageType::ExpireSwap => {
+            let msg = ExpireSwapMsg::decode(&packet_data.data)?;
+            let order = get_atomic_order(deps.storage, &msg.order_id)?;
+            authenticate_packet(deps.as_ref(), packet, &order.path, &order.side)?;
+            on_received_expire(deps, env, order)
+        }
+        SwapMessageType::RejectBid => {
+            let msg = RejectBidMsg::decode(&packet_data.data)?;
+            authenticate_order_packet(deps.as_ref(), packet, &msg.order_id, Side::Native)?;
+            on_received_reject_bid(deps, env, packet, msg)
+        }
+    }
+}
+
+/// Loads the order a packet refers to and checks that the packet came over the path of
+/// the order and is meant for the side the order has on this chain.
+fn authenticate_order_packet(
+    deps: Deps,
+    packet: &IbcPacket,
+    order_id: &str,
+    side: Side,
+) -> Result<(), ContractError> {
+    let order = get_atomic_order(deps.storage, order_id)?;
+    if order.side != side {
+        return Err(ContractError::InvalidChain);
     }
+    authenticate_packet(deps, packet, &order.path, &side)
 }
 
 pub(crate) fn on_received_make(
@@ -89,9 +198,17 @@ pub(crate) fn on_received_make(
     packet: &IbcPacket,
     msg: MakeSwapMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
-    let packet_data: AtomicSwapPacketData = from_json(&packet.data)?;
-    let order_id = packet_data.order_id.unwrap();
-    let path = packet_data.path.unwrap();
+    let packet_data = decode_packet(&packet.data)?;
+    let order_id = required_order_id(&packet_data)?;
+    let path = required_path(&packet_data)?;
+
+    // The maker is on the counterparty chain, the receiving address and taker are here
+    let prefix = counterparty_prefix(deps.as_ref(), &packet.dest.channel_id)?;
+    validate_remote_address(&msg.maker_address, &prefix)?;
+    deps.api.addr_validate(&msg.maker_receiving_address)?;
+    if !msg.desired_taker.is_empty() {
+        deps.api.addr_validate(&msg.desired_taker)?;
+    }
     let swap_order = AtomicSwapOrder {
         id: order_id.clone(),
         side: Side::Remote,
@@ -104,6 +221,12 @@ pub(crate) fn on_received_make(
         create_timestamp: env.block.time.seconds(),
         min_bid_price: msg.min_bid_price,
         vesting_details: msg.vesting,
+        remote: None,
+        pending: None,
+        last_error: None,
+        filled_sell_amount: Uint128::zero(),
+        filled_buy_amount: Uint128::zero(),
+        fill_count: 0,
     };
 
     let count_check = ORDER_TO_COUNT.may_load(deps.storage, &order_id)?;
@@ -114,7 +237,7 @@ pub(crate) fn on_received_make(
     }
 
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(order_state(&order_id, Status::Sync, &env)))
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -126,16 +249,40 @@ pub(crate) fn on_received_make(
 pub(crate) fn on_received_take(
     deps: DepsMut,
     env: Env,
-    _packet: &IbcPacket,
+    packet: &IbcPacket,
     msg: TakeSwapMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let order_id = msg.order_id.clone();
     let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
 
-    if msg.sell_token != swap_order.maker.buy_token {
-        return Err(ContractError::InvalidSellToken);
+    let prefix = counterparty_prefix(deps.as_ref(), &packet.dest.channel_id)?;
+    validate_remote_address(&msg.taker_address, &prefix)?;
+
+    // Dutch auctions are priced by this chain's clock, the taker chain refunds the surplus
+    let price = swap_order.current_price(env.block.time.seconds());
+    if swap_order.maker.dutch_auction.is_some() {
+        if msg.sell_token.denom != price.denom {
+            return Err(ContractError::InvalidSellToken);
+        }
+        if msg.sell_token.amount < price.amount {
+            return Err(ContractError::BelowCurrentPrice {
+                price: price.to_string(),
+            });
+        }
     }
 
+    // Partial orders take a slice of the remaining buy token, others the whole of it
+    let sell_token = if swap_order.maker.allow_partial {
+        partial_fill(&swap_order, &msg.sell_token)?
+    } else if swap_order.maker.dutch_auction.is_some() {
+        swap_order.maker.sell_token.clone()
+    } else {
+        if msg.sell_token != swap_order.maker.buy_token {
+            return Err(ContractError::InvalidSellToken);
+        }
+        swap_order.maker.sell_token.clone()
+    };
+
     if !swap_order.maker.desired_taker.is_empty()
         && swap_order.maker.desired_taker != msg.taker_address
     {
@@ -144,12 +291,13 @@ pub(crate) fn on_received_take(
 
     let taker_receiving_address = deps.api.addr_validate(&msg.taker_receiving_address)?;
 
-    let (fee, taker_amount, treasury) = taker_fee(
-        deps.as_ref(),
-        &swap_order.maker.sell_token.amount,
-        swap_order.maker.sell_token.denom.clone(),
-    );
-    let mut submsg: Vec<SubMsg> = vec![send_tokens(&treasury, fee)?];
+    let (fee, taker_amount, treasury) =
+        taker_fee(deps.as_ref(), &sell_token.amount, sell_token.denom.clone())?;
+    let mut submsg: Vec<SubMsg> = vec![];
+    // Sold NFTs are not charged
+    if !fee.amount.is_zero() {
+        submsg.push(Asset::from(fee).transfer_msg(&treasury)?);
+    }
     // Vesting details and check
     if let Some(val) = swap_order.vesting_details.clone() {
         let cfg = CONFIG.load(deps.storage)?;
@@ -167,25 +315,62 @@ pub(crate) fn on_received_take(
             order_id: swap_order.id.clone(),
         };
 
-        submsg.push(SubMsg::new(WasmMsg::Execute {
-            contract_addr: cfg.vesting_contract,
-            msg: to_json_binary(&vesting_msg)?,
-            funds: vec![taker_amount],
-        }));
+        submsg.push(
+            Asset::from(taker_amount)
+                .execute_msg(cfg.vesting_contract, to_json_binary(&vesting_msg)?)?,
+        );
     } else {
-        submsg.push(send_tokens(&taker_receiving_address, taker_amount)?);
+        submsg.push(Asset::from(taker_amount).transfer_msg(&taker_receiving_address)?);
+    }
+    submsg.extend(basket_payouts(
+        deps.as_ref(),
+        &swap_order.maker.extra_sell_tokens,
+        &taker_receiving_address,
+        taker_fee,
+    )?);
+
+    if swap_order.maker.allow_partial {
+        let exhausted = record_fill(deps.storage, &env, &mut swap_order, &msg, &sell_token)?;
+        // The order stays open until the last slice is taken
+        if !exhausted {
+            set_atomic_order(deps.storage, &order_id, &swap_order)?;
+            let res = IbcReceiveResponse::new()
+                .set_ack(ack_result(order_state(&order_id, Status::Sync, &env)))
+                .add_submessages(submsg)
+                .add_attribute("order_id", order_id)
+                .add_attribute("remaining", swap_order.remaining_sell().to_string())
+                .add_attribute("action", "receive")
+                .add_attribute("success", "true")
+                .add_attribute("action", "take_swap_received");
+            return Ok(res);
+        }
     }
 
+    let dutch = swap_order.maker.dutch_auction.is_some();
     swap_order.status = Status::Complete;
-    swap_order.taker = Some(msg.clone());
+    swap_order.taker = Some(if dutch {
+        TakeSwapMsg {
+            sell_token: price.clone(),
+            ..msg.clone()
+        }
+    } else {
+        msg.clone()
+    });
     swap_order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
 
     set_atomic_order(deps.storage, &msg.order_id, &swap_order)?;
     move_order_to_bottom(deps.storage, &msg.order_id)?;
 
+    let (refunds, events) = refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(RemoteState {
+            price: dutch.then_some(price.amount),
+            ..order_state(&order_id, Status::Complete, &env)
+        }))
         .add_submessages(submsg)
+        .add_events(events)
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -215,12 +400,30 @@ pub(crate) fn on_received_cancel(
         return Err(ContractError::AlreadyTakenOrder);
     }
 
+    // Bidders of an auction are committed until it settles, so is the maker
+    if swap_order.maker.auction.is_some()
+        && top_bid(deps.storage, &order_id, |bid| {
+            bid.status == BidStatus::Placed
+        })?
+        .is_some()
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "An auction with bids can't be cancelled".to_string(),
+        )));
+    }
+    // A take bid sent from here could still complete on the other chain
+    ensure_not_pending(&swap_order.pending)?;
+
     swap_order.status = Status::Cancel;
     swap_order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
     set_atomic_order(deps.storage, &msg.order_id, &swap_order)?;
 
+    let (submsg, events) = refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(order_state(&order_id, Status::Cancel, &env)))
+        .add_submessages(submsg)
+        .add_events(events)
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -229,13 +432,92 @@ pub(crate) fn on_received_cancel(
     Ok(res)
 }
 
-pub(crate) fn on_received_make_bid(
+pub(crate) fn on_received_update(
     deps: DepsMut,
     env: Env,
     _packet: &IbcPacket,
+    msg: UpdateSwapMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let order_id = msg.order_id.clone();
+    let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
+
+    if swap_order.status != Status::Sync && swap_order.status != Status::Initial {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    // Taken on this chain before the update arrived, the taker keeps the old terms
+    if swap_order.taker.is_some() {
+        return Err(ContractError::AlreadyTakenOrder);
+    }
+    ensure_not_pending(&swap_order.pending)?;
+
+    let bids_closed = swap_order.maker.take_bids && msg.take_bids == Some(false);
+    update_order(&mut swap_order, &msg)?;
+    set_atomic_order(deps.storage, &order_id, &swap_order)?;
+
+    let (submsg, events) = if bids_closed {
+        refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?
+    } else {
+        (vec![], vec![])
+    };
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_result(order_state(&order_id, swap_order.status, &env)))
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "receive")
+        .add_attribute("success", "true")
+        .add_attribute("action", "update_swap_received");
+
+    Ok(res)
+}
+
+pub(crate) fn on_received_expire(
+    deps: DepsMut,
+    env: Env,
+    order: AtomicSwapOrder,
+) -> Result<IbcReceiveResponse, ContractError> {
+    if order.status != Status::Sync && order.status != Status::Initial {
+        return Err(ContractError::InvalidStatus);
+    }
+    // Checked against the clock of this chain as well
+    if env.block.time.seconds() <= order.maker.expiration_timestamp {
+        return Err(ContractError::NotExpired);
+    }
+    if order.taker.is_some() {
+        return Err(ContractError::AlreadyTakenOrder);
+    }
+    // A cancel or take bid sent from here could still complete on the other chain
+    ensure_not_pending(&order.pending)?;
+
+    let order_id = order.id.clone();
+    let (submsg, events) = expire_order(deps, &env, order)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_result(order_state(&order_id, Status::Cancel, &env)))
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "receive")
+        .add_attribute("success", "true")
+        .add_attribute("action", "expire_swap_received");
+
+    Ok(res)
+}
+
+pub(crate) fn on_received_make_bid(
+    deps: DepsMut,
+    env: Env,
+    packet: &IbcPacket,
     msg: MakeBidMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let order_id = msg.order_id.clone();
+
+    // The bidder is on the counterparty chain and is paid out here
+    let prefix = counterparty_prefix(deps.as_ref(), &packet.dest.channel_id)?;
+    validate_remote_address(&msg.taker_address, &prefix)?;
+    deps.api.addr_validate(&msg.taker_receiving_address)?;
     let key = bid_key(&msg.order_id, &msg.taker_address);
 
     let bid: Bid = Bid {
@@ -246,12 +528,16 @@ pub(crate) fn on_received_make_bid(
         bidder_receiver: msg.taker_receiving_address,
         receive_timestamp: env.block.time.seconds(), //TODO get from packet
         expire_timestamp: msg.expiration_timestamp,
+        remote: None,
+        pending: None,
+        last_error: None,
+        nonce: 0,
     };
 
     bids().save(deps.storage, key, &bid)?;
 
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(bid_state(&bid, &env)))
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -269,15 +555,15 @@ pub(crate) fn on_received_take_bid(
     let order_id = msg.order_id.clone();
     let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
 
+    // Check before writing anything, state changes are kept when the receive fails
     let key = bid_key(&msg.order_id, &msg.bidder);
-    if !bids().has(deps.storage, key.clone()) {
+    let mut bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
+    if bid.status != BidStatus::Placed {
         return Err(ContractError::BidDoesntExist);
     }
 
-    let mut bid = bids().load(deps.storage, key.clone())?;
-    bid.status = BidStatus::Executed;
-    bids().save(deps.storage, key, &bid)?;
-
     if !swap_order.maker.desired_taker.is_empty() && swap_order.maker.desired_taker != msg.bidder {
         return Err(ContractError::InvalidTakerAddress);
     }
@@ -288,8 +574,11 @@ pub(crate) fn on_received_take_bid(
         deps.as_ref(),
         &swap_order.maker.sell_token.amount,
         swap_order.maker.sell_token.denom.clone(),
-    );
-    let mut submsg: Vec<SubMsg> = vec![send_tokens(&treasury, fee)?];
+    )?;
+    let mut submsg: Vec<SubMsg> = vec![];
+    if !fee.amount.is_zero() {
+        submsg.push(Asset::from(fee).transfer_msg(&treasury)?);
+    }
     // Vesting details and check
     if let Some(val) = swap_order.vesting_details.clone() {
         let cfg = CONFIG.load(deps.storage)?;
@@ -307,15 +596,16 @@ pub(crate) fn on_received_take_bid(
             order_id: swap_order.id.clone(),
         };
 
-        submsg.push(SubMsg::new(WasmMsg::Execute {
-            contract_addr: cfg.vesting_contract,
-            msg: to_json_binary(&vesting_msg)?,
-            funds: vec![taker_amount],
-        }));
+        submsg.push(
+            Asset::from(taker_amount)
+                .execute_msg(cfg.vesting_contract, to_json_binary(&vesting_msg)?)?,
+        );
     } else {
-        submsg.push(send_tokens(&taker_receiving_address, taker_amount)?);
+        submsg.push(Asset::from(taker_amount).transfer_msg(&taker_receiving_address)?);
     }
 
+    bid.status = BidStatus::Executed;
+    let executed_bid = bid.clone();
     let take_msg: TakeSwapMsg = TakeSwapMsg {
         order_id: order_id.clone(),
         sell_token: bid.bid,
@@ -331,13 +621,21 @@ pub(crate) fn on_received_take_bid(
     swap_order.taker = Some(take_msg);
     swap_order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
 
+    bids().save(deps.storage, key, &executed_bid)?;
     set_atomic_order(deps.storage, &msg.order_id, &swap_order)?;
     move_order_to_bottom(deps.storage, &msg.order_id)?;
     // bids().remove(deps.storage, key)?;
 
+    let (refunds, events) = refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(RemoteState {
+            status: Some(Status::Complete),
+            ..bid_state(&executed_bid, &env)
+        }))
         .add_submessages(submsg)
+        .add_events(events)
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -348,7 +646,7 @@ pub(crate) fn on_received_take_bid(
 
 pub(crate) fn on_received_cancel_bid(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     _packet: &IbcPacket,
     msg: CancelBidMsg,
 ) -> Result<IbcReceiveResponse, ContractError> {
@@ -359,11 +657,15 @@ pub(crate) fn on_received_cancel_bid(
         return Err(ContractError::BidDoesntExist);
     }
     let mut bid = bids().load(deps.storage, key.clone())?;
+    // Taken or refunded here first, the bidder chain keeps the outcome it is told
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
     bid.status = BidStatus::Cancelled;
     bids().save(deps.storage, key, &bid)?;
 
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(bid_state(&bid, &env)))
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -372,6 +674,35 @@ pub(crate) fn on_received_cancel_bid(
     Ok(res)
 }
 
+pub(crate) fn on_received_reject_bid(
+    deps: DepsMut,
+    env: Env,
+    _packet: &IbcPacket,
+    msg: RejectBidMsg,
+) -> Result<IbcReceiveResponse, ContractError> {
+    let order_id = msg.order_id.clone();
+
+    let key = bid_key(&msg.order_id, &msg.bidder);
+    let mut bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+    bid.status = BidStatus::Rejected;
+    bids().save(deps.storage, key, &bid)?;
+
+    let res = IbcReceiveResponse::new()
+        .set_ack(ack_result(bid_state(&bid, &env)))
+        .add_event(bid_rejected_event(&msg, &bid.bid))
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "receive")
+        .add_attribute("success", "true")
+        .add_attribute("action", "reject_bid_received");
+
+    Ok(res)
+}
+
 pub(crate) fn on_received_update_bid(
     deps: DepsMut,
     env: Env,
@@ -394,11 +725,15 @@ pub(crate) fn on_received_update_bid(
         return Err(ContractError::Expired);
     }
 
-    bid.bid.amount += msg.addition;
+    bid.bid.amount = bid
+        .bid
+        .amount
+        .checked_add(msg.addition)
+        .map_err(StdError::from)?;
     bids().save(deps.storage, key, &bid)?;
 
     let res = IbcReceiveResponse::new()
-        .set_ack(ack_success())
+        .set_ack(ack_result(bid_state(&bid, &env)))
         .add_attribute("order_id", order_id)
         .add_attribute("action", "receive")
         .add_attribute("success", "true")
@@ -411,9 +746,22 @@ pub(crate) fn on_received_update_bid(
 pub(crate) fn on_packet_success(
     deps: DepsMut,
     packet: IbcPacket,
+    ack: &IbcAcknowledgement,
     env: Env,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet_data: AtomicSwapPacketData = from_json(packet.data)?;
+    let packet_data = decode_packet(&packet.data)?;
+    // Keep the previous state when the counterparty doesn't report one
+    let remote = try_get_ack_result(ack);
+    // An earlier failure of the same request has nothing left to retry
+    if let Some((order_id, sender)) = retryable_packet(deps.as_ref(), &packet_data)? {
+        FAILED_PACKETS.remove(
+            deps.storage,
+            failed_packet_key(&order_id, &sender, &packet_data.r#type),
+        );
+    }
+    if let Some((key, nonce)) = bid_operation(&packet_data)? {
+        record_bid_operation(deps.storage, key, nonce)?;
+    }
 
     // similar event messages like ibctransfer module
     let attributes = vec![attr("action", "acknowledge"), attr("success", "true")];
@@ -423,16 +771,17 @@ pub(crate) fn on_packet_success(
         // This logic is executed when Taker chain acknowledge the make swap packet.
         SwapMessageType::Unspecified => Ok(IbcBasicResponse::new()),
         SwapMessageType::MakeSwap => {
-            let order_id = &packet_data.order_id.unwrap();
+            let order_id = &required_order_id(&packet_data)?;
             let mut order = get_atomic_order(deps.storage, order_id)?;
             order.status = Status::Sync;
+            order.remote = remote.or(order.remote);
             set_atomic_order(deps.storage, order_id, &order)?;
             Ok(IbcBasicResponse::new().add_attributes(attributes))
         }
         // This is the step 9 (Transfer Take Token & Close order): https://github.com/cosmos/ibc/tree/main/spec/app/ics-100-atomic-swap
         // The step is executed on the Taker chain.
         SwapMessageType::TakeSwap => {
-            let msg: TakeSwapMsg = decode_take_swap_msg(&packet_data.data);
+            let msg = TakeSwapMsg::decode(&packet_data.data)?;
 
             let order_id = msg.order_id.clone();
             let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
@@ -441,53 +790,142 @@ pub(crate) fn on_packet_success(
                 .api
                 .addr_validate(&swap_order.maker.maker_receiving_address)?;
 
-            let (fee, maker_amount, treasury) = maker_fee(
-                deps.as_ref(),
-                &msg.sell_token.amount,
-                msg.sell_token.denom.clone(),
-            );
-            let submsg: Vec<SubMsg> = vec![
-                send_tokens(&maker_receiving_address, maker_amount)?,
-                send_tokens(&treasury, fee)?,
+            // The maker chain reports the price of a Dutch auction, the rest is refunded
+            let mut paid = msg.sell_token.clone();
+            if swap_order.maker.dutch_auction.is_some() {
+                if let Some(price) = remote.as_ref().and_then(|remote| remote.price) {
+                    paid.amount = price.min(paid.amount);
+                }
+            }
+
+            let (fee, maker_amount, treasury) =
+                maker_fee(deps.as_ref(), &paid.amount, paid.denom.clone())?;
+            let mut submsg: Vec<SubMsg> = vec![
+                Asset::from(maker_amount).transfer_msg(&maker_receiving_address)?,
+                Asset::from(fee).transfer_msg(&treasury)?,
             ];
+            if msg.sell_token.amount > paid.amount {
+                let surplus = Coin {
+                    denom: paid.denom.clone(),
+                    amount: msg.sell_token.amount - paid.amount,
+                };
+                let taker_address = deps.api.addr_validate(&msg.taker_address)?;
+                submsg.push(Asset::from(surplus).transfer_msg(&taker_address)?);
+            }
+            submsg.extend(basket_payouts(
+                deps.as_ref(),
+                &swap_order.maker.extra_buy_tokens,
+                &maker_receiving_address,
+                maker_fee,
+            )?);
+
+            if swap_order.maker.allow_partial {
+                let sell_token = partial_fill(&swap_order, &msg.sell_token)?;
+                let exhausted =
+                    record_fill(deps.storage, &env, &mut swap_order, &msg, &sell_token)?;
+                // Unlock the order for the next taker
+                if !exhausted {
+                    swap_order.taker = None;
+                    swap_order.remote = remote.or(swap_order.remote);
+                    set_atomic_order(deps.storage, &order_id, &swap_order)?;
+                    return Ok(IbcBasicResponse::new()
+                        .add_submessages(submsg)
+                        .add_attributes(attributes));
+                }
+            }
 
             swap_order.status = Status::Complete;
-            swap_order.taker = Some(msg.clone());
+            swap_order.taker = Some(TakeSwapMsg {
+                sell_token: paid,
+                ..msg.clone()
+            });
             swap_order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+            swap_order.remote = remote.or(swap_order.remote);
 
             set_atomic_order(deps.storage, &order_id, &swap_order)?;
             move_order_to_bottom(deps.storage, &msg.order_id)?;
 
+            let (refunds, events) =
+                refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+            submsg.extend(refunds);
+
             Ok(IbcBasicResponse::new()
                 .add_submessages(submsg)
+                .add_events(events)
                 .add_attributes(attributes))
         }
         // This is the step 14 (Cancel & refund) of the atomic swap: https://github.com/cosmos/ibc/tree/main/spec/app/ics-100-atomic-swap
         // It is executed on the Maker chain.
         SwapMessageType::CancelSwap => {
-            let msg: CancelSwapMsg = from_json(&packet_data.data)?;
+            let msg = CancelSwapMsg::decode(&packet_data.data)?;
             let order_id = msg.order_id;
             let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
 
             let maker_address = deps.api.addr_validate(&swap_order.maker.maker_address)?;
-            let maker_msg = swap_order.maker.clone();
 
-            let submsg = vec![send_tokens(&maker_address, maker_msg.sell_token)?];
+            // Partially filled orders only refund what is left
+            let mut submsg = transfer_msgs(swap_order.remaining_sell_tokens(), &maker_address)?;
 
             swap_order.status = Status::Cancel;
             swap_order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+            swap_order.remote = remote.or(swap_order.remote);
+            swap_order.pending = None;
 
             set_atomic_order(deps.storage, &order_id, &swap_order)?;
 
+            let (refunds, events) =
+                refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+            submsg.extend(refunds);
+
             Ok(IbcBasicResponse::new()
                 .add_submessages(submsg)
+                .add_events(events)
+                .add_attributes(attributes))
+        }
+        // The taker chain updated its copy, this one follows. A take that locked the order
+        // in the meantime is refused there, so the order is still open here.
+        SwapMessageType::UpdateSwap => {
+            let msg = UpdateSwapMsg::decode(&packet_data.data)?;
+            let order_id = msg.order_id.clone();
+            let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
+
+            let bids_closed = swap_order.maker.take_bids && msg.take_bids == Some(false);
+            update_order(&mut swap_order, &msg)?;
+            swap_order.remote = remote.or(swap_order.remote);
+            swap_order.pending = None;
+            set_atomic_order(deps.storage, &order_id, &swap_order)?;
+
+            let (submsg, events) = if bids_closed {
+                refund_order_bids(deps, &order_id, &swap_order.side, MAX_BID_REFUNDS)?
+            } else {
+                (vec![], vec![])
+            };
+
+            Ok(IbcBasicResponse::new()
+                .add_submessages(submsg)
+                .add_events(events)
                 .add_attributes(attributes))
         }
         SwapMessageType::MakeBid => {
-            let msg: MakeBidMsg = from_json(&packet_data.data)?;
+            let msg = MakeBidMsg::decode(&packet_data.data)?;
 
             let key = bid_key(&msg.order_id, &msg.taker_address);
             let mut bid = bids().load(deps.storage, key.clone())?;
+            bid.remote = remote.or(bid.remote);
+
+            // The order was closed while the bid was in flight, nothing can take it anymore
+            let open = may_get_atomic_order(deps.storage, &msg.order_id)?.is_some_and(|order| {
+                order.status == Status::Sync || order.status == Status::Initial
+            });
+            if !open {
+                let bidder = deps.api.addr_validate(&bid.bidder)?;
+                let submsg = vec![Asset::from(bid.bid.clone()).transfer_msg(&bidder)?];
+                bid.status = BidStatus::Refunded;
+                bids().save(deps.storage, key, &bid)?;
+                return Ok(IbcBasicResponse::new()
+                    .add_submessages(submsg)
+                    .add_attributes(attributes));
+            }
 
             bid.status = BidStatus::Placed;
             bids().save(deps.storage, key, &bid)?;
@@ -495,7 +933,7 @@ pub(crate) fn on_packet_success(
             Ok(IbcBasicResponse::new().add_attributes(attributes))
         }
         SwapMessageType::TakeBid => {
-            let msg: TakeBidMsg = from_json(&packet_data.data)?;
+            let msg = TakeBidMsg::decode(&packet_data.data)?;
             let order_id = msg.order_id.clone();
             let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
 
@@ -505,7 +943,23 @@ pub(crate) fn on_packet_success(
             }
 
             let mut bid = bids().load(deps.storage, key.clone())?;
+            // The deposit already went back to the bidder, the maker is not paid from the
+            // escrow of other bids
+            if bid.status != BidStatus::Placed {
+                let err = format!("bid of {} is no longer placed", bid.bidder);
+                swap_order.remote = remote.or(swap_order.remote);
+                swap_order.pending = None;
+                swap_order.last_error = Some(err.clone());
+                set_atomic_order(deps.storage, &order_id, &swap_order)?;
+                return Ok(IbcBasicResponse::new()
+                    .add_event(
+                        packet_failed_event("take_bid", &order_id, &err)
+                            .add_attribute("bidder", msg.bidder),
+                    )
+                    .add_attributes(attributes));
+            }
             bid.status = BidStatus::Executed;
+            bid.remote = remote.clone().or(bid.remote);
             bids().save(deps.storage, key, &bid)?;
             //bids().remove(deps.storage, key)?;
 
@@ -513,7 +967,8 @@ pub(crate) fn on_packet_success(
                 .api
                 .addr_validate(&swap_order.maker.maker_receiving_address)?;
 
-            let submsg: Vec<SubMsg> = vec![send_tokens(&maker_receiving_address, bid.bid.clone())?];
+            let mut submsg: Vec<SubMsg> =
+                vec![Asset::from(bid.bid.clone()).transfer_msg(&maker_receiving_address)?];
 
             let take_msg: TakeSwapMsg = TakeSwapMsg {
                 order_id,
@@ -529,25 +984,41 @@ pub(crate) fn on_packet_success(
             swap_order.status = Status::Complete;
             swap_order.taker = Some(take_msg);
             swap_order.complete_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+            swap_order.remote = remote.or(swap_order.remote);
+            swap_order.pending = None;
 
             set_atomic_order(deps.storage, &msg.order_id, &swap_order)?;
             move_order_to_bottom(deps.storage, &msg.order_id)?;
+
+            let (refunds, events) =
+                refund_order_bids(deps, &msg.order_id, &swap_order.side, MAX_BID_REFUNDS)?;
+            submsg.extend(refunds);
+
             Ok(IbcBasicResponse::new()
                 .add_submessages(submsg)
+                .add_events(events)
                 .add_attributes(attributes))
         }
         SwapMessageType::CancelBid => {
-            let msg: CancelBidMsg = from_json(&packet_data.data)?;
+            let msg = CancelBidMsg::decode(&packet_data.data)?;
 
             let key = bid_key(&msg.order_id, &msg.bidder);
             if !bids().has(deps.storage, key.clone()) {
                 return Err(ContractError::BidDoesntExist);
             }
             let mut bid = bids().load(deps.storage, key.clone())?;
+            bid.remote = remote.or(bid.remote);
+            bid.pending = None;
+            // Taken or refunded in the meantime, there is nothing left to refund
+            if bid.status != BidStatus::Placed {
+                bids().save(deps.storage, key, &bid)?;
+                return Ok(IbcBasicResponse::new().add_attributes(attributes));
+            }
 
             let taker_receiving_address = deps.api.addr_validate(&bid.bidder)?;
             // Refund amount
-            let submsg: Vec<SubMsg> = vec![send_tokens(&taker_receiving_address, bid.bid.clone())?];
+            let submsg: Vec<SubMsg> =
+                vec![Asset::from(bid.bid.clone()).transfer_msg(&taker_receiving_address)?];
 
             bid.status = BidStatus::Cancelled;
             bids().save(deps.storage, key, &bid)?;
@@ -558,15 +1029,80 @@ pub(crate) fn on_packet_success(
                 .add_attributes(attributes))
         }
         SwapMessageType::UpdateBid => {
-            let msg: UpdateBidMsg = from_json(&packet_data.data)?;
+            let msg = UpdateBidMsg::decode(&packet_data.data)?;
 
             let key = bid_key(&msg.order_id, &msg.bidder);
             let mut bid = bids().load(deps.storage, key.clone())?;
+            bid.remote = remote.or(bid.remote);
+            bid.pending = None;
+
+            // Taken or refunded in the meantime, the addition goes back to the bidder
+            if bid.status != BidStatus::Placed {
+                bids().save(deps.storage, key, &bid)?;
+                let bidder = deps.api.addr_validate(&bid.bidder)?;
+                let submsg = vec![Asset::from(Coin {
+                    denom: bid.bid.denom,
+                    amount: msg.addition,
+                })
+                .transfer_msg(&bidder)?];
+                return Ok(IbcBasicResponse::new()
+                    .add_submessages(submsg)
+                    .add_attributes(attributes));
+            }
 
-            bid.bid.amount += msg.addition;
+            bid.bid.amount = bid
+                .bid
+                .amount
+                .checked_add(msg.addition)
+                .map_err(StdError::from)?;
+            let (submsg, events) = refund_deferred_bid(deps.as_ref(), &mut bid)?;
             bids().save(deps.storage, key, &bid)?;
 
-            Ok(IbcBasicResponse::new().add_attributes(attributes))
+            let mut res = IbcBasicResponse::new()
+                .add_submessages(submsg)
+                .add_events(events)
+                .add_attributes(attributes);
+            if bid.status == BidStatus::Placed && accept_counter_offer(deps.storage, &env, &bid)? {
+                res = res.add_attribute("counter_offer", "accepted");
+            }
+            Ok(res)
+        }
+        // This is the chain holding the deposit
+        SwapMessageType::RejectBid => {
+            let msg = RejectBidMsg::decode(&packet_data.data)?;
+
+            let key = bid_key(&msg.order_id, &msg.bidder);
+            let mut bid = bids().load(deps.storage, key.clone())?;
+            bid.remote = remote.or(bid.remote);
+            bid.pending = None;
+            // Taken or refunded in the meantime, there is nothing left to refund
+            if bid.status != BidStatus::Placed {
+                bids().save(deps.storage, key, &bid)?;
+                return Ok(IbcBasicResponse::new().add_attributes(attributes));
+            }
+
+            let bidder = deps.api.addr_validate(&bid.bidder)?;
+            let submsg = vec![Asset::from(bid.bid.clone()).transfer_msg(&bidder)?];
+
+            bid.status = BidStatus::Rejected;
+            bids().save(deps.storage, key, &bid)?;
+
+            Ok(IbcBasicResponse::new()
+                .add_submessages(submsg)
+                .add_event(bid_rejected_event(&msg, &bid.bid))
+                .add_attributes(attributes))
+        }
+        // The other chain closed its copy, close this one. It is already gone when both
+        // chains expired the order at the same time.
+        SwapMessageType::ExpireSwap => {
+            let msg = ExpireSwapMsg::decode(&packet_data.data)?;
+            let mut res = IbcBasicResponse::new().add_attributes(attributes);
+            if let Some(mut order) = may_get_atomic_order(deps.storage, &msg.order_id)? {
+                order.remote = remote.or(order.remote);
+                let (submsg, events) = expire_order(deps, &env, order)?;
+                res = res.add_submessages(submsg).add_events(events);
+            }
+            Ok(res)
         }
     }
 }
@@ -576,11 +1112,24 @@ pub(crate) fn on_packet_failure(
     packet: IbcPacket,
     err: String,
 ) -> Result<IbcBasicResponse, ContractError> {
-    let packet_data: AtomicSwapPacketData = from_json(packet.data)?;
-    let submsg = refund_packet_token(deps, packet_data)?;
+    let packet_data = decode_packet(&packet.data)?;
+    if let Some((order_id, sender)) = retryable_packet(deps.as_ref(), &packet_data)? {
+        let failed = FailedPacket {
+            order_id,
+            kind: packet_data.r#type.clone(),
+            sender,
+            data: packet.data,
+            error: err.clone(),
+        };
+        FAILED_PACKETS.save(
+            deps.storage,
+            failed_packet_key(&failed.order_id, &failed.sender, &failed.kind),
+            &failed,
+        )?;
+    }
+    let res = refund_packet_token(deps, packet_data, &err)?;
 
-    let res = IbcBasicResponse::new()
-        .add_submessages(submsg)
+    let res = res
         .add_attribute("action", "acknowledge")
         .add_attribute("success", "false")
         .add_attribute("error", err);
@@ -591,65 +1140,522 @@ pub(crate) fn on_packet_failure(
 pub(crate) fn refund_packet_token(
     deps: DepsMut,
     packet: AtomicSwapPacketData,
-) -> Result<Vec<SubMsg>, ContractError> {
+    err: &str,
+) -> Result<IbcBasicResponse, ContractError> {
+    let res = IbcBasicResponse::new();
     match packet.r#type {
         // This is the step 3.2 (Refund) of the atomic swap: https://github.com/liangping/ibc/blob/atomic-swap/spec/app/ics-100-atomic-swap/ibcswap.png
         // This logic will be executed when Relayer sends make swap packet to the taker chain, but the request timeout
         // and locked tokens form the first step (see the picture on the link above) MUST be returned to the account of
         // the maker on the maker chain.
-        SwapMessageType::Unspecified => Ok(vec![]),
+        SwapMessageType::Unspecified => Ok(res),
         SwapMessageType::MakeSwap => {
-            let msg: MakeSwapMsg = decode_make_swap_msg(&packet.data);
+            let msg = MakeSwapMsg::decode(&packet.data)?;
             let maker_address: Addr = deps.api.addr_validate(&msg.maker_address)?;
-            let submsg = vec![send_tokens(&maker_address, msg.sell_token)?];
-            let order_id = packet.order_id.unwrap();
-            let mut order = get_atomic_order(deps.storage, &order_id)?;
+            let order_id = required_order_id(&packet)?;
+            // Closing the channel already refunded and archived the order
+            let Some(mut order) = may_get_atomic_order(deps.storage, &order_id)? else {
+                return Ok(res);
+            };
+            let submsg = transfer_msgs(msg.sell_tokens(), &maker_address)?;
             order.status = Status::Failed;
+            order.last_error = Some(err.to_string());
             set_atomic_order(deps.storage, &order_id, &order)?;
-            Ok(submsg)
+            Ok(res.add_submessages(submsg))
         }
         // This is the step 7.2 (Unlock order and refund) of the atomic swap: https://github.com/cosmos/ibc/tree/main/spec/app/ics-100-atomic-swap
         // This step is executed on the Taker chain when Take Swap request timeout.
         SwapMessageType::TakeSwap => {
-            let msg: TakeSwapMsg = decode_take_swap_msg(&packet.data);
+            let msg = TakeSwapMsg::decode(&packet.data)?;
             let order_id: String = msg.order_id.clone();
-            let mut swap_order = get_atomic_order(deps.storage, &order_id)?;
+            // Closing the channel already refunded the taker and archived the order
+            let Some(mut swap_order) = may_get_atomic_order(deps.storage, &order_id)? else {
+                return Ok(res);
+            };
             let taker_address: Addr = deps.api.addr_validate(&msg.taker_address)?;
 
-            let submsg = vec![send_tokens(&taker_address, msg.sell_token)?];
+            let submsg = transfer_msgs(swap_order.taker_tokens(&msg.sell_token), &taker_address)?;
 
             swap_order.taker = None;
             swap_order.status = Status::Sync;
+            swap_order.last_error = Some(err.to_string());
             set_atomic_order(deps.storage, &order_id, &swap_order)?;
 
-            Ok(submsg)
+            Ok(res.add_submessages(submsg))
+        }
+        // Tokens are only sent back when the cancel is acknowledged, the order stays open
+        // and the maker can cancel again.
+        SwapMessageType::CancelSwap => {
+            let msg = CancelSwapMsg::decode(&packet.data)?;
+            release_pending_order(deps, &msg.order_id, err)?;
+            Ok(res.add_event(packet_failed_event("cancel_swap", &msg.order_id, err)))
+        }
+        // Neither copy changed, the order can be updated again.
+        SwapMessageType::UpdateSwap => {
+            let msg = UpdateSwapMsg::decode(&packet.data)?;
+            release_pending_order(deps, &msg.order_id, err)?;
+            Ok(res.add_event(packet_failed_event("update_swap", &msg.order_id, err)))
         }
-        // do nothing, only send tokens back when cancel msg is acknowledged.
-        SwapMessageType::CancelSwap => Ok(vec![]),
         SwapMessageType::MakeBid => {
-            let msg: MakeBidMsg = from_json(&packet.data)?;
+            let msg = MakeBidMsg::decode(&packet.data)?;
             let taker_address: Addr = deps.api.addr_validate(&msg.taker_address)?;
-            let submsg = vec![send_tokens(&taker_address, msg.sell_token)?];
             let order_id = msg.order_id;
 
-            // Remove bid
+            // Only a bid still waiting for this packet holds the deposit
             let key = bid_key(&order_id, &msg.taker_address);
             let mut bid = bids().load(deps.storage, key.clone())?;
+            if bid.status != BidStatus::Initial {
+                return Ok(res);
+            }
+            let submsg = vec![Asset::from(msg.sell_token).transfer_msg(&taker_address)?];
             bid.status = BidStatus::Failed;
+            bid.last_error = Some(err.to_string());
             bids().save(deps.storage, key, &bid)?;
 
-            Ok(submsg)
+            Ok(res.add_submessages(submsg))
         }
-        SwapMessageType::TakeBid => Ok(vec![]),
-        SwapMessageType::CancelBid => Ok(vec![]),
+        // The bid stays placed and the order open, so the bid can be taken again.
+        SwapMessageType::TakeBid => {
+            let msg = TakeBidMsg::decode(&packet.data)?;
+            release_pending_order(deps, &msg.order_id, err)?;
+            Ok(res.add_event(
+                packet_failed_event("take_bid", &msg.order_id, err)
+                    .add_attribute("bidder", msg.bidder),
+            ))
+        }
+        // The deposit stays escrowed until a cancel is acknowledged, or is refunded now
+        // when the order closed in the meantime.
+        SwapMessageType::CancelBid => {
+            let msg = CancelBidMsg::decode(&packet.data)?;
+            let key = bid_key(&msg.order_id, &msg.bidder);
+            let (submsg, events) = match bids().may_load(deps.storage, key.clone())? {
+                Some(mut bid) => {
+                    bid.pending = None;
+                    bid.last_error = Some(err.to_string());
+                    let refund = refund_deferred_bid(deps.as_ref(), &mut bid)?;
+                    bids().save(deps.storage, key, &bid)?;
+                    refund
+                }
+                None => (vec![], vec![]),
+            };
+            Ok(res.add_submessages(submsg).add_events(events).add_event(
+                packet_failed_event("cancel_bid", &msg.order_id, err)
+                    .add_attribute("bidder", msg.bidder),
+            ))
+        }
+        // The addition is refunded, the bid stays as it was and can be updated again.
         SwapMessageType::UpdateBid => {
-            let msg: UpdateBidMsg = from_json(&packet.data)?;
+            let msg = UpdateBidMsg::decode(&packet.data)?;
             let taker_address: Addr = deps.api.addr_validate(&msg.bidder)?;
             let key = bid_key(&msg.order_id, &taker_address.to_string());
-            let bid = bids().load(deps.storage, key.clone())?;
-        
-            let submsg = vec![send_tokens(&taker_address, Coin {denom: bid.bid.denom, amount: msg.addition})?];
-            Ok(submsg)
-        },
+            let mut bid = bids().load(deps.storage, key.clone())?;
+
+            let mut submsg = vec![Asset::from(Coin {
+                denom: bid.bid.denom.clone(),
+                amount: msg.addition,
+            })
+            .transfer_msg(&taker_address)?];
+            bid.pending = None;
+            bid.last_error = Some(err.to_string());
+            let (refunds, events) = refund_deferred_bid(deps.as_ref(), &mut bid)?;
+            submsg.extend(refunds);
+            bids().save(deps.storage, key, &bid)?;
+            Ok(res.add_submessages(submsg).add_events(events).add_event(
+                packet_failed_event("update_bid", &msg.order_id, err)
+                    .add_attribute("bidder", msg.bidder),
+            ))
+        }
+        // The bid stays placed and can be rejected again, or is refunded now when the
+        // order closed in the meantime.
+        SwapMessageType::RejectBid => {
+            let msg = RejectBidMsg::decode(&packet.data)?;
+            let key = bid_key(&msg.order_id, &msg.bidder);
+            let (submsg, events) = match bids().may_load(deps.storage, key.clone())? {
+                Some(mut bid) => {
+                    bid.pending = None;
+                    bid.last_error = Some(err.to_string());
+                    let refund = refund_deferred_bid(deps.as_ref(), &mut bid)?;
+                    bids().save(deps.storage, key, &bid)?;
+                    refund
+                }
+                None => (vec![], vec![]),
+            };
+            Ok(res.add_submessages(submsg).add_events(events).add_event(
+                packet_failed_event("reject_bid", &msg.order_id, err)
+                    .add_attribute("bidder", msg.bidder),
+            ))
+        }
+        // Nothing was refunded yet, the order stays open and can be expired again.
+        SwapMessageType::ExpireSwap => {
+            let msg = ExpireSwapMsg::decode(&packet.data)?;
+            release_pending_order(deps, &msg.order_id, err)?;
+            Ok(res.add_event(packet_failed_event("expire_swap", &msg.order_id, err)))
+        }
+    }
+}
+
+// Order id and sender of packets that can be sent again with RetryPacket.
+fn retryable_packet(
+    deps: Deps,
+    packet: &AtomicSwapPacketData,
+) -> Result<Option<(String, String)>, ContractError> {
+    let retryable = match packet.r#type {
+        SwapMessageType::CancelSwap => {
+            let msg = CancelSwapMsg::decode(&packet.data)?;
+            Some((msg.order_id, msg.maker_address))
+        }
+        // Bids are taken by the maker's receiving address on this chain
+        SwapMessageType::TakeBid => {
+            let msg = TakeBidMsg::decode(&packet.data)?;
+            may_get_atomic_order(deps.storage, &msg.order_id)?
+                .map(|order| (msg.order_id, order.maker.maker_receiving_address))
+        }
+        SwapMessageType::CancelBid => {
+            let msg = CancelBidMsg::decode(&packet.data)?;
+            Some((msg.order_id, msg.bidder))
+        }
+        SwapMessageType::UpdateBid => {
+            let msg = UpdateBidMsg::decode(&packet.data)?;
+            Some((msg.order_id, msg.bidder))
+        }
+        _ => None,
+    };
+    Ok(retryable)
+}
+
+// Clears the in-flight marker of an order after its packet failed. The order may have
+// been archived in the meantime, e.g. when it was taken while a cancel was in flight.
+fn release_pending_order(deps: DepsMut, order_id: &str, err: &str) -> StdResult<()> {
+    if let Some(mut order) = may_get_atomic_order(deps.storage, order_id)? {
+        order.pending = None;
+        order.last_error = Some(err.to_string());
+        set_atomic_order(deps.storage, order_id, &order)?;
+    }
+    Ok(())
+}
+
+fn bid_rejected_event(msg: &RejectBidMsg, amount: &Coin) -> Event {
+    Event::new("bid_rejected")
+        .add_attribute("order_id", msg.order_id.clone())
+        .add_attribute("bidder", msg.bidder.clone())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("reason", msg.reason.clone().unwrap_or_default())
+}
+
+fn packet_failed_event(action: &str, order_id: &str, err: &str) -> Event {
+    Event::new("packet_failed")
+        .add_attribute("action", action)
+        .add_attribute("order_id", order_id)
+        .add_attribute("error", err)
+}
+
+// Return every token this chain escrows for orders routed over a closed channel.
+// On the maker chain the maker's sell token is refunded, on the taker chain a pending
+// taker deposit and the placed bids are refunded. Affected orders are archived.
+pub(crate) fn on_channel_close(
+    deps: DepsMut,
+    env: Env,
+    channel_id: &str,
+) -> Result<IbcBasicResponse, ContractError> {
+    CHANNEL_INFO.remove(deps.storage, channel_id);
+    let (submsg, events) = refund_channel_orders(deps, &env, channel_id, 0, MAX_CHANNEL_REFUNDS)?;
+
+    let res = IbcBasicResponse::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("action", "channel_close")
+        .add_attribute("channel_id", channel_id);
+
+    Ok(res)
+}
+
+// Refunds and archives the orders of a closed channel, looking at up to `limit` orders
+// from `start`. The rest is queued in CHANNEL_REFUNDS for RefundChannel. Bids still in
+// flight are refunded when their packet times out.
+pub(crate) fn refund_channel_orders(
+    deps: DepsMut,
+    env: &Env,
+    channel_id: &str,
+    start: u64,
+    limit: u32,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let limit = limit as usize;
+    let mut orders = SWAP_ORDERS
+        .range(
+            deps.storage,
+            Some(Bound::inclusive(start)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut submsg: Vec<SubMsg> = vec![];
+    let mut events: Vec<Event> = vec![];
+    if orders.len() > limit {
+        let (next, _) = orders.remove(limit);
+        CHANNEL_REFUNDS.save(deps.storage, channel_id, &next)?;
+        events.push(Event::new("channel_refunds_queued").add_attribute("channel_id", channel_id));
+    } else {
+        CHANNEL_REFUNDS.remove(deps.storage, channel_id);
+    }
+
+    for (_, mut order) in orders {
+        if local_channel_for_order(&order)? != channel_id {
+            continue;
+        }
+        // Packets left on the channel can only time out, their timeouts still refund
+        let pending = pending_packets()
+            .idx
+            .order
+            .prefix(order.id.clone())
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (id, packet) in pending {
+            if packet.channel_id == channel_id {
+                pending_packets().remove(deps.storage, &id)?;
+            }
+        }
+        if order.status != Status::Initial && order.status != Status::Sync {
+            continue;
+        }
+
+        match order.side {
+            Side::Native => {
+                let maker_address = deps.api.addr_validate(&order.maker.maker_address)?;
+                // Partially filled orders only refund what is left
+                for refund in order.remaining_sell_tokens() {
+                    submsg.push(Asset::from(refund.clone()).transfer_msg(&maker_address)?);
+                    events.push(channel_close_refund_event(
+                        channel_id,
+                        &order.id,
+                        &maker_address,
+                        &refund,
+                    ));
+                }
+                order.status = Status::Cancel;
+                order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+            }
+            Side::Remote => {
+                if let Some(taker) = order.taker.take() {
+                    let taker_address = deps.api.addr_validate(&taker.taker_address)?;
+                    for refund in order.taker_tokens(&taker.sell_token) {
+                        submsg.push(Asset::from(refund.clone()).transfer_msg(&taker_address)?);
+                        events.push(channel_close_refund_event(
+                            channel_id,
+                            &order.id,
+                            &taker_address,
+                            &refund,
+                        ));
+                    }
+                    order.status = Status::Failed;
+                } else {
+                    order.status = Status::Cancel;
+                    order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+                }
+            }
+        }
+
+        // Bid deposits are only escrowed on the taker chain, the maker chain keeps a copy.
+        let placed = bids()
+            .idx
+            .order_status
+            .prefix((order.id.clone(), BidStatus::Placed as u8))
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (key, mut bid) in placed {
+            if order.side == Side::Remote {
+                let bidder = deps.api.addr_validate(&bid.bidder)?;
+                submsg.push(Asset::from(bid.bid.clone()).transfer_msg(&bidder)?);
+                events.push(
+                    channel_close_refund_event(channel_id, &order.id, &bidder, &bid.bid)
+                        .add_attribute("bidder", bid.bidder.clone()),
+                );
+            }
+            bid.status = BidStatus::Cancelled;
+            bids().save(deps.storage, key, &bid)?;
+        }
+
+        set_atomic_order(deps.storage, &order.id, &order)?;
+        move_order_to_bottom(deps.storage, &order.id)?;
+    }
+
+    Ok((submsg, events))
+}
+
+// Refunds what this chain escrows for an expired order and archives it. The maker chain
+// returns the maker's sell token, the taker chain the placed bids. Bids still in flight are
+// refunded when their packet is acknowledged.
+fn expire_order(
+    deps: DepsMut,
+    env: &Env,
+    mut order: AtomicSwapOrder,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let mut submsg: Vec<SubMsg> = vec![];
+    let mut events: Vec<Event> = vec![];
+    if order.side == Side::Native {
+        let maker_address = deps.api.addr_validate(&order.maker.maker_address)?;
+        for refund in order.remaining_sell_tokens() {
+            submsg.push(Asset::from(refund.clone()).transfer_msg(&maker_address)?);
+            events.push(expire_refund_event(&order.id, &maker_address, &refund));
+        }
+    }
+
+    order.status = Status::Cancel;
+    order.cancel_timestamp = Some(Timestamp::from_nanos(env.block.time.nanos()));
+    order.pending = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
+    move_order_to_bottom(deps.storage, &order.id)?;
+
+    let (refunds, bid_events) = refund_order_bids(deps, &order.id, &order.side, MAX_BID_REFUNDS)?;
+    submsg.extend(refunds);
+    events.extend(bid_events);
+
+    Ok((submsg, events))
+}
+
+// `maker_fee` or `taker_fee`
+type FeeFn = fn(Deps, &Uint128, String) -> StdResult<(Coin, Coin, Addr)>;
+
+// Pays the rest of a basket order to `recipient`, each token less its own fee
+fn basket_payouts(
+    deps: Deps,
+    tokens: &[Coin],
+    recipient: &Addr,
+    fee: FeeFn,
+) -> StdResult<Vec<SubMsg>> {
+    let mut submsg = vec![];
+    for token in tokens {
+        let (fee, send, treasury) = fee(deps, &token.amount, token.denom.clone())?;
+        submsg.push(Asset::from(send).transfer_msg(recipient)?);
+        submsg.push(Asset::from(fee).transfer_msg(&treasury)?);
+    }
+    Ok(submsg)
+}
+
+// Records a partial fill of the order and tells whether it used up the order. Both chains
+// record the same fills, the taker chain when the take is acknowledged.
+fn record_fill(
+    storage: &mut dyn Storage,
+    env: &Env,
+    order: &mut AtomicSwapOrder,
+    msg: &TakeSwapMsg,
+    sell_token: &Coin,
+) -> StdResult<bool> {
+    let fill = Fill {
+        taker: msg.taker_address.clone(),
+        sell_token: sell_token.clone(),
+        buy_token: msg.sell_token.clone(),
+        timestamp: env.block.time.seconds(),
+    };
+    FILLS.save(storage, (&order.id, order.fill_count), &fill)?;
+    order.fill_count += 1;
+    order.filled_sell_amount += sell_token.amount;
+    order.filled_buy_amount += msg.sell_token.amount;
+    Ok(order.remaining_buy().amount.is_zero())
+}
+
+// Marks an open counter offer accepted once the bid reaches its price. A failed top up
+// leaves the offer open.
+fn accept_counter_offer(storage: &mut dyn Storage, env: &Env, bid: &Bid) -> StdResult<bool> {
+    let key = bid_key(&bid.order, &bid.bidder);
+    let Some(mut offer) = counter_offers().may_load(storage, key.clone())? else {
+        return Ok(false);
+    };
+    if offer.status != CounterOfferStatus::Open
+        || env.block.time.seconds() > offer.expire_timestamp
+        || bid.bid.amount < offer.price
+    {
+        return Ok(false);
+    }
+
+    offer.status = CounterOfferStatus::Accepted;
+    offer.update_timestamp = env.block.time.seconds();
+    counter_offers().save(storage, key, &offer)?;
+    Ok(true)
+}
+
+// Closes up to `limit` placed bids of a finished order, or one that stopped taking bids.
+// Deposits are only escrowed on the taker chain, the maker chain just updates its copy.
+// Bids left over are queued in BID_REFUNDS for RefundBids. Bids with a packet in flight
+// are left to its acknowledgement or timeout, see `refund_deferred_bid`.
+pub(crate) fn refund_order_bids(
+    deps: DepsMut,
+    order_id: &str,
+    side: &Side,
+    limit: u32,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let limit = limit as usize;
+    let mut placed = bids()
+        .idx
+        .order_status
+        .prefix((order_id.to_string(), BidStatus::Placed as u8))
+        .range(deps.storage, None, None, Order::Ascending)
+        .filter(|item| item.as_ref().map_or(true, |(_, bid)| bid.pending.is_none()))
+        .take(limit + 1)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut events: Vec<Event> = vec![];
+    if placed.len() > limit {
+        placed.truncate(limit);
+        BID_REFUNDS.save(deps.storage, order_id, side)?;
+        events.push(Event::new("bid_refunds_queued").add_attribute("order_id", order_id));
+    } else {
+        BID_REFUNDS.remove(deps.storage, order_id);
+    }
+
+    let mut submsg: Vec<SubMsg> = vec![];
+    for (key, mut bid) in placed {
+        if *side == Side::Remote {
+            let bidder = deps.api.addr_validate(&bid.bidder)?;
+            submsg.push(Asset::from(bid.bid.clone()).transfer_msg(&bidder)?);
+            events.push(bid_refund_event(&bid));
+        }
+        bid.status = BidStatus::Refunded;
+        bids().save(deps.storage, key, &bid)?;
+    }
+
+    Ok((submsg, events))
+}
+
+// Refunds a placed bid whose order stopped taking bids while a packet of the bid was in
+// flight. The order's refunds skipped it, so it is refunded once the packet is back.
+// The caller saves the bid.
+fn refund_deferred_bid(
+    deps: Deps,
+    bid: &mut Bid,
+) -> Result<(Vec<SubMsg>, Vec<Event>), ContractError> {
+    let open = may_get_atomic_order(deps.storage, &bid.order)?.is_some_and(|order| {
+        (order.status == Status::Sync || order.status == Status::Initial) && order.maker.take_bids
+    });
+    if open || bid.status != BidStatus::Placed {
+        return Ok((vec![], vec![]));
     }
+
+    let bidder = deps.api.addr_validate(&bid.bidder)?;
+    let submsg = vec![Asset::from(bid.bid.clone()).transfer_msg(&bidder)?];
+    bid.status = BidStatus::Refunded;
+    Ok((submsg, vec![bid_refund_event(bid)]))
+}
+
+fn bid_refund_event(bid: &Bid) -> Event {
+    Event::new("bid_refund")
+        .add_attribute("order_id", bid.order.clone())
+        .add_attribute("bidder", bid.bidder.clone())
+        .add_attribute("amount", bid.bid.to_string())
+}
+
+fn expire_refund_event(order_id: &str, to: &Addr, amount: &Coin) -> Event {
+    Event::new("expire_refund")
+        .add_attribute("order_id", order_id)
+        .add_attribute("receiver", to.to_string())
+        .add_attribute("amount", amount.to_string())
+}
+
+fn channel_close_refund_event(channel_id: &str, order_id: &str, to: &Addr, amount: &Coin) -> Event {
+    Event::new("channel_close_refund")
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("order_id", order_id)
+        .add_attribute("receiver", to.to_string())
+        .add_attribute("amount", amount.to_string())
 }
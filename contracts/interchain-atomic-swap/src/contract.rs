@@ -1,32 +1,55 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Binary, Deps, DepsMut, Env, IbcMsg, IbcTimeout, MessageInfo, Order, Response,
-    StdError, StdResult, Uint128,
+    from_json, to_json_binary, Addr, Binary, Deps, DepsMut, Env, IbcMsg, IbcTimeout, MessageInfo,
+    Order, Response, StdError, StdResult, Storage, Uint128,
 };
 
 use cw2::set_contract_version;
+use cw20::Cw20ReceiveMsg;
+use cw721::Cw721ReceiveMsg;
 
+use crate::asset::{is_cw20_denom, is_cw721_denom, Asset};
+use crate::atomic_swap_handler::{refund_channel_orders, refund_order_bids};
+use crate::codec::{decode_packet, encode_packet, packet_order, PacketMsg};
 use crate::error::ContractError;
 use crate::msg::{
-    AtomicSwapPacketData, BidOffset, BidOffsetTime, BidsResponse, CancelBidMsg, CancelSwapMsg,
-    DetailsResponse, ExecuteMsg, InstantiateMsg, ListResponse, MakeBidMsg, MakeSwapMsg, MigrateMsg,
-    QueryMsg, SwapMessageType, TakeBidMsg, TakeSwapMsg, UpdateBidMsg,
+    AcceptCounterOfferMsg, AtomicSwapPacketData, BidOffset, BidOffsetTime, BidsResponse,
+    CancelBidMsg, CancelSwapMsg, CounterOfferMsg, CounterOffersResponse,
+    CounterpartyChainsResponse, CurrentPriceResponse, DeclineCounterOfferMsg, DetailsResponse,
+    ExecuteMsg, ExpireSwapMsg, FailedPacketsResponse, FillsResponse, HeightOutput, InstantiateMsg,
+    ListResponse, MakeBidMsg, MakeSwapMsg, MigrateMsg, PendingPacketsResponse, QueryMsg,
+    ReceiveMsg, RejectBidMsg, SettleAuctionMsg, SwapMessageType, TakeBidMsg, TakeSwapMsg,
+    UpdateBidMsg, UpdateConfigMsg, UpdateSwapMsg,
 };
 use crate::query_reverse::{
     query_list_by_desired_taker_reverse, query_list_by_maker_reverse, query_list_by_taker_reverse,
     query_list_reverse,
 };
 use crate::state::{
-    append_atomic_order, bid_key, bids, get_atomic_order, move_order_to_bottom, set_atomic_order, AtomicSwapOrder, Bid, BidKey, BidStatus, Config, FeeInfo, MarketState, Side, Status, CHANNEL_INFO, CONFIG, COUNT, FEE_INFO, INACTIVE_COUNT, INACTIVE_SWAP_ORDERS, ORDER_TO_COUNT, SWAP_ORDERS, SWAP_SEQUENCE
+    append_atomic_order, bid_key, bids, counter_offers, failed_packet_key, get_atomic_order,
+    pending_packets, set_atomic_order, top_bid, AtomicSwapOrder, Bid, BidKey, BidStatus, Config,
+    CounterOffer, CounterOfferStatus, CounterpartyChain, FeeInfo, MarketState, PendingPacket, Side,
+    Status, BID_REFUNDS, CHANNEL_INFO, CHANNEL_REFUNDS, CONFIG, COUNT, COUNTERPARTY_CHAINS,
+    DEFAULT_TIMEOUT_TIMESTAMP_OFFSET, FAILED_PACKETS, FEE_INFO, FILLS, INACTIVE_COUNT,
+    INACTIVE_SWAP_ORDERS, MAX_BID_REFUNDS, MAX_CHANNEL_REFUNDS, MAX_TIMEOUT_TIMESTAMP_OFFSET,
+    ORDER_TO_COUNT, PACKET_RETRIES, SWAP_ORDERS, SWAP_SEQUENCE,
+};
+use crate::utils::{
+    counterparty_prefix, enforce_counterparty_chain, ensure_not_auction, ensure_not_pending,
+    extract_source_channel_for_taker_msg, generate_order_id, is_exact_funds,
+    local_channel_for_order, order_path, packet_timeout, parse_height_output, parse_u64_field,
+    partial_fill, pending_packet_id, place_auction_bid, update_order, validate_auction,
+    validate_basket, validate_dutch_auction, validate_remote_address,
 };
-use crate::utils::{extract_source_channel_for_taker_msg, generate_order_id, order_path};
 use cw_storage_plus::Bound;
 
 // Version info, for migration info
 const CONTRACT_NAME: &str = "ics100-swap";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
-const DEFAULT_TIMEOUT_TIMESTAMP_OFFSET: u64 = 600;
+
+// Longest reason accepted by RejectBid
+const MAX_REASON_LENGTH: usize = 256;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -44,7 +67,9 @@ pub fn instantiate(
         &Config {
             vesting_contract: msg.vesting_contract,
             admin: info.sender.to_string(),
-            state: MarketState::Active
+            state: MarketState::Active,
+            default_timeout: DEFAULT_TIMEOUT_TIMESTAMP_OFFSET,
+            max_timeout: MAX_TIMEOUT_TIMESTAMP_OFFSET,
         },
     )?;
 
@@ -65,17 +90,101 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    if info.funds.iter().any(|coin| is_cw20_denom(&coin.denom)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Cw20 tokens must be sent through Receive".to_string(),
+        )));
+    }
+    if info.funds.iter().any(|coin| is_cw721_denom(&coin.denom)) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs must be sent through ReceiveNft".to_string(),
+        )));
+    }
     match msg {
         ExecuteMsg::MakeSwap(msg) => execute_make_swap(deps, env, info, msg),
         ExecuteMsg::TakeSwap(msg) => execute_take_swap(deps, env, info, msg),
         ExecuteMsg::CancelSwap(msg) => execute_cancel_swap(deps, env, info, msg),
+        ExecuteMsg::UpdateSwap(msg) => execute_update_swap(deps, env, info, msg),
         ExecuteMsg::MakeBid(msg) => execute_make_bid(deps, env, info, msg),
         ExecuteMsg::TakeBid(msg) => execute_take_bid(deps, env, info, msg),
         ExecuteMsg::CancelBid(msg) => execute_cancel_bid(deps, env, info, msg),
         ExecuteMsg::UpdateBid(msg) => execute_update_bid(deps, env, info, msg),
+        ExecuteMsg::RejectBid(msg) => execute_reject_bid(deps, env, info, msg),
+        ExecuteMsg::CounterOffer(msg) => execute_counter_offer(deps, env, info, msg),
+        ExecuteMsg::AcceptCounterOffer(msg) => execute_accept_counter_offer(deps, env, info, msg),
+        ExecuteMsg::DeclineCounterOffer(msg) => execute_decline_counter_offer(deps, env, info, msg),
         ExecuteMsg::PauseMarket => execute_pause_market(deps, env, info),
         ExecuteMsg::UnpauseMarket => execute_unpause_market(deps, env, info),
+        ExecuteMsg::UpdateConfig(msg) => execute_update_config(deps, env, info, msg),
+        ExecuteMsg::SetCounterpartyChain(msg) => {
+            execute_set_counterparty_chain(deps, env, info, msg)
+        }
+        ExecuteMsg::RemoveCounterpartyChain { channel_id } => {
+            execute_remove_counterparty_chain(deps, env, info, channel_id)
+        }
+        ExecuteMsg::ExpireSwap(msg) => execute_expire_swap(deps, env, info, msg),
+        ExecuteMsg::SettleAuction(msg) => execute_settle_auction(deps, env, info, msg),
+        ExecuteMsg::RetryPacket { order_id, kind } => {
+            execute_retry_packet(deps, env, info, order_id, kind)
+        }
+        ExecuteMsg::RefundBids { order_id, limit } => {
+            execute_refund_bids(deps, env, info, order_id, limit)
+        }
+        ExecuteMsg::RefundChannel { channel_id, limit } => {
+            execute_refund_channel(deps, env, info, channel_id, limit)
+        }
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
+    }
+}
+
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Receive doesn't accept native funds".to_string(),
+        )));
+    }
+    let msg: ReceiveMsg = from_json(&wrapper.msg)?;
+    // The calling cw20 contract is the token, the wrapped message runs as the cw20 sender
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Asset::cw20(info.sender, wrapper.amount).to_coin()],
+    };
+    match msg {
+        ReceiveMsg::MakeSwap(msg) => execute_make_swap(deps, env, info, msg),
+        ReceiveMsg::TakeSwap(msg) => execute_take_swap(deps, env, info, msg),
+        ReceiveMsg::MakeBid(msg) => execute_make_bid(deps, env, info, msg),
+        ReceiveMsg::UpdateBid(msg) => execute_update_bid(deps, env, info, msg),
+    }
+}
+
+pub fn execute_receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "ReceiveNft doesn't accept native funds".to_string(),
+        )));
     }
+    let ReceiveMsg::MakeSwap(msg) = from_json(&wrapper.msg)? else {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can only be sold with MakeSwap".to_string(),
+        )));
+    };
+    // The calling cw721 contract holds the NFT, the order is made by its previous owner
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Asset::cw721(info.sender, wrapper.token_id).to_coin()],
+    };
+    execute_make_swap(deps, env, info, msg)
 }
 
 pub fn execute_pause_market(
@@ -85,7 +194,9 @@ pub fn execute_pause_market(
 ) -> Result<Response, ContractError> {
     let mut cfg = CONFIG.load(deps.storage)?;
     if cfg.admin != info.sender {
-        return Err(ContractError::Std(StdError::generic_err("only admin allowed".to_string())));
+        return Err(ContractError::Std(StdError::generic_err(
+            "only admin allowed".to_string(),
+        )));
     }
     cfg.state = MarketState::Paused;
     CONFIG.save(deps.storage, &cfg)?;
@@ -100,7 +211,9 @@ pub fn execute_unpause_market(
 ) -> Result<Response, ContractError> {
     let mut cfg = CONFIG.load(deps.storage)?;
     if cfg.admin != info.sender {
-        return Err(ContractError::Std(StdError::generic_err("only admin allowed".to_string())));
+        return Err(ContractError::Std(StdError::generic_err(
+            "only admin allowed".to_string(),
+        )));
     }
     cfg.state = MarketState::Active;
     CONFIG.save(deps.storage, &cfg)?;
@@ -108,6 +221,78 @@ pub fn execute_unpause_market(
     Ok(Response::new().add_attribute("action", "unpause_market"))
 }
 
+pub fn execute_update_config(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: UpdateConfigMsg,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "only admin allowed".to_string(),
+        )));
+    }
+    if let Some(default_timeout) = msg.default_timeout {
+        cfg.default_timeout = default_timeout;
+    }
+    if let Some(max_timeout) = msg.max_timeout {
+        cfg.max_timeout = max_timeout;
+    }
+    if cfg.default_timeout == 0 || cfg.default_timeout > cfg.max_timeout {
+        return Err(ContractError::InvalidTimeoutConfig);
+    }
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("default_timeout", cfg.default_timeout.to_string())
+        .add_attribute("max_timeout", cfg.max_timeout.to_string()))
+}
+
+pub fn execute_set_counterparty_chain(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: CounterpartyChain,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "only admin allowed".to_string(),
+        )));
+    }
+    if msg.channel_id.is_empty() || msg.chain_id.is_empty() || msg.bech32_prefix.is_empty() {
+        return Err(ContractError::InvalidCounterpartyChain);
+    }
+    COUNTERPARTY_CHAINS.save(deps.storage, &msg.channel_id, &msg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_counterparty_chain")
+        .add_attribute("channel_id", msg.channel_id)
+        .add_attribute("chain_id", msg.chain_id)
+        .add_attribute("enabled", msg.enabled.to_string()))
+}
+
+pub fn execute_remove_counterparty_chain(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    channel_id: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Std(StdError::generic_err(
+            "only admin allowed".to_string(),
+        )));
+    }
+    COUNTERPARTY_CHAINS.remove(deps.storage, &channel_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_counterparty_chain")
+        .add_attribute("channel_id", channel_id))
+}
+
 // MakeSwap is called when the maker wants to make atomic swap. The method create new order and lock tokens.
 // This is the step 1 (Create order & Lock Token) of the atomic swap: https://github.com/cosmos/ibc/tree/main/spec/app/ics-100-atomic-swap
 pub fn execute_make_swap(
@@ -116,15 +301,8 @@ pub fn execute_make_swap(
     info: MessageInfo,
     msg: MakeSwapMsg,
 ) -> Result<Response, ContractError> {
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == msg.sell_token.denom && msg.sell_token.amount == asset.amount {
-            ok = true;
-        }
-    }
-    if !ok {
+    // Exactly the tokens sold must be sent, a basket's included
+    if !is_exact_funds(&info.funds, &msg.sell_tokens()) {
         return Err(ContractError::Std(StdError::generic_err(
             "Funds mismatch: Funds mismatched to with message and sent values: Make swap"
                 .to_string(),
@@ -143,6 +321,52 @@ pub fn execute_make_swap(
         }
     }
 
+    // NFTs are only sold, as a whole and without vesting
+    if is_cw721_denom(&msg.buy_token.denom) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can only be sold".to_string(),
+        )));
+    }
+    if is_cw721_denom(&msg.sell_token.denom) && (msg.allow_partial || msg.vesting.is_some()) {
+        return Err(ContractError::Std(StdError::generic_err(
+            "NFTs can't be sold in parts or vested".to_string(),
+        )));
+    }
+
+    validate_basket(&msg)?;
+    validate_auction(&msg, env.block.time.seconds())?;
+    validate_dutch_auction(&msg)?;
+
+    if msg.allow_partial {
+        // Bids and vesting are for the whole order
+        if msg.take_bids || msg.vesting.is_some() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Partial fills can't be combined with bids or vesting".to_string(),
+            )));
+        }
+        if msg
+            .min_fill_amount
+            .is_some_and(|min| min > msg.buy_token.amount)
+        {
+            return Err(ContractError::InvalidFillAmount);
+        }
+    } else if msg.min_fill_amount.is_some() {
+        return Err(ContractError::InvalidFillAmount);
+    }
+
+    let chain = enforce_counterparty_chain(
+        deps.as_ref(),
+        &msg.source_channel,
+        &msg.sell_token.denom,
+        &msg.buy_token.denom,
+    )?;
+    // The receiving address and the desired taker are on the counterparty chain
+    deps.api.addr_validate(&msg.maker_address)?;
+    validate_remote_address(&msg.maker_receiving_address, &chain.bech32_prefix)?;
+    if !msg.desired_taker.is_empty() {
+        validate_remote_address(&msg.desired_taker, &chain.bech32_prefix)?;
+    }
+
     // Add swap message
     let channel_info = CHANNEL_INFO.load(deps.storage, &msg.source_channel)?;
     let sequence = SWAP_SEQUENCE.load(deps.storage)?;
@@ -167,28 +391,42 @@ pub fn execute_make_swap(
         create_timestamp: env.block.time.seconds(),
         min_bid_price: msg.min_bid_price,
         vesting_details: msg.vesting.clone(),
+        remote: None,
+        pending: None,
+        last_error: None,
+        filled_sell_amount: Uint128::zero(),
+        filled_buy_amount: Uint128::zero(),
+        fill_count: 0,
     };
     append_atomic_order(deps.storage, &order_id, &new_order)?;
     let ibc_packet = AtomicSwapPacketData {
         r#type: SwapMessageType::MakeSwap,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: Some(order_id.clone()),
         path: Some(path),
+        memo: String::new(),
+        nonce: None,
     };
 
     // Increment the sequence counter.
     let new_sequence = sequence + 1;
     SWAP_SEQUENCE.save(deps.storage, &new_sequence)?;
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: msg.source_channel,
-        data: to_json_binary(&ibc_packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        Some(msg.timeout_height.clone()),
+        Some(msg.timeout_timestamp),
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        msg.source_channel,
+        &ibc_packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -205,33 +443,45 @@ pub fn execute_take_swap(
     info: MessageInfo,
     msg: TakeSwapMsg,
 ) -> Result<Response, ContractError> {
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == msg.sell_token.denom && msg.sell_token.amount == asset.amount {
-            ok = true;
-        }
-    }
-    if !ok {
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    // Exactly the tokens paid must be sent, the rest of a basket included
+    if !is_exact_funds(&info.funds, &order.taker_tokens(&msg.sell_token)) {
         return Err(ContractError::Std(StdError::generic_err(
             "Funds mismatch: Funds mismatched to with message and sent values: Make swap"
                 .to_string(),
         )));
     }
 
-    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+    deps.api.addr_validate(&msg.taker_address)?;
+    let prefix = counterparty_prefix(deps.as_ref(), &local_channel_for_order(&order)?)?;
+    validate_remote_address(&msg.taker_receiving_address, &prefix)?;
 
     if order.status != Status::Initial && order.status != Status::Sync {
         return Err(ContractError::OrderTaken);
     }
 
-    // Make sure the maker's buy token matches the taker's sell token
-    if order.maker.buy_token != msg.sell_token {
+    // Partial orders take a slice of the remaining buy token, others the whole of it.
+    // A Dutch auction takes anything from its price, which is final on the maker chain.
+    if order.maker.allow_partial {
+        partial_fill(&order, &msg.sell_token)?;
+    } else if order.maker.dutch_auction.is_some() {
+        let price = order.current_price(env.block.time.seconds());
+        if msg.sell_token.denom != price.denom {
+            return Err(ContractError::InvalidSellToken);
+        }
+        if msg.sell_token.amount < price.amount {
+            return Err(ContractError::BelowCurrentPrice {
+                price: price.to_string(),
+            });
+        }
+    } else if order.maker.buy_token != msg.sell_token {
+        // Make sure the maker's buy token matches the taker's sell token
         return Err(ContractError::InvalidSellToken);
     }
 
-    // Checks if the order has already been taken
+    // Checks if the order has already been taken, a partial fill locks the order until
+    // it is acknowledged
     if let Some(_taker) = order.taker {
         return Err(ContractError::OrderTaken);
     }
@@ -241,29 +491,38 @@ pub fn execute_take_swap(
         return Err(ContractError::InvalidTakerAddress);
     }
 
+    // Expired orders are closed with ExpireSwap
     if env.block.time.seconds() > order.maker.expiration_timestamp {
-        move_order_to_bottom(deps.storage, &msg.order_id)?;
         return Err(ContractError::Expired);
     }
+    ensure_not_pending(&order.pending)?;
 
     order.taker = Some(msg.clone());
 
     let ibc_packet = AtomicSwapPacketData {
         r#type: SwapMessageType::TakeSwap,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: None,
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: extract_source_channel_for_taker_msg(&order.path)?,
-        data: to_json_binary(&ibc_packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        Some(msg.timeout_height.clone()),
+        Some(msg.timeout_timestamp),
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &ibc_packet,
+        timeout,
+    )?;
 
     // Save order
     set_atomic_order(deps.storage, &order.id, &order)?;
@@ -285,7 +544,7 @@ pub fn execute_cancel_swap(
     msg: CancelSwapMsg,
 ) -> Result<Response, ContractError> {
     let sender = info.sender.to_string();
-    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
 
     if sender != order.maker.maker_address {
         return Err(ContractError::InvalidSender);
@@ -300,23 +559,37 @@ pub fn execute_cancel_swap(
     if order.status != Status::Sync && order.status != Status::Initial {
         return Err(ContractError::InvalidStatus);
     }
+    ensure_not_pending(&order.pending)?;
+
+    // Cleared when the packet is acknowledged or times out
+    order.pending = Some(SwapMessageType::CancelSwap);
+    order.last_error = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
 
     let packet = AtomicSwapPacketData {
         r#type: SwapMessageType::CancelSwap,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: None,
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: order.maker.source_channel,
-        data: to_json_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        Some(parse_height_output(&msg.timeout_height)?),
+        Some(parse_u64_field(&msg.timeout_timestamp)?),
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        order.maker.source_channel.clone(),
+        &packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -326,6 +599,86 @@ pub fn execute_cancel_swap(
     Ok(res)
 }
 
+/// Update swap: changes the price, expiration or bid settings of an untaken order.
+/// The taker chain updates its copy when it receives the packet, this chain on the ack.
+pub fn execute_update_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: UpdateSwapMsg,
+) -> Result<Response, ContractError> {
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    // The maker's tokens are escrowed on this chain
+    if order.side != Side::Native {
+        return Err(ContractError::InvalidChain);
+    }
+
+    if info.sender != order.maker.maker_address {
+        return Err(ContractError::InvalidSender);
+    }
+
+    // Orders that haven't reached the taker chain can be cancelled and made again
+    if order.status != Status::Sync {
+        return Err(ContractError::InvalidStatus);
+    }
+    if order.taker.is_some() {
+        return Err(ContractError::AlreadyTakenOrder);
+    }
+    ensure_not_pending(&order.pending)?;
+
+    if msg
+        .expiration_timestamp
+        .is_some_and(|expiration| expiration <= env.block.time.seconds())
+    {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Expiration must be in the future".to_string(),
+        )));
+    }
+    // The desired taker is on the counterparty chain
+    if let Some(desired_taker) = msg.desired_taker.as_ref().filter(|taker| !taker.is_empty()) {
+        let prefix = counterparty_prefix(deps.as_ref(), &order.maker.source_channel)?;
+        validate_remote_address(desired_taker, &prefix)?;
+    }
+    // Fails before anything is sent, the order itself changes on the ack
+    update_order(&mut order.clone(), &msg)?;
+
+    order.pending = Some(SwapMessageType::UpdateSwap);
+    order.last_error = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
+
+    let packet = AtomicSwapPacketData {
+        r#type: SwapMessageType::UpdateSwap,
+        data: msg.encode(&env)?,
+        order_id: None,
+        path: None,
+        memo: String::new(),
+        nonce: None,
+    };
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        order.maker.source_channel.clone(),
+        &packet,
+        timeout,
+    )?;
+
+    let res = Response::new()
+        .add_message(ibc_msg)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "update_swap");
+    Ok(res)
+}
+
 /// Make bid: Use it to make bid from taker chain
 /// For each order each user can create atmost 1 bid(they can cancel and recreate it)
 /// Panics id bid is already taken
@@ -337,7 +690,7 @@ pub fn execute_make_bid(
     msg: MakeBidMsg,
 ) -> Result<Response, ContractError> {
     let sender = info.sender.to_string();
-    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
     // check if given tokens are received here
     let mut ok = false;
     // First token in this chain only first token needs to be verified
@@ -363,9 +716,13 @@ pub fn execute_make_bid(
         }
     }
 
+    let prefix = counterparty_prefix(deps.as_ref(), &local_channel_for_order(&order)?)?;
+    validate_remote_address(&msg.taker_receiving_address, &prefix)?;
+
     if !order.maker.take_bids {
         return Err(ContractError::TakeBidNotAllowed);
     }
+    ensure_not_pending(&order.pending)?;
 
     // Make sure the maker's buy token matches the taker's sell token
     if order.maker.buy_token.denom != msg.sell_token.denom {
@@ -373,7 +730,7 @@ pub fn execute_make_bid(
     }
 
     // Checks if the order has already been taken
-    if let Some(_taker) = order.taker {
+    if order.taker.is_some() {
         return Err(ContractError::OrderTaken);
     }
 
@@ -382,10 +739,22 @@ pub fn execute_make_bid(
     }
 
     let key = bid_key(&msg.order_id, &msg.taker_address);
-    if let Some(bid) = bids().may_load(deps.storage, key.clone())? {
+    let previous = bids().may_load(deps.storage, key.clone())?;
+    if let Some(bid) = &previous {
         if bid.status == BidStatus::Initial || bid.status == BidStatus::Placed {
             return Err(ContractError::BidAlreadyExist {});
         }
+        ensure_not_pending(&bid.pending)?;
+    }
+
+    if place_auction_bid(
+        deps.storage,
+        env.block.time.seconds(),
+        &mut order,
+        &msg.taker_address,
+        msg.sell_token.amount,
+    )? {
+        set_atomic_order(deps.storage, &msg.order_id, &order)?;
     }
 
     let bid: Bid = Bid {
@@ -396,26 +765,38 @@ pub fn execute_make_bid(
         bidder_receiver: msg.taker_receiving_address.clone(),
         receive_timestamp: env.block.time.seconds(),
         expire_timestamp: msg.expiration_timestamp,
+        remote: None,
+        pending: None,
+        last_error: None,
+        nonce: previous.map(|bid| bid.nonce).unwrap_or_default(),
     };
 
     bids().save(deps.storage, key, &bid)?;
 
     let packet = AtomicSwapPacketData {
         r#type: SwapMessageType::MakeBid,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: Some(bid.nonce),
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: extract_source_channel_for_taker_msg(&order.path)?,
-        data: to_json_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -436,14 +817,15 @@ pub fn execute_take_bid(
     msg: TakeBidMsg,
 ) -> Result<Response, ContractError> {
     let sender = info.sender.to_string();
-    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
 
     if !order.maker.take_bids {
         return Err(ContractError::TakeBidNotAllowed);
     }
+    ensure_not_auction(&order)?;
 
     // Checks if the order has already been taken
-    if let Some(_taker) = order.taker {
+    if order.taker.is_some() {
         return Err(ContractError::OrderTaken);
     }
 
@@ -464,23 +846,38 @@ pub fn execute_take_bid(
     if env.block.time.seconds() > bid.expire_timestamp {
         return Err(ContractError::Expired);
     }
+    ensure_not_pending(&order.pending)?;
+    ensure_not_pending(&bid.pending)?;
+
+    // Only one bid can be taken at a time, cleared on acknowledgement or timeout
+    order.pending = Some(SwapMessageType::TakeBid);
+    order.last_error = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
 
     let packet = AtomicSwapPacketData {
         r#type: SwapMessageType::TakeBid,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: None,
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: extract_source_channel_for_taker_msg(&order.path)?,
-        data: to_json_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -507,7 +904,7 @@ pub fn execute_cancel_bid(
     }
 
     let key = bid_key(&msg.order_id, &msg.bidder);
-    if !bids().has(deps.storage, key) {
+    if !bids().has(deps.storage, key.clone()) {
         return Err(ContractError::BidDoesntExist);
     }
 
@@ -515,22 +912,54 @@ pub fn execute_cancel_bid(
         return Err(ContractError::InvalidSender);
     }
 
+    // A bid that is being taken can't be cancelled
+    ensure_not_pending(&order.pending)?;
+    let mut bid = bids().load(deps.storage, key.clone())?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+    ensure_not_pending(&bid.pending)?;
+
+    // The highest bid of an auction stands until it is outbid or settled
+    if order.maker.auction.is_some() {
+        let highest = top_bid(deps.storage, &msg.order_id, |bid| {
+            bid.status == BidStatus::Placed
+        })?;
+        if highest.is_some_and(|(highest, _)| highest == key) {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The highest bid of an auction can't be cancelled".to_string(),
+            )));
+        }
+    }
+
+    bid.pending = Some(SwapMessageType::CancelBid);
+    bid.last_error = None;
+    bids().save(deps.storage, key, &bid)?;
+
     let packet = AtomicSwapPacketData {
         r#type: SwapMessageType::CancelBid,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: Some(bid.nonce),
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: extract_source_channel_for_taker_msg(&order.path)?,
-        data: to_json_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -539,79 +968,319 @@ pub fn execute_cancel_bid(
     Ok(res)
 }
 
-pub fn execute_update_bid(
+/// Reject Bid: the maker refunds and closes a placed bid
+/// Sent by the maker receiving address from the chain holding the bid, like TakeBid
+/// The deposit is refunded when the packet is acknowledged
+pub fn execute_reject_bid(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    msg: UpdateBidMsg,
+    msg: RejectBidMsg,
 ) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-    if cfg.state != MarketState::Active {
-        return Err(ContractError::Std(StdError::generic_err("market not active".to_string())));
-    }
-
-    if info.sender.to_string() != msg.bidder {
-        return Err(ContractError::InvalidSender);
-    }
-
-    let bidder = msg.bidder.clone();
     let order = get_atomic_order(deps.storage, &msg.order_id)?;
 
-    // check if given tokens are received here
-    let mut ok = false;
-    // First token in this chain only first token needs to be verified
-    for asset in info.funds {
-        if asset.denom == order.maker.buy_token.denom && msg.addition == asset.amount {
-            ok = true;
-        }
-    }
-    if !ok {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Funds mismatch: Funds mismatched to with message and sent values: Update bid"
-                .to_string(),
-        )));
+    if info.sender != order.maker.maker_receiving_address {
+        return Err(ContractError::InvalidSender);
     }
-
-    let key = bid_key(&msg.order_id, &bidder);
-    if !bids().has(deps.storage, key.clone()) {
-        return Err(ContractError::BidDoesntExist);
+    ensure_not_auction(&order)?;
+
+    if msg
+        .reason
+        .as_ref()
+        .is_some_and(|reason| reason.len() > MAX_REASON_LENGTH)
+    {
+        return Err(ContractError::Std(StdError::generic_err(format!(
+            "Reason must not be longer than {} bytes",
+            MAX_REASON_LENGTH
+        ))));
     }
 
-    let mut bid = bids().load(deps.storage, key.clone())?;
+    let key = bid_key(&msg.order_id, &msg.bidder);
+    let mut bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
     if bid.status != BidStatus::Placed {
         return Err(ContractError::BidDoesntExist);
     }
 
-    if env.block.time.seconds() > bid.expire_timestamp {
-        return Err(ContractError::Expired);
-    }
+    // The bid could be taken by a take bid in flight
+    ensure_not_pending(&order.pending)?;
+    ensure_not_pending(&bid.pending)?;
+    bid.pending = Some(SwapMessageType::RejectBid);
+    bid.last_error = None;
+    bids().save(deps.storage, key, &bid)?;
+
+    let packet = AtomicSwapPacketData {
+        r#type: SwapMessageType::RejectBid,
+        data: msg.encode(&env)?,
+        order_id: None,
+        path: None,
+        memo: String::new(),
+        nonce: None,
+    };
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
+
+    let res = Response::new()
+        .add_message(ibc_msg)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("bidder", msg.bidder)
+        .add_attribute("action", "reject_bid");
+    Ok(res)
+}
+
+/// Counter offer: the maker answers a placed bid with the total amount they would accept
+/// Sent by the maker receiving address on the chain holding the bid, no packet is sent
+/// A new counter offer replaces the previous one for the same bid
+pub fn execute_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: CounterOfferMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err(
+            "market not active".to_string(),
+        )));
+    }
+
+    let order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    if info.sender != order.maker.maker_receiving_address {
+        return Err(ContractError::InvalidSender);
+    }
+    ensure_not_auction(&order)?;
+
+    if order.taker.is_some() {
+        return Err(ContractError::OrderTaken);
+    }
+
+    let key = bid_key(&msg.order_id, &msg.bidder);
+    let bid = bids()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::BidDoesntExist)?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+    ensure_not_pending(&order.pending)?;
+    ensure_not_pending(&bid.pending)?;
+
+    // Accepting tops up the bid, so the price can only be above the bid
+    if msg.price <= bid.bid.amount || msg.price > order.maker.buy_token.amount {
+        return Err(ContractError::InvalidBidAmount);
+    }
+
+    if env.block.time.seconds() >= msg.expiration_timestamp {
+        return Err(ContractError::Expired);
+    }
+
+    let round = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .map_or(0, |offer| offer.round)
+        + 1;
+    let offer = CounterOffer {
+        order: msg.order_id.clone(),
+        bidder: msg.bidder.clone(),
+        price: msg.price,
+        expire_timestamp: msg.expiration_timestamp,
+        status: CounterOfferStatus::Open,
+        round,
+        update_timestamp: env.block.time.seconds(),
+    };
+    counter_offers().save(deps.storage, key, &offer)?;
+
+    let res = Response::new()
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("bidder", msg.bidder)
+        .add_attribute("price", msg.price)
+        .add_attribute("round", round.to_string())
+        .add_attribute("action", "counter_offer");
+    Ok(res)
+}
+
+/// Accept counter offer: the bidder tops up their bid to the counter offer price
+/// Sends an UpdateBid packet, the offer is accepted when it is acknowledged
+pub fn execute_accept_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: AcceptCounterOfferMsg,
+) -> Result<Response, ContractError> {
+    let key = bid_key(&msg.order_id, &info.sender.to_string());
+    let offer = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::CounterOfferDoesntExist)?;
+    if offer.status != CounterOfferStatus::Open {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    if env.block.time.seconds() > offer.expire_timestamp {
+        return Err(ContractError::Expired);
+    }
+
+    let bid = bids()
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::BidDoesntExist)?;
+    let addition = offer
+        .price
+        .checked_sub(bid.bid.amount)
+        .map_err(StdError::from)?;
+
+    let bidder = info.sender.to_string();
+    let res = execute_update_bid(
+        deps,
+        env,
+        info,
+        UpdateBidMsg {
+            order_id: msg.order_id,
+            addition,
+            bidder,
+            timeout_height: msg.timeout_height,
+            timeout_timestamp: msg.timeout_timestamp,
+        },
+    )?;
+    Ok(res.add_attribute("action", "accept_counter_offer"))
+}
+
+/// Decline counter offer: the bidder closes the counter offer, the bid stays placed
+pub fn execute_decline_counter_offer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: DeclineCounterOfferMsg,
+) -> Result<Response, ContractError> {
+    let key = bid_key(&msg.order_id, &info.sender.to_string());
+    let mut offer = counter_offers()
+        .may_load(deps.storage, key.clone())?
+        .ok_or(ContractError::CounterOfferDoesntExist)?;
+    if offer.status != CounterOfferStatus::Open {
+        return Err(ContractError::InvalidStatus);
+    }
+
+    offer.status = CounterOfferStatus::Declined;
+    offer.update_timestamp = env.block.time.seconds();
+    counter_offers().save(deps.storage, key, &offer)?;
+
+    let res = Response::new()
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("bidder", info.sender)
+        .add_attribute("action", "decline_counter_offer");
+    Ok(res)
+}
+
+pub fn execute_update_bid(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: UpdateBidMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err(
+            "market not active".to_string(),
+        )));
+    }
+
+    if info.sender.to_string() != msg.bidder {
+        return Err(ContractError::InvalidSender);
+    }
+
+    let bidder = msg.bidder.clone();
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    // check if given tokens are received here
+    let mut ok = false;
+    // First token in this chain only first token needs to be verified
+    for asset in info.funds {
+        if asset.denom == order.maker.buy_token.denom && msg.addition == asset.amount {
+            ok = true;
+        }
+    }
+    if !ok {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Funds mismatch: Funds mismatched to with message and sent values: Update bid"
+                .to_string(),
+        )));
+    }
+
+    let key = bid_key(&msg.order_id, &bidder);
+    if !bids().has(deps.storage, key.clone()) {
+        return Err(ContractError::BidDoesntExist);
+    }
+
+    let mut bid = bids().load(deps.storage, key.clone())?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::BidDoesntExist);
+    }
+    ensure_not_pending(&bid.pending)?;
+
+    if env.block.time.seconds() > bid.expire_timestamp {
+        return Err(ContractError::Expired);
+    }
 
     if msg.addition == Uint128::from(0u64) {
         return Err(ContractError::InvalidBidAmount);
     }
 
-    if bid.bid.amount + msg.addition > order.maker.buy_token.amount {
+    // Auctions have no ceiling
+    if order.maker.auction.is_none() && bid.bid.amount + msg.addition > order.maker.buy_token.amount
+    {
         return Err(ContractError::InvalidBidAmount);
     }
 
-    bid.bid.amount += msg.addition;
+    if place_auction_bid(
+        deps.storage,
+        env.block.time.seconds(),
+        &mut order,
+        &bidder,
+        bid.bid.amount + msg.addition,
+    )? {
+        set_atomic_order(deps.storage, &msg.order_id, &order)?;
+    }
+
+    // The addition is only added to the bid when the packet is acknowledged
+    bid.pending = Some(SwapMessageType::UpdateBid);
+    bid.last_error = None;
+    bids().save(deps.storage, key, &bid)?;
 
     let packet = AtomicSwapPacketData {
         r#type: SwapMessageType::UpdateBid,
-        data: to_json_binary(&msg)?,
+        data: msg.encode(&env)?,
         order_id: None,
         path: None,
+        memo: String::new(),
+        nonce: Some(bid.nonce),
     };
 
-    let ibc_msg = IbcMsg::SendPacket {
-        channel_id: extract_source_channel_for_taker_msg(&order.path)?,
-        data: to_json_binary(&packet)?,
-        timeout: IbcTimeout::from(
-            env.block
-                .time
-                .plus_seconds(DEFAULT_TIMEOUT_TIMESTAMP_OFFSET),
-        ),
-    };
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
 
     let res = Response::new()
         .add_message(ibc_msg)
@@ -620,6 +1289,307 @@ pub fn execute_update_bid(
     Ok(res)
 }
 
+/// Expire swap: closes an order once it has expired, can be called by anyone on either chain
+/// The packet tells the other chain to refund and archive its copy, this chain does the
+/// same on acknowledgement. The maker chain refunds the maker, the taker chain the bids.
+pub fn execute_expire_swap(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExpireSwapMsg,
+) -> Result<Response, ContractError> {
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+
+    // Orders that haven't reached the taker chain are refunded when the make swap fails
+    if order.status != Status::Sync {
+        return Err(ContractError::InvalidStatus);
+    }
+    if env.block.time.seconds() <= order.maker.expiration_timestamp {
+        return Err(ContractError::NotExpired);
+    }
+    if order.taker.is_some() {
+        return Err(ContractError::OrderTaken);
+    }
+    ensure_not_pending(&order.pending)?;
+
+    order.pending = Some(SwapMessageType::ExpireSwap);
+    order.last_error = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
+
+    let packet = AtomicSwapPacketData {
+        r#type: SwapMessageType::ExpireSwap,
+        data: msg.encode(&env)?,
+        order_id: None,
+        path: None,
+        memo: String::new(),
+        nonce: None,
+    };
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        local_channel_for_order(&order)?,
+        &packet,
+        timeout,
+    )?;
+
+    let res = Response::new()
+        .add_message(ibc_msg)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "expire_swap");
+    Ok(res)
+}
+
+/// Settle auction: takes the highest placed bid of an ended auction with a TakeBid packet
+/// Sent from the chain holding the bids, anyone can call it
+pub fn execute_settle_auction(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: SettleAuctionMsg,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.state != MarketState::Active {
+        return Err(ContractError::Std(StdError::generic_err(
+            "market not active".to_string(),
+        )));
+    }
+
+    let mut order = get_atomic_order(deps.storage, &msg.order_id)?;
+    let auction = order
+        .maker
+        .auction
+        .clone()
+        .ok_or(ContractError::NotAuction)?;
+
+    // Bids and the auction end are kept on the taker chain
+    if order.side != Side::Remote {
+        return Err(ContractError::InvalidChain);
+    }
+    if order.status != Status::Sync || order.taker.is_some() {
+        return Err(ContractError::InvalidStatus);
+    }
+    if env.block.time.seconds() < auction.end_time {
+        return Err(ContractError::AuctionNotEnded);
+    }
+    ensure_not_pending(&order.pending)?;
+
+    // Bid expirations don't apply, auction bids stand until the auction settles
+    let (_, bid) = top_bid(deps.storage, &msg.order_id, |bid| {
+        matches!(bid.status, BidStatus::Initial | BidStatus::Placed)
+    })?
+    .ok_or(ContractError::NoAuctionBids)?;
+    if bid.status != BidStatus::Placed {
+        return Err(ContractError::PacketInFlight {
+            packet: "MakeBid".to_string(),
+        });
+    }
+    ensure_not_pending(&bid.pending)?;
+
+    order.pending = Some(SwapMessageType::TakeBid);
+    order.last_error = None;
+    set_atomic_order(deps.storage, &order.id, &order)?;
+
+    let take = TakeBidMsg {
+        order_id: msg.order_id.clone(),
+        bidder: bid.bidder.clone(),
+        timeout_height: msg.timeout_height.clone(),
+        timeout_timestamp: msg.timeout_timestamp,
+    };
+    let packet = AtomicSwapPacketData {
+        r#type: SwapMessageType::TakeBid,
+        data: take.encode(&env)?,
+        order_id: None,
+        path: None,
+        memo: String::new(),
+        nonce: None,
+    };
+
+    let timeout = packet_timeout(
+        &cfg,
+        &env,
+        msg.timeout_height.clone(),
+        msg.timeout_timestamp,
+    )?;
+    let ibc_msg = send_packet(
+        deps.storage,
+        &env,
+        &info.sender,
+        extract_source_channel_for_taker_msg(&order.path)?,
+        &packet,
+        timeout,
+    )?;
+
+    let res = Response::new()
+        .add_message(ibc_msg)
+        .add_attribute("order_id", msg.order_id)
+        .add_attribute("action", "settle_auction")
+        .add_attribute("bidder", bid.bidder);
+    Ok(res)
+}
+
+/// Retry packet: sends a failed packet of the sender again
+/// The request goes through the same checks as the original one, with a new timeout
+pub fn execute_retry_packet(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    order_id: String,
+    kind: SwapMessageType,
+) -> Result<Response, ContractError> {
+    let key = failed_packet_key(&order_id, info.sender.as_str(), &kind);
+    let failed = FAILED_PACKETS
+        .may_load(deps.storage, key)?
+        .ok_or(ContractError::NoFailedPacket)?;
+    FAILED_PACKETS.remove(deps.storage, key);
+
+    let retries = PACKET_RETRIES
+        .may_load(deps.storage, &order_id)?
+        .unwrap_or_default()
+        + 1;
+    PACKET_RETRIES.save(deps.storage, &order_id, &retries)?;
+
+    let packet = decode_packet(&failed.data)?;
+    let res = match kind {
+        SwapMessageType::CancelSwap => {
+            let msg = CancelSwapMsg {
+                timeout_height: HeightOutput {
+                    revision_number: "0".to_string(),
+                    revision_height: "0".to_string(),
+                },
+                timeout_timestamp: "0".to_string(),
+                ..CancelSwapMsg::decode(&packet.data)?
+            };
+            execute_cancel_swap(deps, env, info, msg)?
+        }
+        SwapMessageType::TakeBid => {
+            let msg = TakeBidMsg {
+                timeout_height: None,
+                timeout_timestamp: None,
+                ..TakeBidMsg::decode(&packet.data)?
+            };
+            // Auctions are settled on their highest bid again instead of being taken
+            let order = get_atomic_order(deps.storage, &msg.order_id)?;
+            if order.maker.auction.is_some() {
+                let msg = SettleAuctionMsg {
+                    order_id: msg.order_id,
+                    timeout_height: None,
+                    timeout_timestamp: None,
+                };
+                execute_settle_auction(deps, env, info, msg)?
+            } else {
+                execute_take_bid(deps, env, info, msg)?
+            }
+        }
+        SwapMessageType::CancelBid => {
+            let msg = CancelBidMsg {
+                timeout_height: None,
+                timeout_timestamp: None,
+                ..CancelBidMsg::decode(&packet.data)?
+            };
+            execute_cancel_bid(deps, env, info, msg)?
+        }
+        SwapMessageType::UpdateBid => {
+            let msg = UpdateBidMsg {
+                timeout_height: None,
+                timeout_timestamp: None,
+                ..UpdateBidMsg::decode(&packet.data)?
+            };
+            execute_update_bid(deps, env, info, msg)?
+        }
+        _ => return Err(ContractError::NoFailedPacket),
+    };
+
+    Ok(res.add_attribute("retries", retries.to_string()))
+}
+
+/// Refund bids: anyone can continue refunding the bids of a closed order
+/// Only needed when the order had more placed bids than one transaction refunds
+pub fn execute_refund_bids(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    order_id: String,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let side = BID_REFUNDS
+        .may_load(deps.storage, &order_id)?
+        .ok_or(ContractError::NoBidsToRefund)?;
+
+    let limit = limit.unwrap_or(MAX_BID_REFUNDS).clamp(1, MAX_BID_REFUNDS);
+    let (submsg, events) = refund_order_bids(deps, &order_id, &side, limit)?;
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("order_id", order_id)
+        .add_attribute("action", "refund_bids");
+    Ok(res)
+}
+
+pub fn execute_refund_channel(
+    deps: DepsMut,
+    env: Env,
+    _info: MessageInfo,
+    channel_id: String,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let start = CHANNEL_REFUNDS
+        .may_load(deps.storage, &channel_id)?
+        .ok_or(ContractError::NoChannelRefunds)?;
+
+    let limit = limit
+        .unwrap_or(MAX_CHANNEL_REFUNDS)
+        .clamp(1, MAX_CHANNEL_REFUNDS);
+    let (submsg, events) = refund_channel_orders(deps, &env, &channel_id, start, limit)?;
+
+    let res = Response::new()
+        .add_submessages(submsg)
+        .add_events(events)
+        .add_attribute("channel_id", channel_id)
+        .add_attribute("action", "refund_channel");
+    Ok(res)
+}
+
+// Builds the packet message and keeps track of the packet until it is acknowledged or
+// times out.
+fn send_packet(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    channel_id: String,
+    packet: &AtomicSwapPacketData,
+    timeout: IbcTimeout,
+) -> Result<IbcMsg, ContractError> {
+    let data = encode_packet(packet)?;
+    let (order_id, bidder) = packet_order(packet)?;
+    let pending = PendingPacket {
+        id: pending_packet_id(packet)?,
+        order_id,
+        bidder,
+        kind: packet.r#type.clone(),
+        channel_id: channel_id.clone(),
+        sender: sender.to_string(),
+        send_timestamp: env.block.time.seconds(),
+        timeout: timeout.clone(),
+    };
+    pending_packets().save(storage, &pending.id, &pending)?;
+
+    Ok(IbcMsg::SendPacket {
+        channel_id,
+        data,
+        timeout,
+    })
+}
+
 #[entry_point]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     let ver = cw2::get_contract_version(deps.storage)?;
@@ -639,7 +1609,7 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, C
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::List {
             start_after,
@@ -731,20 +1701,51 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         )?),
 
         // Inactive fields
-        QueryMsg::InactiveList {
-            start_after,
-            limit,
+        QueryMsg::CounterOfferDetails { order, bidder } => {
+            to_json_binary(&query_counter_offer(deps, order, bidder)?)
+        }
+        QueryMsg::CounterOffersByOrder {
             order,
-        } => to_json_binary(&query_inactive_list(deps, start_after, limit, order)?),
-        QueryMsg::InactiveListByDesiredTaker {
             start_after,
             limit,
-            desired_taker,
-        } => to_json_binary(&query_inactive_list_by_desired_taker(
+        } => to_json_binary(&query_counter_offers_by_order(
             deps,
+            order,
             start_after,
             limit,
-            desired_taker,
+        )?),
+        QueryMsg::CounterOffersByBidder {
+            bidder,
+            start_after,
+            limit,
+        } => to_json_binary(&query_counter_offers_by_bidder(
+            deps,
+            bidder,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Fills {
+            order_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_fills(deps, order_id, start_after, limit)?),
+        QueryMsg::CurrentPrice { order_id } => {
+            to_json_binary(&query_current_price(deps, env, order_id)?)
+        }
+        QueryMsg::InactiveList {
+            start_after,
+            limit,
+            order,
+        } => to_json_binary(&query_inactive_list(deps, start_after, limit, order)?),
+        QueryMsg::InactiveListByDesiredTaker {
+            start_after,
+            limit,
+            desired_taker,
+        } => to_json_binary(&query_inactive_list_by_desired_taker(
+            deps,
+            start_after,
+            limit,
+            desired_taker,
         )?),
         QueryMsg::InactiveListByMaker {
             start_after,
@@ -801,9 +1802,107 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             limit,
             taker,
         )?),
+        QueryMsg::CounterpartyChain { channel_id } => {
+            to_json_binary(&COUNTERPARTY_CHAINS.load(deps.storage, &channel_id)?)
+        }
+        QueryMsg::CounterpartyChains { start_after, limit } => {
+            to_json_binary(&query_counterparty_chains(deps, start_after, limit)?)
+        }
+        QueryMsg::FailedPackets { order_id } => {
+            to_json_binary(&query_failed_packets(deps, order_id)?)
+        }
+        QueryMsg::PendingPackets {
+            order_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_pending_packets(
+            deps,
+            PendingPacketFilter::Order(order_id),
+            start_after,
+            limit,
+        )?),
+        QueryMsg::PendingPacketsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_json_binary(&query_pending_packets(
+            deps,
+            PendingPacketFilter::Sender(sender),
+            start_after,
+            limit,
+        )?),
+        QueryMsg::PendingPacketsByChannel {
+            channel_id,
+            start_after,
+            limit,
+        } => to_json_binary(&query_pending_packets(
+            deps,
+            PendingPacketFilter::Channel(channel_id),
+            start_after,
+            limit,
+        )?),
     }
 }
 
+enum PendingPacketFilter {
+    Order(String),
+    Sender(String),
+    Channel(String),
+}
+
+fn query_pending_packets(
+    deps: Deps,
+    filter: PendingPacketFilter,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PendingPacketsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let idx = pending_packets().idx;
+    let prefix = match filter {
+        PendingPacketFilter::Order(order_id) => idx.order.prefix(order_id),
+        PendingPacketFilter::Sender(sender) => idx.sender.prefix(sender),
+        PendingPacketFilter::Channel(channel_id) => idx.channel.prefix(channel_id),
+    };
+    let packets = prefix
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, packet)| packet))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(PendingPacketsResponse { packets })
+}
+
+fn query_failed_packets(deps: Deps, order_id: String) -> StdResult<FailedPacketsResponse> {
+    let packets = FAILED_PACKETS
+        .sub_prefix(&order_id)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, packet)| packet))
+        .collect::<StdResult<Vec<_>>>()?;
+    let retries = PACKET_RETRIES
+        .may_load(deps.storage, &order_id)?
+        .unwrap_or_default();
+    Ok(FailedPacketsResponse { packets, retries })
+}
+
+fn query_counterparty_chains(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<CounterpartyChainsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+
+    let chains = COUNTERPARTY_CHAINS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, chain)| chain))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(CounterpartyChainsResponse { chains })
+}
+
 fn query_details(deps: Deps, id: String) -> StdResult<DetailsResponse> {
     let swap_order = get_atomic_order(deps.storage, &id)?;
 
@@ -815,6 +1914,7 @@ fn query_details(deps: Deps, id: String) -> StdResult<DetailsResponse> {
         taker: swap_order.taker.clone(),
         cancel_timestamp: swap_order.cancel_timestamp,
         complete_timestamp: swap_order.complete_timestamp,
+        remote: swap_order.remote,
     };
     Ok(details)
 }
@@ -1099,6 +2199,91 @@ pub fn query_bids_by_bidder(
     Ok(BidsResponse { bids })
 }
 
+pub fn query_counter_offer(
+    deps: Deps,
+    order: String,
+    bidder: String,
+) -> StdResult<CounterOffersResponse> {
+    let offer = counter_offers().may_load(deps.storage, bid_key(&order, &bidder))?;
+
+    Ok(CounterOffersResponse {
+        offers: offer.into_iter().collect(),
+    })
+}
+
+pub fn query_counter_offers_by_order(
+    deps: Deps,
+    order: String,
+    start_after: Option<String>, // bidder
+    limit: Option<u32>,
+) -> StdResult<CounterOffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(Bound::exclusive);
+
+    let offers = counter_offers()
+        .prefix(order)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(CounterOffersResponse { offers })
+}
+
+pub fn query_counter_offers_by_bidder(
+    deps: Deps,
+    bidder: String,
+    start_after: Option<String>, // order
+    limit: Option<u32>,
+) -> StdResult<CounterOffersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(|start| Bound::exclusive(bid_key(&start, &bidder)));
+
+    let offers = counter_offers()
+        .idx
+        .bidder
+        .prefix(bidder)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, offer)| offer))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(CounterOffersResponse { offers })
+}
+
+pub fn query_fills(
+    deps: Deps,
+    order_id: String,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<FillsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let start = start_after.map(Bound::exclusive);
+
+    let fills = FILLS
+        .prefix(&order_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, fill)| fill))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(FillsResponse { fills })
+}
+
+pub fn query_current_price(
+    deps: Deps,
+    env: Env,
+    order_id: String,
+) -> StdResult<CurrentPriceResponse> {
+    let order = get_atomic_order(deps.storage, &order_id)?;
+    Ok(CurrentPriceResponse {
+        price: order.current_price(env.block.time.seconds()),
+    })
+}
+
 // Inactive fields
 
 fn query_inactive_list(
@@ -1243,14 +2428,48 @@ fn query_inactive_list_by_taker(
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coin, from_json, Coin, StdError, Uint128};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_ibc_channel_close_init, mock_ibc_channel_connect_ack,
+        mock_ibc_packet_ack, mock_ibc_packet_recv, mock_ibc_packet_timeout, mock_info,
+    };
+    use cosmwasm_std::{
+        attr, coin, from_json, BankMsg, Coin, CosmosMsg, IbcAcknowledgement, IbcEndpoint, IbcOrder,
+        IbcTimeout, IbcTimeoutBlock, SubMsg, Uint128, WasmMsg,
+    };
+
+    use crate::atomic_swap_handler::ack_success;
+    use crate::codec::PacketMsg;
+    use crate::ibc::{
+        ibc_channel_close, ibc_channel_connect, ibc_packet_ack, ibc_packet_receive,
+        ibc_packet_timeout,
+    };
 
-    use crate::msg::{Height, TakeSwapMsgOutput};
-    use crate::utils::{generate_order_id, order_path};
+    use crate::msg::{Height, HeightOutput};
+    use crate::state::{AuctionConfig, DutchAuctionConfig};
+    use crate::utils::{
+        generate_order_id, order_path, try_get_ack_error, try_get_ack_result,
+        validate_remote_address,
+    };
 
     use super::*;
 
+    fn register_channel(deps: DepsMut, channel_id: &str) {
+        execute(
+            deps,
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::SetCounterpartyChain(CounterpartyChain {
+                channel_id: channel_id.to_string(),
+                chain_id: "side-1".to_string(),
+                bech32_prefix: "side".to_string(),
+                sell_denoms: vec![],
+                buy_denoms: vec![],
+                enabled: true,
+            }),
+        )
+        .unwrap();
+    }
+
     #[test]
     fn test_instantiate() {
         let mut deps = mock_dependencies();
@@ -1295,6 +2514,10 @@ mod tests {
             bidder_receiver: bidder.clone(),
             receive_timestamp: 10,
             expire_timestamp: 100,
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid)
@@ -1328,6 +2551,10 @@ mod tests {
             bidder_receiver: bidder.clone(),
             receive_timestamp: 20,
             expire_timestamp: 100,
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid1)
@@ -1360,6 +2587,10 @@ mod tests {
             bidder_receiver: bidder.clone(),
             receive_timestamp: 30,
             expire_timestamp: 100,
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid2)
@@ -1414,6 +2645,10 @@ mod tests {
             bidder_receiver: bidder.clone(),
             receive_timestamp: 40,
             expire_timestamp: 100,
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
         };
         bids()
             .save(deps.as_mut().storage, bid_key(&order, &bidder), &bid3)
@@ -1480,6 +2715,12 @@ mod tests {
             take_bids: false,
             min_bid_price: None,
             vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
         };
         let err = execute(deps.as_mut(), env, info, ExecuteMsg::MakeSwap(create)).unwrap_err();
         assert_eq!(err, ContractError::EmptyBalance {});
@@ -1531,6 +2772,12 @@ mod tests {
             take_bids: false,
             min_bid_price: None,
             vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
         };
 
         let path = order_path(
@@ -1591,28 +2838,3768 @@ mod tests {
         println!("bytes is {:?}", &bytes);
         // let msg: TakeSwapMsg = from_json(&bytes.clone()).unwrap();
 
-        let msg_res: Result<TakeSwapMsg, StdError> = from_json(&bytes);
-        let msg: TakeSwapMsg;
+        let msg = TakeSwapMsg::decode(&bytes).unwrap();
+        assert_eq!(msg.order_id, create.order_id);
+        assert_eq!(msg.sell_token, create.sell_token);
+        assert_eq!(msg.taker_address, create.taker_address);
+        assert_eq!(msg.timeout_height.revision_height, 9999996);
+        assert_eq!(msg.timeout_timestamp, create.timeout_timestamp);
+        println!("msg is {:?}", &msg);
+    }
+
+    #[test]
+    fn test_packet_timeout() {
+        let env = mock_env();
+        let cfg = Config {
+            vesting_contract: "".to_string(),
+            admin: "admin".to_string(),
+            state: MarketState::Active,
+            default_timeout: 600,
+            max_timeout: 3600,
+        };
+        let default_timeout = IbcTimeout::with_timestamp(env.block.time.plus_seconds(600));
+
+        // Nothing or zero values given, fall back to the default
+        assert_eq!(
+            packet_timeout(&cfg, &env, None, None).unwrap(),
+            default_timeout
+        );
+        assert_eq!(
+            packet_timeout(
+                &cfg,
+                &env,
+                Some(Height {
+                    revision_number: 0,
+                    revision_height: 0,
+                }),
+                Some(0),
+            )
+            .unwrap(),
+            default_timeout
+        );
+
+        let timestamp = env.block.time.plus_seconds(1800);
+        assert_eq!(
+            packet_timeout(&cfg, &env, None, Some(timestamp.nanos())).unwrap(),
+            IbcTimeout::with_timestamp(timestamp)
+        );
+
+        // A height alone is capped by the maximum timeout
+        assert_eq!(
+            packet_timeout(
+                &cfg,
+                &env,
+                Some(Height {
+                    revision_number: 1,
+                    revision_height: 100,
+                }),
+                None,
+            )
+            .unwrap(),
+            IbcTimeout::with_both(
+                IbcTimeoutBlock {
+                    revision: 1,
+                    height: 100,
+                },
+                env.block.time.plus_seconds(3600)
+            )
+        );
+
+        assert_eq!(
+            packet_timeout(&cfg, &env, None, Some(env.block.time.nanos())).unwrap_err(),
+            ContractError::TimeoutInPast
+        );
+        assert_eq!(
+            packet_timeout(
+                &cfg,
+                &env,
+                None,
+                Some(env.block.time.plus_seconds(3601).nanos())
+            )
+            .unwrap_err(),
+            ContractError::TimeoutTooLong { max: 3600 }
+        );
+    }
+
+    #[test]
+    fn test_channel_close_refunds_escrow() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let sell_token = coin(100, "token1");
+        let create = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: sell_token.clone(),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(create),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        // Copy of a remote order with an escrowed bid on this chain.
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..get_atomic_order(&deps.storage, &order_id).unwrap()
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Placed,
+            bidder: "bidder0001".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        bids()
+            .save(
+                deps.as_mut().storage,
+                bid_key(&remote.id, &bid.bidder),
+                &bid,
+            )
+            .unwrap();
+
+        let res = ibc_channel_close(
+            deps.as_mut(),
+            env,
+            mock_ibc_channel_close_init("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
 
-        match msg_res {
-            Ok(value) => {
-                msg = value;
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.events.len(), 2);
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker0001".to_string(),
+                amount: vec![sell_token],
             }
-            Err(_err) => {
-                let msg_output: TakeSwapMsgOutput = from_json(&bytes).unwrap();
-                msg = TakeSwapMsg {
-                    order_id: msg_output.order_id.clone(),
-                    sell_token: msg_output.sell_token.clone(),
-                    taker_address: msg_output.taker_address.clone(),
-                    taker_receiving_address: msg_output.taker_receiving_address.clone(),
-                    timeout_height: Height {
-                        revision_number: msg_output.timeout_height.revision_number.parse().unwrap(),
-                        revision_height: msg_output.timeout_height.revision_height.parse().unwrap(),
-                    },
-                    timeout_timestamp: msg_output.timeout_timestamp.parse().unwrap(),
-                }
+            .into()
+        );
+        assert_eq!(
+            res.messages[1].msg,
+            BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(150, "token2")],
+            }
+            .into()
+        );
+
+        assert!(CHANNEL_INFO
+            .may_load(&deps.storage, "channel-0")
+            .unwrap()
+            .is_none());
+        assert!(get_atomic_order(&deps.storage, &order_id).is_err());
+        let bid = bids()
+            .load(&deps.storage, bid_key(&remote.id, &bid.bidder))
+            .unwrap();
+        assert_eq!(bid.status, BidStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_channel_close_leaves_in_flight_bids() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let receiver = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: receiver.to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let packet_data = |res: &Response| match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let mut order_ids = vec![];
+        let mut make_packets = vec![];
+        for _ in 0..MAX_CHANNEL_REFUNDS {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("maker0001", &[coin(100, "token1")]),
+                ExecuteMsg::MakeSwap(make.clone()),
+            )
+            .unwrap();
+            order_ids.push(res.attributes[0].value.clone());
+            make_packets.push(packet_data(&res));
+        }
+
+        // Copy of a remote order with one bid placed and one still in flight
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..get_atomic_order(&deps.storage, &order_ids[0]).unwrap()
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = |deps: DepsMut, bidder: &str, amount: u128| {
+            let msg = MakeBidMsg {
+                order_id: remote.id.clone(),
+                sell_token: coin(amount, "token2"),
+                taker_address: bidder.to_string(),
+                taker_receiving_address: receiver.to_string(),
+                expiration_timestamp: env.block.time.plus_seconds(10).seconds(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            };
+            let info = mock_info(bidder, &[coin(amount, "token2")]);
+            execute(deps, env.clone(), info, ExecuteMsg::MakeBid(msg)).unwrap()
+        };
+        let in_flight = packet_data(&bid(deps.as_mut(), "bidder0001", 150));
+        bid(deps.as_mut(), "bidder0002", 160);
+        let key = bid_key(&remote.id, &"bidder0002".to_string());
+        let mut placed = bids().load(&deps.storage, key.clone()).unwrap();
+        placed.status = BidStatus::Placed;
+        bids().save(deps.as_mut().storage, key, &placed).unwrap();
+
+        // One page of orders is refunded when the channel closes, the rest is queued
+        let res = ibc_channel_close(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_close_init("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), MAX_CHANNEL_REFUNDS as usize);
+        assert!(res
+            .events
+            .iter()
+            .any(|event| event.ty == "channel_refunds_queued"));
+        assert!(get_atomic_order(&deps.storage, &remote.id).is_ok());
+
+        // Only the placed bid is refunded, the one in flight waits for its timeout
+        let refund = ExecuteMsg::RefundChannel {
+            channel_id: "channel-0".to_string(),
+            limit: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            refund.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages
+                .iter()
+                .map(|msg| msg.msg.clone())
+                .collect::<Vec<_>>(),
+            vec![CosmosMsg::from(BankMsg::Send {
+                to_address: "bidder0002".to_string(),
+                amount: vec![coin(160, "token2")],
+            })]
+        );
+        let err =
+            execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), refund).unwrap_err();
+        assert_eq!(err, ContractError::NoChannelRefunds);
+
+        // A make swap timing out after the close has nothing left to refund
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = make_packets[0].clone();
+        let res = ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = in_flight;
+        let res = ibc_packet_timeout(deps.as_mut(), env.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(150, "token2")],
             }
+            .into()
+        );
+
+        // The deposit is only refunded once
+        let res = ibc_packet_timeout(deps.as_mut(), env, msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_packet_acks_error() {
+        let mut deps = mock_dependencies();
+        let receive = |deps: DepsMut, data: &str| {
+            let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+            msg.packet.data = Binary::from(data.as_bytes());
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)).unwrap()
+        };
+
+        let err = receive(deps.as_mut(), "not json");
+        assert!(err.starts_with("Invalid packet:"), "{}", err);
+
+        let make = Binary::from(br#"{"source_port":"port"}"#).to_base64();
+        let err = receive(
+            deps.as_mut(),
+            &format!(r#"{{"type":"TYPE_MSG_MAKE_SWAP","data":"{}"}}"#, make),
+        );
+        assert!(err.starts_with("Invalid make swap message:"), "{}", err);
+
+        let make = MakeSwapMsg {
+            source_port: "their_port".to_string(),
+            source_channel: "channel-7".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "makerrcpt0001".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: 0,
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
         }
-        println!("msg is {:?}", &msg);
+        .encode(&mock_env())
+        .unwrap()
+        .to_base64();
+        let err = receive(
+            deps.as_mut(),
+            &format!(r#"{{"type":"TYPE_MSG_MAKE_SWAP","data":"{}"}}"#, make),
+        );
+        assert_eq!(err, "Packet is missing path");
+
+        let take = Binary::from(
+            br#"{"order_id":"1","sell_token":{"denom":"a","amount":"1"},"taker_address":"t","taker_receiving_address":"r","timeout_timestamp":"soon"}"#,
+        )
+        .to_base64();
+        let err = receive(
+            deps.as_mut(),
+            &format!(r#"{{"type":"TYPE_MSG_TAKE_SWAP","data":"{}"}}"#, take),
+        );
+        assert!(err.starts_with("Invalid take swap message:"), "{}", err);
+    }
+
+    #[test]
+    fn test_packet_path_authentication() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make.clone()),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        let take = TakeSwapMsg {
+            order_id: order_id.clone(),
+            sell_token: coin(200, "token2"),
+            taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+        };
+        let receive = |deps: DepsMut,
+                       r#type: SwapMessageType,
+                       data: Binary,
+                       path: Option<String>,
+                       src: (&str, &str),
+                       dest: (&str, &str)| {
+            let packet = AtomicSwapPacketData {
+                r#type,
+                data,
+                order_id: None,
+                path,
+                memo: String::new(),
+                nonce: None,
+            };
+            let mut msg = mock_ibc_packet_recv(dest.1, &"").unwrap();
+            msg.packet.data = encode_packet(&packet).unwrap();
+            msg.packet.src = IbcEndpoint {
+                port_id: src.0.to_string(),
+                channel_id: src.1.to_string(),
+            };
+            msg.packet.dest = IbcEndpoint {
+                port_id: dest.0.to_string(),
+                channel_id: dest.1.to_string(),
+            };
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement))
+        };
+        let take_data = take.encode(&env).unwrap();
+
+        // Sent from another channel of the counterparty
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::TakeSwap,
+            take_data.clone(),
+            None,
+            ("their_port", "channel-8"),
+            ("my_port", "channel-0"),
+        );
+        assert_eq!(
+            err,
+            Some("Packet on channel channel-0 doesn't match the order path".to_string())
+        );
+
+        // A cancel is only accepted for orders made on the other chain
+        let cancel = CancelSwapMsg {
+            order_id: order_id.clone(),
+            maker_address: "maker0001".to_string(),
+            timeout_height: HeightOutput {
+                revision_number: "0".to_string(),
+                revision_height: "0".to_string(),
+            },
+            timeout_timestamp: "0".to_string(),
+        };
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::CancelSwap,
+            cancel.encode(&env).unwrap(),
+            None,
+            ("their_port", "channel-7"),
+            ("my_port", "channel-0"),
+        );
+        assert_eq!(err, Some("Order is not for this chain".to_string()));
+
+        // A make swap over a channel this contract doesn't know
+        let path = order_path(
+            "channel-9".to_string(),
+            "their_port".to_string(),
+            "channel-5".to_string(),
+            "my_port".to_string(),
+            0,
+        )
+        .unwrap();
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::MakeSwap,
+            make.encode(&env).unwrap(),
+            Some(path),
+            ("their_port", "channel-9"),
+            ("my_port", "channel-5"),
+        );
+        assert_eq!(err, Some("Unknown channel channel-5".to_string()));
+
+        // The take swap over the order path is accepted
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::TakeSwap,
+            take_data.clone(),
+            None,
+            ("their_port", "channel-7"),
+            ("my_port", "channel-0"),
+        );
+        assert_eq!(err, None);
+        // Completed orders are archived
+        assert!(get_atomic_order(&deps.storage, &order_id).is_err());
+    }
+
+    #[test]
+    fn test_replayed_bid_packets() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make.clone()),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        let bidder = "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string();
+        let key = bid_key(&order_id, &bidder);
+        let receive = |deps: DepsMut, r#type: SwapMessageType, data: Binary, nonce: Option<u64>| {
+            let packet = AtomicSwapPacketData {
+                r#type,
+                data,
+                order_id: None,
+                path: None,
+                memo: "user memo".to_string(),
+                nonce,
+            };
+            let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+            msg.packet.data = encode_packet(&packet).unwrap();
+            msg.packet.src = IbcEndpoint {
+                port_id: "their_port".to_string(),
+                channel_id: "channel-7".to_string(),
+            };
+            msg.packet.dest = IbcEndpoint {
+                port_id: "my_port".to_string(),
+                channel_id: "channel-0".to_string(),
+            };
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement))
+        };
+        let make_bid = MakeBidMsg {
+            order_id: order_id.clone(),
+            sell_token: coin(150, "token2"),
+            taker_address: bidder.clone(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        }
+        .encode(&env)
+        .unwrap();
+        let update_bid = UpdateBidMsg {
+            order_id: order_id.clone(),
+            addition: Uint128::new(20),
+            bidder: bidder.clone(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        }
+        .encode(&env)
+        .unwrap();
+
+        // The first bid packet is processed, a replay of it is refused
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::MakeBid,
+            make_bid.clone(),
+            Some(0),
+        );
+        assert_eq!(err, None);
+        assert_eq!(bids().load(&deps.storage, key.clone()).unwrap().nonce, 1);
+        let err = receive(deps.as_mut(), SwapMessageType::MakeBid, make_bid, Some(0));
+        assert_eq!(
+            err,
+            Some(format!(
+                "Bid packet 0 of {} on order {} was already received",
+                bidder, order_id
+            ))
+        );
+
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::UpdateBid,
+            update_bid.clone(),
+            Some(1),
+        );
+        assert_eq!(err, None);
+        let err = receive(
+            deps.as_mut(),
+            SwapMessageType::UpdateBid,
+            update_bid.clone(),
+            Some(1),
+        );
+        assert!(err.is_some());
+        let bid = bids().load(&deps.storage, key.clone()).unwrap();
+        assert_eq!(bid.bid.amount, Uint128::new(170));
+        assert_eq!(bid.nonce, 2);
+
+        // Bid packets without a nonce are refused
+        let err = receive(deps.as_mut(), SwapMessageType::UpdateBid, update_bid, None);
+        assert_eq!(
+            err,
+            Some(
+                ContractError::MissingPacketField {
+                    field: "nonce".to_string()
+                }
+                .to_string()
+            )
+        );
+        let bid = bids().load(&deps.storage, key).unwrap();
+        assert_eq!(bid.bid.amount, Uint128::new(170));
+        assert_eq!(bid.nonce, 2);
+
+        // The bidder's chain sends the nonce and counts the packet once it is acknowledged
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[coin(150, "token2")]),
+            ExecuteMsg::MakeBid(MakeBidMsg {
+                order_id: remote.id.clone(),
+                sell_token: coin(150, "token2"),
+                taker_address: "bidder0001".to_string(),
+                taker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+                expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let packet = decode_packet(&data).unwrap();
+        assert_eq!(packet.nonce, Some(0));
+        assert_eq!(packet.memo, "");
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = data;
+        ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        let key = bid_key(&remote.id, &"bidder0001".to_string());
+        assert_eq!(bids().load(&deps.storage, key).unwrap().nonce, 1);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[coin(20, "token2")]),
+            ExecuteMsg::UpdateBid(UpdateBidMsg {
+                order_id: remote.id.clone(),
+                addition: Uint128::new(20),
+                bidder: "bidder0001".to_string(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        assert_eq!(decode_packet(&data).unwrap().nonce, Some(1));
+    }
+
+    #[test]
+    fn test_receive_take_bid() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        let bidder = "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string();
+        let key = bid_key(&order_id, &bidder);
+        let receive = |deps: DepsMut, r#type: SwapMessageType, data: Binary| {
+            let packet = AtomicSwapPacketData {
+                nonce: (r#type == SwapMessageType::MakeBid).then_some(0),
+                r#type,
+                data,
+                order_id: None,
+                path: None,
+                memo: String::new(),
+            };
+            let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+            msg.packet.data = encode_packet(&packet).unwrap();
+            msg.packet.src = IbcEndpoint {
+                port_id: "their_port".to_string(),
+                channel_id: "channel-7".to_string(),
+            };
+            msg.packet.dest = IbcEndpoint {
+                port_id: "my_port".to_string(),
+                channel_id: "channel-0".to_string(),
+            };
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            (
+                res.messages,
+                try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            )
+        };
+        let make_bid = MakeBidMsg {
+            order_id: order_id.clone(),
+            sell_token: coin(200, "token2"),
+            taker_address: bidder.clone(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        };
+        let (_, err) = receive(
+            deps.as_mut(),
+            SwapMessageType::MakeBid,
+            make_bid.encode(&env).unwrap(),
+        );
+        assert_eq!(err, None);
+        let take_bid = TakeBidMsg {
+            order_id: order_id.clone(),
+            bidder: bidder.clone(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        }
+        .encode(&env)
+        .unwrap();
+
+        // Only placed bids are taken
+        let mut bid = bids().load(&deps.storage, key.clone()).unwrap();
+        bid.status = BidStatus::Cancelled;
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &bid)
+            .unwrap();
+        let (messages, err) = receive(deps.as_mut(), SwapMessageType::TakeBid, take_bid.clone());
+        assert_eq!(err, Some("Bid doesn't exist".to_string()));
+        assert!(messages.is_empty());
+
+        // A failing check leaves the bid and the order as they were
+        bid.status = BidStatus::Placed;
+        bid.bidder_receiver = "x".to_string();
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &bid)
+            .unwrap();
+        let (messages, err) = receive(deps.as_mut(), SwapMessageType::TakeBid, take_bid.clone());
+        assert!(err.is_some());
+        assert!(messages.is_empty());
+        assert_eq!(
+            bids().load(&deps.storage, key.clone()).unwrap().status,
+            BidStatus::Placed
+        );
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.status, Status::Initial);
+        assert_eq!(order.taker, None);
+
+        bid.bidder_receiver = "takerrcpt0001".to_string();
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &bid)
+            .unwrap();
+        let (messages, err) = receive(deps.as_mut(), SwapMessageType::TakeBid, take_bid);
+        assert_eq!(err, None);
+        assert_eq!(
+            messages[1].msg,
+            BankMsg::Send {
+                to_address: "takerrcpt0001".to_string(),
+                amount: vec![coin(99, "token1")],
+            }
+            .into()
+        );
+        assert_eq!(
+            bids().load(&deps.storage, key).unwrap().status,
+            BidStatus::Executed
+        );
+        assert!(get_atomic_order(&deps.storage, &order_id).is_err());
+    }
+
+    #[test]
+    fn test_counterparty_chain_registry() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+
+        let chain = CounterpartyChain {
+            channel_id: "channel-0".to_string(),
+            chain_id: "side-1".to_string(),
+            bech32_prefix: "side".to_string(),
+            sell_denoms: vec!["token1".to_string()],
+            buy_denoms: vec!["token2".to_string()],
+            enabled: false,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::SetCounterpartyChain(chain.clone()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Std(StdError::generic_err("only admin allowed"))
+        );
+
+        let make = |deps: DepsMut, sell: &str| {
+            let msg = MakeSwapMsg {
+                source_port: "my_port".to_string(),
+                source_channel: "channel-0".to_string(),
+                sell_token: coin(100, sell),
+                buy_token: coin(200, "token2"),
+                maker_address: "maker0001".to_string(),
+                maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+                desired_taker: "".to_string(),
+                expiration_timestamp: mock_env().block.time.plus_seconds(100).seconds(),
+                timeout_height: Height {
+                    revision_number: 0,
+                    revision_height: 0,
+                },
+                timeout_timestamp: 0,
+                take_bids: false,
+                min_bid_price: None,
+                vesting: None,
+                allow_partial: false,
+                min_fill_amount: None,
+                extra_sell_tokens: vec![],
+                extra_buy_tokens: vec![],
+                auction: None,
+                dutch_auction: None,
+            };
+            execute(
+                deps,
+                mock_env(),
+                mock_info("maker0001", &[coin(100, sell)]),
+                ExecuteMsg::MakeSwap(msg),
+            )
+        };
+
+        let err = make(deps.as_mut(), "token1").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ChannelNotRegistered {
+                channel: "channel-0".to_string()
+            }
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetCounterpartyChain(chain.clone()),
+        )
+        .unwrap();
+        let err = make(deps.as_mut(), "token1").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ChannelDisabled {
+                channel: "channel-0".to_string()
+            }
+        );
+
+        let chain = CounterpartyChain {
+            enabled: true,
+            ..chain
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::SetCounterpartyChain(chain.clone()),
+        )
+        .unwrap();
+        let err = make(deps.as_mut(), "token3").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DenomNotAllowed {
+                denom: "token3".to_string(),
+                channel: "channel-0".to_string()
+            }
+        );
+        make(deps.as_mut(), "token1").unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::CounterpartyChains {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: CounterpartyChainsResponse = from_json(res).unwrap();
+        assert_eq!(value.chains, vec![chain]);
+    }
+
+    #[test]
+    fn test_validate_remote_address() {
+        let address = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        validate_remote_address(address, "side").unwrap();
+
+        let err = validate_remote_address(address, "cosmos").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidRemoteAddress {
+                address: address.to_string(),
+                prefix: "cosmos".to_string()
+            }
+        );
+        validate_remote_address("side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuyna", "side").unwrap_err();
+        validate_remote_address("side1", "side").unwrap_err();
+    }
+
+    #[test]
+    fn test_cancel_swap_timeout_and_retry() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        let cancel = ExecuteMsg::CancelSwap(CancelSwapMsg {
+            order_id: order_id.clone(),
+            maker_address: "maker0001".to_string(),
+            timeout_height: HeightOutput {
+                revision_number: "0".to_string(),
+                revision_height: "0".to_string(),
+            },
+            timeout_timestamp: "0".to_string(),
+        });
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[]),
+            cancel.clone(),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+
+        // Only one cancel at a time
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[]),
+            cancel.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketInFlight {
+                packet: "CancelSwap".to_string()
+            }
+        );
+
+        let pending = |deps: Deps, msg: QueryMsg| {
+            let res = query(deps, mock_env(), msg).unwrap();
+            from_json::<PendingPacketsResponse>(res).unwrap().packets
+        };
+        let packets = pending(
+            deps.as_ref(),
+            QueryMsg::PendingPackets {
+                order_id: order_id.clone(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert_eq!(packets.len(), 2);
+        assert!(packets
+            .iter()
+            .any(|packet| packet.kind == SwapMessageType::CancelSwap
+                && packet.id == pending_packet_id(&decode_packet(&data).unwrap()).unwrap()));
+
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = data;
+        let res = ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.events[0].ty, "packet_failed");
+
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.status, Status::Initial);
+        assert_eq!(order.pending, None);
+        // Only the make swap packet is left
+        let packets = pending(
+            deps.as_ref(),
+            QueryMsg::PendingPacketsByChannel {
+                channel_id: "channel-0".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].kind, SwapMessageType::MakeSwap);
+        assert_eq!(packets[0].sender, "maker0001");
+        assert_eq!(order.last_error, Some("timeout".to_string()));
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::FailedPackets {
+                order_id: order_id.clone(),
+            },
+        )
+        .unwrap();
+        let value: FailedPacketsResponse = from_json(res).unwrap();
+        assert_eq!(value.packets.len(), 1);
+        assert_eq!(value.packets[0].sender, "maker0001");
+        assert_eq!(value.retries, 0);
+
+        // Only the maker can send it again
+        let retry = ExecuteMsg::RetryPacket {
+            order_id: order_id.clone(),
+            kind: SwapMessageType::CancelSwap,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            retry.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NoFailedPacket);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[]),
+            retry.clone(),
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.attributes.last().unwrap().value, "1");
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.pending, Some(SwapMessageType::CancelSwap));
+        assert_eq!(order.last_error, None);
+
+        let err = execute(deps.as_mut(), env, mock_info("maker0001", &[]), retry).unwrap_err();
+        assert_eq!(err, ContractError::NoFailedPacket);
+        // A manual cancel waits for the retried packet
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("maker0001", &[]),
+            cancel,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketInFlight {
+                packet: "CancelSwap".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_pending_packets() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let receiver = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: receiver.to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of an order made on the other chain, bid on from this one
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+
+        let pending = |deps: Deps, msg: QueryMsg| {
+            let res = query(deps, mock_env(), msg).unwrap();
+            from_json::<PendingPacketsResponse>(res).unwrap().packets
+        };
+        let by_order = |start_after: Option<String>, limit: Option<u32>| QueryMsg::PendingPackets {
+            order_id: remote.id.clone(),
+            start_after,
+            limit,
+        };
+        let mut sent = vec![];
+        for bidder in ["bidder0001", "bidder0002"] {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(bidder, &[coin(150, "token2")]),
+                ExecuteMsg::MakeBid(MakeBidMsg {
+                    order_id: remote.id.clone(),
+                    sell_token: coin(150, "token2"),
+                    taker_address: bidder.to_string(),
+                    taker_receiving_address: receiver.to_string(),
+                    expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+                    timeout_height: None,
+                    timeout_timestamp: None,
+                }),
+            )
+            .unwrap();
+            match &res.messages[0].msg {
+                CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => sent.push(data.clone()),
+                msg => panic!("unexpected message {:?}", msg),
+            }
+        }
+
+        // Each bid packet has its own entry
+        let packets = pending(deps.as_ref(), by_order(None, None));
+        let ids: Vec<String> = packets.iter().map(|packet| packet.id.clone()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "remote-order/4/bidder0001".to_string(),
+                "remote-order/4/bidder0002".to_string(),
+            ]
+        );
+        assert_eq!(packets[0].kind, SwapMessageType::MakeBid);
+        assert_eq!(packets[0].bidder, Some("bidder0001".to_string()));
+        let packets = pending(deps.as_ref(), by_order(Some(ids[0].clone()), Some(1)));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].id, ids[1]);
+        let packets = pending(
+            deps.as_ref(),
+            QueryMsg::PendingPacketsBySender {
+                sender: "bidder0002".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].id, ids[1]);
+        let packets = pending(
+            deps.as_ref(),
+            QueryMsg::PendingPacketsByChannel {
+                channel_id: "channel-0".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        // The make swap packet is on the same channel
+        assert_eq!(packets.len(), 3);
+
+        // An ack clears the entry of its packet only
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = sent[0].clone();
+        ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        let packets = pending(deps.as_ref(), by_order(None, None));
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].id, ids[1]);
+
+        // So does a timeout
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = sent[1].clone();
+        ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        assert!(pending(deps.as_ref(), by_order(None, None)).is_empty());
+
+        // Closing the channel drops the packets still on it
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[]),
+            ExecuteMsg::CancelBid(CancelBidMsg {
+                order_id: remote.id.clone(),
+                bidder: "bidder0001".to_string(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap();
+        assert_eq!(pending(deps.as_ref(), by_order(None, None)).len(), 1);
+        ibc_channel_close(
+            deps.as_mut(),
+            env,
+            mock_ibc_channel_close_init("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        let packets = pending(
+            deps.as_ref(),
+            QueryMsg::PendingPacketsByChannel {
+                channel_id: "channel-0".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert!(packets.is_empty());
+    }
+
+    #[test]
+    fn test_expire_swap() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let sell_token = coin(100, "token1");
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: sell_token.clone(),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+        let mut order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        order.status = Status::Sync;
+        set_atomic_order(deps.as_mut().storage, &order_id, &order).unwrap();
+
+        // Copy of an order made on the other chain, with a bid escrowed here
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Placed,
+            bidder: "bidder0001".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        let bid_key = bid_key(&remote.id, &bid.bidder);
+        bids()
+            .save(deps.as_mut().storage, bid_key.clone(), &bid)
+            .unwrap();
+
+        let expire = |order_id: &str| {
+            ExecuteMsg::ExpireSwap(ExpireSwapMsg {
+                order_id: order_id.to_string(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            })
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            expire(&order_id),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NotExpired);
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            expire(&order_id),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+
+        // The taker chain closed its copy, the maker is refunded
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = data;
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "maker0001".to_string(),
+                amount: vec![sell_token],
+            }
+            .into()
+        );
+        assert!(get_atomic_order(&deps.storage, &order_id).is_err());
+
+        // The other chain expires its order, the bids escrowed here are refunded
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::ExpireSwap,
+            data: ExpireSwapMsg {
+                order_id: remote.id.clone(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            None
+        );
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(150, "token2")],
+            }
+            .into()
+        );
+        assert!(get_atomic_order(&deps.storage, &remote.id).is_err());
+        let bid = bids().load(&deps.storage, bid_key).unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+    }
+
+    #[test]
+    fn test_update_swap() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+        let mut order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        order.status = Status::Sync;
+        set_atomic_order(deps.as_mut().storage, &order_id, &order).unwrap();
+
+        let update = UpdateSwapMsg {
+            order_id: order_id.clone(),
+            buy_token: Some(coin(300, "token2")),
+            expiration_timestamp: None,
+            min_bid_price: None,
+            take_bids: Some(false),
+            desired_taker: None,
+            timeout_height: None,
+            timeout_timestamp: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::UpdateSwap(update.clone()),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        // The order only changes once the taker chain acknowledges the update
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[]),
+            ExecuteMsg::UpdateSwap(update.clone()),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.pending, Some(SwapMessageType::UpdateSwap));
+        assert_eq!(order.maker.buy_token, coin(200, "token2"));
+
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = data;
+        ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.pending, None);
+        assert_eq!(order.maker.buy_token, coin(300, "token2"));
+        assert!(!order.maker.take_bids);
+
+        // Copy of an order made on the other chain, with a bid escrowed here
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            maker: MakeSwapMsg {
+                take_bids: true,
+                ..order.maker
+            },
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Placed,
+            bidder: "bidder0001".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        bids()
+            .save(
+                deps.as_mut().storage,
+                bid_key(&remote.id, &bid.bidder),
+                &bid,
+            )
+            .unwrap();
+
+        // The maker stops taking bids, the bid escrowed here is refunded
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::UpdateSwap,
+            data: UpdateSwapMsg {
+                order_id: remote.id.clone(),
+                buy_token: None,
+                expiration_timestamp: Some(env.block.time.plus_seconds(500).seconds()),
+                ..update
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            None
+        );
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(150, "token2")],
+            }
+            .into()
+        );
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert!(!remote.maker.take_bids);
+        assert_eq!(
+            remote.maker.expiration_timestamp,
+            mock_env().block.time.plus_seconds(500).seconds()
+        );
+    }
+
+    #[test]
+    fn test_cw20_swap() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "cw20:token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "maker0001".to_string(),
+            amount: Uint128::from(100u64),
+            msg: to_json_binary(&ReceiveMsg::MakeSwap(make)).unwrap(),
+        });
+        let res = execute(deps.as_mut(), env, mock_info("token1", &[]), receive).unwrap();
+        let order_id = res.attributes[0].value.clone();
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.maker.maker_address, "maker0001");
+        assert_eq!(order.maker.sell_token, coin(100, "cw20:token1"));
+
+        // The take is received from the taker chain and pays out the cw20 tokens
+        let take = TakeSwapMsg {
+            order_id: order_id.clone(),
+            sell_token: coin(200, "token2"),
+            taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+        };
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::TakeSwap,
+            data: take.encode(&mock_env()).unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let transfer = |recipient: &str, amount: u128| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token1".to_string(),
+                msg: to_json_binary(&cw20::Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::from(amount),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+        assert_eq!(
+            res.messages,
+            vec![transfer("treasury", 1), transfer("takerrcpt0001", 99)]
+        );
+    }
+
+    #[test]
+    fn test_nft_swap() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(1, "cw721:nft/7"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let receive = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "maker0001".to_string(),
+            token_id: "7".to_string(),
+            msg: to_json_binary(&ReceiveMsg::MakeSwap(make)).unwrap(),
+        });
+        let res = execute(deps.as_mut(), env, mock_info("nft", &[]), receive).unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        // The NFT is transferred to the taker without a fee
+        let take = TakeSwapMsg {
+            order_id,
+            sell_token: coin(200, "token2"),
+            taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+        };
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::TakeSwap,
+            data: take.encode(&mock_env()).unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let transfer_nft = SubMsg::new(WasmMsg::Execute {
+            contract_addr: "nft".to_string(),
+            msg: to_json_binary(&cw721::Cw721ExecuteMsg::TransferNft {
+                recipient: "takerrcpt0001".to_string(),
+                token_id: "7".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        });
+        assert_eq!(res.messages, vec![transfer_nft]);
+    }
+
+    #[test]
+    fn test_basket_order() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![coin(3000, "token3")],
+            extra_buy_tokens: vec![coin(50, "token4")],
+            auction: None,
+            dutch_auction: None,
+        };
+        // The whole basket has to be sent
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make.clone()),
+        )
+        .unwrap_err();
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("maker0001", &[coin(100, "token1"), coin(3000, "token3")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        // The packet carries the basket to the taker chain
+        let CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) = &res.messages[0].msg else {
+            panic!("expected a packet");
+        };
+        let packet = decode_packet(data).unwrap();
+        let sent = MakeSwapMsg::decode(&packet.data).unwrap();
+        assert_eq!(sent.extra_sell_tokens, vec![coin(3000, "token3")]);
+        assert_eq!(sent.extra_buy_tokens, vec![coin(50, "token4")]);
+
+        // The taker gets every sold token, each less its own fee
+        let take = TakeSwapMsg {
+            order_id,
+            sell_token: coin(200, "token2"),
+            taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+        };
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::TakeSwap,
+            data: take.encode(&mock_env()).unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), mock_env(), msg).unwrap();
+        let send = |to: &str, amount: u128, denom: &str| {
+            SubMsg::new(BankMsg::Send {
+                to_address: to.to_string(),
+                amount: vec![coin(amount, denom)],
+            })
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                send("treasury", 1, "token1"),
+                send("takerrcpt0001", 99, "token1"),
+                send("takerrcpt0001", 2997, "token3"),
+                send("treasury", 3, "token3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_fill() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: true,
+            min_fill_amount: Some(Uint128::from(50u64)),
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order_id = res.attributes[0].value.clone();
+
+        // Takes received from the taker chain over the order path
+        let mut sequence = 0;
+        let mut receive = |deps: DepsMut, amount: u128| {
+            let take = TakeSwapMsg {
+                order_id: order_id.clone(),
+                sell_token: coin(amount, "token2"),
+                taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+                taker_receiving_address: "takerrcpt0001".to_string(),
+                timeout_height: Height {
+                    revision_number: 0,
+                    revision_height: 0,
+                },
+                timeout_timestamp: 0,
+            };
+            let packet = AtomicSwapPacketData {
+                r#type: SwapMessageType::TakeSwap,
+                data: take.encode(&mock_env()).unwrap(),
+                order_id: None,
+                path: None,
+                memo: String::new(),
+                nonce: None,
+            };
+            let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+            sequence += 1;
+            msg.packet.sequence = sequence;
+            msg.packet.data = encode_packet(&packet).unwrap();
+            msg.packet.src = IbcEndpoint {
+                port_id: "their_port".to_string(),
+                channel_id: "channel-7".to_string(),
+            };
+            msg.packet.dest = IbcEndpoint {
+                port_id: "my_port".to_string(),
+                channel_id: "channel-0".to_string(),
+            };
+            let res = ibc_packet_receive(deps, mock_env(), msg).unwrap();
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement))
+        };
+
+        assert_eq!(
+            receive(deps.as_mut(), 30),
+            Some("Invalid fill amount".to_string())
+        );
+        assert_eq!(receive(deps.as_mut(), 50), None);
+        let order = get_atomic_order(&deps.storage, &order_id).unwrap();
+        assert_eq!(order.taker, None);
+        assert_eq!(order.remaining_sell(), coin(75, "token1"));
+        assert_eq!(order.remaining_buy(), coin(150, "token2"));
+
+        // The last fill takes the rest and completes the order
+        assert_eq!(receive(deps.as_mut(), 150), None);
+        assert!(get_atomic_order(&deps.storage, &order_id).is_err());
+
+        let res: FillsResponse = from_json(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::Fills {
+                    order_id: order_id.clone(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.fills.len(), 2);
+        assert_eq!(res.fills[0].sell_token, coin(25, "token1"));
+        assert_eq!(res.fills[0].buy_token, coin(50, "token2"));
+        assert_eq!(res.fills[1].sell_token, coin(75, "token1"));
+    }
+
+    #[test]
+    fn test_cancel_swap_refunds_bids() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of an order made on the other chain, with more bids escrowed here than
+        // one transaction refunds
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        // Bids that aren't placed any more are skipped
+        let cancelled = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Cancelled,
+            bidder: "bidder-cancelled".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        let cancelled_key = bid_key(&remote.id, &cancelled.bidder);
+        bids()
+            .save(deps.as_mut().storage, cancelled_key.clone(), &cancelled)
+            .unwrap();
+        for i in 0..=MAX_BID_REFUNDS {
+            let bid = Bid {
+                bid: coin(150, "token2"),
+                order: remote.id.clone(),
+                status: BidStatus::Placed,
+                bidder: format!("bidder{:04}", i),
+                bidder_receiver: "bidderrcpt0001".to_string(),
+                receive_timestamp: env.block.time.seconds(),
+                expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+                remote: None,
+                pending: None,
+                last_error: None,
+                nonce: 0,
+            };
+            bids()
+                .save(
+                    deps.as_mut().storage,
+                    bid_key(&remote.id, &bid.bidder),
+                    &bid,
+                )
+                .unwrap();
+        }
+
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::CancelSwap,
+            data: CancelSwapMsg {
+                order_id: remote.id.clone(),
+                maker_address: "maker0001".to_string(),
+                timeout_height: HeightOutput {
+                    revision_number: "0".to_string(),
+                    revision_height: "0".to_string(),
+                },
+                timeout_timestamp: "0".to_string(),
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+
+        // Not while a bid taken here could still be paid out on the other chain
+        let taking = AtomicSwapOrder {
+            pending: Some(SwapMessageType::TakeBid),
+            ..remote.clone()
+        };
+        set_atomic_order(deps.as_mut().storage, &remote.id, &taking).unwrap();
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            Some(
+                ContractError::PacketInFlight {
+                    packet: "TakeBid".to_string()
+                }
+                .to_string()
+            )
+        );
+        assert!(res.messages.is_empty());
+        set_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            None
+        );
+        assert_eq!(res.messages.len(), MAX_BID_REFUNDS as usize);
+
+        // The last bid is left for RefundBids
+        let refund = ExecuteMsg::RefundBids {
+            order_id: remote.id.clone(),
+            limit: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            refund.clone(),
+        )
+        .unwrap();
+        let last = format!("bidder{:04}", MAX_BID_REFUNDS);
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: last.clone(),
+                amount: vec![coin(150, "token2")],
+            }
+            .into()
+        );
+        let bid = bids()
+            .load(&deps.storage, bid_key(&remote.id, &last))
+            .unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+
+        let err =
+            execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), refund).unwrap_err();
+        assert_eq!(err, ContractError::NoBidsToRefund);
+        assert_eq!(
+            bids().load(&deps.storage, cancelled_key).unwrap().status,
+            BidStatus::Cancelled
+        );
+
+        // A refunded bid can't be cancelled again
+        let cancel = ExecuteMsg::CancelBid(CancelBidMsg {
+            order_id: remote.id.clone(),
+            bidder: last.clone(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+        let err = execute(deps.as_mut(), env.clone(), mock_info(&last, &[]), cancel).unwrap_err();
+        assert_eq!(err, ContractError::BidDoesntExist);
+
+        // Nor taken, its deposit is gone
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::TakeBid,
+            data: TakeBidMsg {
+                order_id: remote.id.clone(),
+                bidder: last.clone(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = encode_packet(&packet).unwrap();
+        let res = ibc_packet_ack(deps.as_mut(), env, msg).unwrap();
+        assert!(res.messages.is_empty());
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert_eq!(remote.status, Status::Cancel);
+        assert_eq!(remote.pending, None);
+        assert!(remote.last_error.is_some());
+        let bid = bids()
+            .load(&deps.storage, bid_key(&remote.id, &last))
+            .unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+    }
+
+    #[test]
+    fn test_order_closes_with_bid_packets_in_flight() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of an order made on the other chain, most of its bids have a packet in flight
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order.clone()
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        for (bidder, status, pending) in [
+            (
+                "bidder0001",
+                BidStatus::Placed,
+                Some(SwapMessageType::CancelBid),
+            ),
+            (
+                "bidder0002",
+                BidStatus::Placed,
+                Some(SwapMessageType::CancelBid),
+            ),
+            ("bidder0003", BidStatus::Placed, None),
+            (
+                "bidder0004",
+                BidStatus::Placed,
+                Some(SwapMessageType::UpdateBid),
+            ),
+            (
+                "bidder0005",
+                BidStatus::Refunded,
+                Some(SwapMessageType::UpdateBid),
+            ),
+        ] {
+            let bid = Bid {
+                bid: coin(150, "token2"),
+                order: remote.id.clone(),
+                status,
+                bidder: bidder.to_string(),
+                bidder_receiver: "bidderrcpt0001".to_string(),
+                receive_timestamp: env.block.time.seconds(),
+                expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+                remote: None,
+                pending,
+                last_error: None,
+                nonce: 0,
+            };
+            bids()
+                .save(
+                    deps.as_mut().storage,
+                    bid_key(&remote.id, &bidder.to_string()),
+                    &bid,
+                )
+                .unwrap();
+        }
+        let bid_status = |deps: Deps, bidder: &str| {
+            bids()
+                .load(
+                    deps.storage,
+                    bid_key(&"remote-order".to_string(), &bidder.to_string()),
+                )
+                .unwrap()
+                .status
+        };
+
+        // Only the bid without a packet in flight is refunded with the order
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::CancelSwap,
+            data: CancelSwapMsg {
+                order_id: remote.id.clone(),
+                maker_address: "maker0001".to_string(),
+                timeout_height: HeightOutput {
+                    revision_number: "0".to_string(),
+                    revision_height: "0".to_string(),
+                },
+                timeout_timestamp: "0".to_string(),
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: None,
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            None
+        );
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0003".to_string(),
+                amount: vec![coin(150, "token2")],
+            })]
+        );
+        assert_eq!(bid_status(deps.as_ref(), "bidder0001"), BidStatus::Placed);
+        assert_eq!(bid_status(deps.as_ref(), "bidder0003"), BidStatus::Refunded);
+
+        // The acknowledged cancel refunds its bid once
+        let cancel_bid = |bidder: &str| {
+            let packet = AtomicSwapPacketData {
+                r#type: SwapMessageType::CancelBid,
+                data: CancelBidMsg {
+                    order_id: "remote-order".to_string(),
+                    bidder: bidder.to_string(),
+                    timeout_height: None,
+                    timeout_timestamp: None,
+                }
+                .encode(&env)
+                .unwrap(),
+                order_id: None,
+                path: None,
+                memo: String::new(),
+                nonce: Some(0),
+            };
+            encode_packet(&packet).unwrap()
+        };
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = cancel_bid("bidder0001");
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg.clone()).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            bid_status(deps.as_ref(), "bidder0001"),
+            BidStatus::Cancelled
+        );
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        // The timed out one is refunded as the order is gone
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = cancel_bid("bidder0002");
+        let res = ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0002".to_string(),
+                amount: vec![coin(150, "token2")],
+            })]
+        );
+        let bid = bids()
+            .load(
+                &deps.storage,
+                bid_key(&"remote-order".to_string(), &"bidder0002".to_string()),
+            )
+            .unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+        assert_eq!(bid.pending, None);
+
+        // A top up of a placed bid is refunded with the bid, the one of a refunded bid alone
+        let update_bid = |bidder: &str| {
+            let packet = AtomicSwapPacketData {
+                r#type: SwapMessageType::UpdateBid,
+                data: UpdateBidMsg {
+                    order_id: "remote-order".to_string(),
+                    addition: Uint128::new(20),
+                    bidder: bidder.to_string(),
+                    timeout_height: None,
+                    timeout_timestamp: None,
+                }
+                .encode(&env)
+                .unwrap(),
+                order_id: None,
+                path: None,
+                memo: String::new(),
+                nonce: Some(0),
+            };
+            encode_packet(&packet).unwrap()
+        };
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = update_bid("bidder0004");
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0004".to_string(),
+                amount: vec![coin(170, "token2")],
+            })]
+        );
+        assert_eq!(bid_status(deps.as_ref(), "bidder0004"), BidStatus::Refunded);
+
+        msg.original_packet.data = update_bid("bidder0005");
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0005".to_string(),
+                amount: vec![coin(20, "token2")],
+            })]
+        );
+        let bid = bids()
+            .load(
+                &deps.storage,
+                bid_key(&"remote-order".to_string(), &"bidder0005".to_string()),
+            )
+            .unwrap();
+        assert_eq!(bid.bid, coin(150, "token2"));
+        assert_eq!(bid.pending, None);
+
+        // The maker chain doesn't cancel a bid it already closed
+        let refunded = Bid {
+            bid: coin(150, "token2"),
+            order: order.id.clone(),
+            status: BidStatus::Refunded,
+            bidder: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        let key = bid_key(&order.id, &refunded.bidder);
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &refunded)
+            .unwrap();
+        let packet = AtomicSwapPacketData {
+            r#type: SwapMessageType::CancelBid,
+            data: CancelBidMsg {
+                order_id: order.id.clone(),
+                bidder: refunded.bidder.clone(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }
+            .encode(&env)
+            .unwrap(),
+            order_id: None,
+            path: None,
+            memo: String::new(),
+            nonce: Some(0),
+        };
+        let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+        msg.packet.data = encode_packet(&packet).unwrap();
+        msg.packet.src = IbcEndpoint {
+            port_id: "their_port".to_string(),
+            channel_id: "channel-7".to_string(),
+        };
+        msg.packet.dest = IbcEndpoint {
+            port_id: "my_port".to_string(),
+            channel_id: "channel-0".to_string(),
+        };
+        let res = ibc_packet_receive(deps.as_mut(), env, msg).unwrap();
+        assert_eq!(
+            try_get_ack_error(&IbcAcknowledgement::new(res.acknowledgement)),
+            Some(ContractError::BidDoesntExist.to_string())
+        );
+        assert_eq!(
+            bids().load(&deps.storage, key).unwrap().status,
+            BidStatus::Refunded
+        );
+    }
+
+    #[test]
+    fn test_reject_bid() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let maker_receiver = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: maker_receiver.to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of an order made on the other chain, with a bid escrowed here
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Placed,
+            bidder: "bidder0001".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        let key = bid_key(&remote.id, &bid.bidder);
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &bid)
+            .unwrap();
+
+        let reject = ExecuteMsg::RejectBid(RejectBidMsg {
+            order_id: remote.id.clone(),
+            bidder: bid.bidder.clone(),
+            reason: Some("too low".to_string()),
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[]),
+            reject.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(maker_receiver, &[]),
+            reject,
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let pending = bids().load(&deps.storage, key.clone()).unwrap();
+        assert_eq!(pending.pending, Some(SwapMessageType::RejectBid));
+
+        // The maker chain closed its copy, the deposit is refunded here
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = data;
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(150, "token2")],
+            }
+            .into()
+        );
+        let rejected = bids().load(&deps.storage, key).unwrap();
+        assert_eq!(rejected.status, BidStatus::Rejected);
+        assert_eq!(rejected.pending, None);
+
+        // Nothing is left to refund for a rejected bid
+        let res = ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        assert!(res.messages.is_empty());
+
+        // A reject that fails after the order closed refunds the bid it kept open
+        let other = Bid {
+            bidder: "bidder0002".to_string(),
+            ..bid
+        };
+        let key = bid_key(&remote.id, &other.bidder);
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &other)
+            .unwrap();
+        let reject = ExecuteMsg::RejectBid(RejectBidMsg {
+            order_id: remote.id.clone(),
+            bidder: other.bidder.clone(),
+            reason: None,
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(maker_receiver, &[]),
+            reject,
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let cancelled = AtomicSwapOrder {
+            status: Status::Cancel,
+            ..remote.clone()
+        };
+        set_atomic_order(deps.as_mut().storage, &remote.id, &cancelled).unwrap();
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = data;
+        let res = ibc_packet_timeout(deps.as_mut(), env, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0002".to_string(),
+                amount: vec![coin(150, "token2")],
+            })]
+        );
+        let bid = bids().load(&deps.storage, key).unwrap();
+        assert_eq!(bid.status, BidStatus::Refunded);
+        assert_eq!(bid.pending, None);
+    }
+
+    #[test]
+    fn test_english_auction() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let receiver = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        let end_time = env.block.time.plus_seconds(100).seconds();
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: receiver.to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(1000).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: Some(AuctionConfig {
+                reserve_price: Uint128::new(100),
+                end_time,
+                min_increment: Uint128::new(10),
+                extension_window: 20,
+            }),
+            dutch_auction: None,
+        };
+        let info = mock_info("maker0001", &[coin(100, "token1")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::MakeSwap(make.clone()),
+        )
+        .unwrap_err();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::MakeSwap(MakeSwapMsg {
+                take_bids: true,
+                ..make
+            }),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of the auction on the chain holding the bids
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order.clone()
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+
+        let bid = |deps: DepsMut, env: &Env, bidder: &str, amount: u128| {
+            let msg = MakeBidMsg {
+                order_id: "remote-order".to_string(),
+                sell_token: coin(amount, "token2"),
+                taker_address: bidder.to_string(),
+                taker_receiving_address: receiver.to_string(),
+                expiration_timestamp: env.block.time.plus_seconds(10).seconds(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            };
+            let info = mock_info(bidder, &[coin(amount, "token2")]);
+            execute(deps, env.clone(), info, ExecuteMsg::MakeBid(msg))
+        };
+        let place = |deps: DepsMut, bidder: &str| {
+            let key = bid_key(&"remote-order".to_string(), &bidder.to_string());
+            let mut bid = bids().load(deps.storage, key.clone()).unwrap();
+            bid.status = BidStatus::Placed;
+            bids().save(deps.storage, key, &bid).unwrap();
+        };
+        let settle = ExecuteMsg::SettleAuction(SettleAuctionMsg {
+            order_id: remote.id.clone(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+
+        // Bids reach the reserve and beat the highest one by the increment, acked or not
+        let err = bid(deps.as_mut(), &env, "bidder0001", 90).unwrap_err();
+        assert_eq!(err, ContractError::InvalidBidAmount);
+        bid(deps.as_mut(), &env, "bidder0001", 100).unwrap();
+        let err = bid(deps.as_mut(), &env, "bidder0002", 105).unwrap_err();
+        assert_eq!(err, ContractError::InvalidBidAmount);
+        place(deps.as_mut(), "bidder0001");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            settle.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::AuctionNotEnded);
+
+        // A late bid extends the auction
+        env.block.time = env.block.time.plus_seconds(90);
+        bid(deps.as_mut(), &env, "bidder0002", 120).unwrap();
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert_eq!(remote.maker.auction.unwrap().end_time, end_time + 10);
+
+        env.block.time = env.block.time.plus_seconds(15);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            settle.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::AuctionNotEnded);
+
+        // The highest bid has to be placed before it is taken
+        env.block.time = env.block.time.plus_seconds(5);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            settle.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketInFlight {
+                packet: "MakeBid".to_string()
+            }
+        );
+        place(deps.as_mut(), "bidder0002");
+
+        // The highest bid stands and the maker can't pick another one
+        let cancel = ExecuteMsg::CancelBid(CancelBidMsg {
+            order_id: remote.id.clone(),
+            bidder: "bidder0002".to_string(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0002", &[]),
+            cancel,
+        )
+        .unwrap_err();
+        let take = ExecuteMsg::TakeBid(TakeBidMsg {
+            order_id: remote.id.clone(),
+            bidder: "bidder0001".to_string(),
+            timeout_height: None,
+            timeout_timestamp: None,
+        });
+        execute(deps.as_mut(), env.clone(), mock_info(receiver, &[]), take).unwrap_err();
+
+        // Only settled where the bids are
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::SettleAuction(SettleAuctionMsg {
+                order_id: order.id.clone(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidChain);
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), settle).unwrap();
+        assert_eq!(res.attributes[2], attr("bidder", "bidder0002"));
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let packet = decode_packet(&data).unwrap();
+        assert_eq!(packet.r#type, SwapMessageType::TakeBid);
+        assert_eq!(
+            TakeBidMsg::decode(&packet.data).unwrap().bidder,
+            "bidder0002"
+        );
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert_eq!(remote.pending, Some(SwapMessageType::TakeBid));
+
+        // A settlement that times out is retried by settling again
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = data;
+        ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert_eq!(remote.pending, None);
+
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info(receiver, &[]),
+            ExecuteMsg::RetryPacket {
+                order_id: remote.id.clone(),
+                kind: SwapMessageType::TakeBid,
+            },
+        )
+        .unwrap();
+        assert_eq!(res.attributes[2], attr("bidder", "bidder0002"));
+        assert!(res.attributes.contains(&attr("retries", "1")));
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let packet = decode_packet(&data).unwrap();
+        assert_eq!(packet.r#type, SwapMessageType::TakeBid);
+        let remote = get_atomic_order(&deps.storage, &remote.id).unwrap();
+        assert_eq!(remote.pending, Some(SwapMessageType::TakeBid));
+    }
+
+    #[test]
+    fn test_dutch_auction() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 100,
+                taker_fee: 100,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(1000, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(1000).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: false,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: true,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: Some(DutchAuctionConfig {
+                floor_price: Uint128::new(400),
+                start_time: env.block.time.seconds(),
+                end_time: env.block.time.plus_seconds(100).seconds(),
+                step: 0,
+            }),
+        };
+        let info = mock_info("maker0001", &[coin(100, "token1")]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::MakeSwap(make.clone()),
+        )
+        .unwrap_err();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::MakeSwap(MakeSwapMsg {
+                allow_partial: false,
+                ..make
+            }),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of the auction on the taker chain
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order.clone()
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+
+        // The price falls linearly
+        env.block.time = env.block.time.plus_seconds(50);
+        let res: CurrentPriceResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CurrentPrice {
+                    order_id: order.id.clone(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.price, coin(700, "token2"));
+
+        let take = |amount: u128| TakeSwapMsg {
+            order_id: order.id.clone(),
+            sell_token: coin(amount, "token2"),
+            taker_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+            taker_receiving_address: "takerrcpt0001".to_string(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+        };
+        let receive = |deps: DepsMut, env: &Env, sequence: u64, take: TakeSwapMsg| {
+            let packet = AtomicSwapPacketData {
+                r#type: SwapMessageType::TakeSwap,
+                data: take.encode(env).unwrap(),
+                order_id: None,
+                path: None,
+                memo: String::new(),
+                nonce: None,
+            };
+            let mut msg = mock_ibc_packet_recv("channel-0", &"").unwrap();
+            msg.packet.sequence = sequence;
+            msg.packet.data = encode_packet(&packet).unwrap();
+            msg.packet.src = IbcEndpoint {
+                port_id: "their_port".to_string(),
+                channel_id: "channel-7".to_string(),
+            };
+            msg.packet.dest = IbcEndpoint {
+                port_id: "my_port".to_string(),
+                channel_id: "channel-0".to_string(),
+            };
+            let res = ibc_packet_receive(deps, env.clone(), msg).unwrap();
+            IbcAcknowledgement::new(res.acknowledgement)
+        };
+
+        // The maker chain charges its current price and reports it
+        let ack = receive(deps.as_mut(), &env, 1, take(650));
+        assert_eq!(
+            try_get_ack_error(&ack),
+            Some("Pay at least the current price of 700token2".to_string())
+        );
+        let ack = receive(deps.as_mut(), &env, 2, take(750));
+        assert_eq!(try_get_ack_error(&ack), None);
+        assert_eq!(
+            try_get_ack_result(&ack).unwrap().price,
+            Some(Uint128::new(700))
+        );
+
+        // The taker chain pays the maker the price, less the fee, and refunds the surplus
+        let taker = "taker0001";
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(taker, &[coin(750, "token2")]),
+            ExecuteMsg::TakeSwap(TakeSwapMsg {
+                order_id: remote.id.clone(),
+                taker_address: taker.to_string(),
+                taker_receiving_address: "side1qgpqyqszqgpqyqszqgpqyqszqgpqyqszhk6pcd".to_string(),
+                ..take(750)
+            }),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let mut msg = mock_ibc_packet_ack("channel-0", &"", ack).unwrap();
+        msg.original_packet.data = data;
+        let res = ibc_packet_ack(deps.as_mut(), env, msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            BankMsg::Send {
+                to_address: "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm".to_string(),
+                amount: vec![coin(693, "token2")],
+            }
+            .into()
+        );
+        assert!(res.messages.contains(&SubMsg::new(BankMsg::Send {
+            to_address: taker.to_string(),
+            amount: vec![coin(50, "token2")],
+        })));
+    }
+
+    #[test]
+    fn test_counter_offer() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            InstantiateMsg {
+                maker_fee: 10,
+                taker_fee: 10,
+                treasury: "treasury".to_string(),
+                vesting_contract: "".to_string(),
+            },
+        )
+        .unwrap();
+        ibc_channel_connect(
+            deps.as_mut(),
+            env.clone(),
+            mock_ibc_channel_connect_ack("channel-0", IbcOrder::Unordered, "ics100-1"),
+        )
+        .unwrap();
+        register_channel(deps.as_mut(), "channel-0");
+
+        let maker_receiver = "side1qyqszqgpqyqszqgpqyqszqgpqyqszqgpxjuynm";
+        let make = MakeSwapMsg {
+            source_port: "my_port".to_string(),
+            source_channel: "channel-0".to_string(),
+            sell_token: coin(100, "token1"),
+            buy_token: coin(200, "token2"),
+            maker_address: "maker0001".to_string(),
+            maker_receiving_address: maker_receiver.to_string(),
+            desired_taker: "".to_string(),
+            expiration_timestamp: env.block.time.plus_seconds(100).seconds(),
+            timeout_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            timeout_timestamp: 0,
+            take_bids: true,
+            min_bid_price: None,
+            vesting: None,
+            allow_partial: false,
+            min_fill_amount: None,
+            extra_sell_tokens: vec![],
+            extra_buy_tokens: vec![],
+            auction: None,
+            dutch_auction: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[coin(100, "token1")]),
+            ExecuteMsg::MakeSwap(make),
+        )
+        .unwrap();
+        let order = get_atomic_order(&deps.storage, &res.attributes[0].value).unwrap();
+
+        // Copy of an order made on the other chain, with a bid escrowed here
+        let remote = AtomicSwapOrder {
+            id: "remote-order".to_string(),
+            side: Side::Remote,
+            status: Status::Sync,
+            path: order_path(
+                "channel-7".to_string(),
+                "their_port".to_string(),
+                "channel-0".to_string(),
+                "my_port".to_string(),
+                0,
+            )
+            .unwrap(),
+            ..order
+        };
+        append_atomic_order(deps.as_mut().storage, &remote.id, &remote).unwrap();
+        let bid = Bid {
+            bid: coin(150, "token2"),
+            order: remote.id.clone(),
+            status: BidStatus::Placed,
+            bidder: "bidder0001".to_string(),
+            bidder_receiver: "bidderrcpt0001".to_string(),
+            receive_timestamp: env.block.time.seconds(),
+            expire_timestamp: env.block.time.plus_seconds(100).seconds(),
+            remote: None,
+            pending: None,
+            last_error: None,
+            nonce: 0,
+        };
+        let key = bid_key(&remote.id, &bid.bidder);
+        bids()
+            .save(deps.as_mut().storage, key.clone(), &bid)
+            .unwrap();
+
+        let counter = ExecuteMsg::CounterOffer(CounterOfferMsg {
+            order_id: remote.id.clone(),
+            bidder: bid.bidder.clone(),
+            price: Uint128::from(180u64),
+            expiration_timestamp: env.block.time.plus_seconds(50).seconds(),
+        });
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("maker0001", &[]),
+            counter.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidSender);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(maker_receiver, &[]),
+            counter,
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[coin(30, "token2")]),
+            ExecuteMsg::AcceptCounterOffer(AcceptCounterOfferMsg {
+                order_id: remote.id.clone(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        // Only accepted once the top up is acknowledged
+        let offer = counter_offers().load(&deps.storage, key.clone()).unwrap();
+        assert_eq!(offer.status, CounterOfferStatus::Open);
+
+        // Another top up waits for the one in flight
+        let bid = bids().load(&deps.storage, key.clone()).unwrap();
+        assert_eq!(bid.bid, coin(150, "token2"));
+        assert_eq!(bid.pending, Some(SwapMessageType::UpdateBid));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[coin(10, "token2")]),
+            ExecuteMsg::UpdateBid(UpdateBidMsg {
+                order_id: remote.id.clone(),
+                addition: Uint128::new(10),
+                bidder: "bidder0001".to_string(),
+                timeout_height: None,
+                timeout_timestamp: None,
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PacketInFlight {
+                packet: "UpdateBid".to_string()
+            }
+        );
+
+        // A top up that times out is refunded and can be sent again
+        let mut msg = mock_ibc_packet_timeout("channel-0", &"").unwrap();
+        msg.packet.data = data;
+        let res = ibc_packet_timeout(deps.as_mut(), env.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "bidder0001".to_string(),
+                amount: vec![coin(30, "token2")],
+            })]
+        );
+        assert_eq!(res.events[0].ty, "packet_failed");
+        assert_eq!(res.events[0].attributes[0], attr("action", "update_bid"));
+        let bid = bids().load(&deps.storage, key.clone()).unwrap();
+        assert_eq!(bid.pending, None);
+        assert_eq!(bid.last_error, Some("timeout".to_string()));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bidder0001", &[coin(30, "token2")]),
+            ExecuteMsg::RetryPacket {
+                order_id: remote.id.clone(),
+                kind: SwapMessageType::UpdateBid,
+            },
+        )
+        .unwrap();
+        let data = match &res.messages[0].msg {
+            CosmosMsg::Ibc(IbcMsg::SendPacket { data, .. }) => data.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+
+        let mut msg =
+            mock_ibc_packet_ack("channel-0", &"", IbcAcknowledgement::new(ack_success())).unwrap();
+        msg.original_packet.data = data;
+        ibc_packet_ack(deps.as_mut(), env.clone(), msg).unwrap();
+        let bid = bids().load(&deps.storage, key).unwrap();
+        assert_eq!(bid.bid, coin(180, "token2"));
+        assert_eq!(bid.pending, None);
+
+        let res: CounterOffersResponse = from_json(
+            query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::CounterOffersByOrder {
+                    order: remote.id.clone(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.offers.len(), 1);
+        assert_eq!(res.offers[0].status, CounterOfferStatus::Accepted);
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("bidder0001", &[]),
+            ExecuteMsg::DeclineCounterOffer(DeclineCounterOfferMsg {
+                order_id: remote.id,
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidStatus);
     }
 }
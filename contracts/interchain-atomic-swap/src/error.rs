@@ -45,6 +45,46 @@ pub enum ContractError {
     #[error("Invalid status")]
     InvalidStatus,
 
+    #[error("A {packet} packet for this order is still in flight")]
+    PacketInFlight { packet: String },
+
+    #[error("No failed packet to retry")]
+    NoFailedPacket,
+
+    #[error("No bids left to refund")]
+    NoBidsToRefund,
+
+    #[error("No orders left to refund for this channel")]
+    NoChannelRefunds,
+
+    #[error("Counter offer doesn't exist")]
+    CounterOfferDoesntExist,
+
+    #[error("Invalid fill amount")]
+    InvalidFillAmount,
+
+    #[error("Order is not an auction")]
+    NotAuction,
+
+    #[error("Auction has not ended")]
+    AuctionNotEnded,
+
+    #[error("Auction has ended")]
+    AuctionEnded,
+
+    #[error("Auction has no bids")]
+    NoAuctionBids,
+
+    #[error("Pay at least the current price of {price}")]
+    BelowCurrentPrice { price: String },
+
+    #[error("Bid packet {nonce} of {bidder} on order {order_id} was already received")]
+    DuplicatePacket {
+        order_id: String,
+        bidder: String,
+        nonce: u64,
+    },
+
     #[error("Got a submessage reply with unknown id: {id}")]
     UnknownReplyId { id: u64 },
 
@@ -74,4 +114,46 @@ pub enum ContractError {
 
     #[error("Invalid bid amount")]
     InvalidBidAmount,
+
+    #[error("Timeout timestamp must be in the future")]
+    TimeoutInPast,
+
+    #[error("Timeout must not be more than {max} seconds from now")]
+    TimeoutTooLong { max: u64 },
+
+    #[error("Invalid timeout config")]
+    InvalidTimeoutConfig,
+
+    #[error("Invalid packet: {reason}")]
+    InvalidPacket { reason: String },
+
+    #[error("Invalid {msg_type} message: {reason}")]
+    InvalidPacketMsg { msg_type: String, reason: String },
+
+    #[error("Packet is missing {field}")]
+    MissingPacketField { field: String },
+
+    #[error("Invalid order path: {path}")]
+    InvalidOrderPath { path: String },
+
+    #[error("Packet on channel {channel} doesn't match the order path")]
+    PacketPathMismatch { channel: String },
+
+    #[error("Unknown channel {channel}")]
+    UnknownChannel { channel: String },
+
+    #[error("Channel {channel} is not registered for a counterparty chain")]
+    ChannelNotRegistered { channel: String },
+
+    #[error("Channel {channel} is disabled")]
+    ChannelDisabled { channel: String },
+
+    #[error("Denom {denom} is not allowed on channel {channel}")]
+    DenomNotAllowed { denom: String, channel: String },
+
+    #[error("Invalid counterparty chain")]
+    InvalidCounterpartyChain,
+
+    #[error("Invalid address {address} for prefix {prefix}")]
+    InvalidRemoteAddress { address: String, prefix: String },
 }
@@ -1,9 +1,12 @@
 // use cw20::{Balance, Cw20ExecuteMsg};
 
 use crate::{
-    atomic_swap_handler::{ack_fail, do_ibc_packet_receive, on_packet_failure, on_packet_success},
+    atomic_swap_handler::{
+        ack_fail, do_ibc_packet_receive, on_channel_close, on_packet_failure, on_packet_success,
+    },
+    codec::decode_packet,
     error::{ContractError, Never},
-    utils::{enforce_order_and_version, try_get_ack_error},
+    utils::{enforce_order_and_version, pending_packet_id, try_get_ack_error},
 };
 use cosmwasm_std::{
     attr, entry_point, DepsMut, Env, IbcBasicResponse, IbcChannel, IbcChannelCloseMsg,
@@ -11,7 +14,7 @@ use cosmwasm_std::{
     IbcPacketTimeoutMsg, IbcReceiveResponse, Reply, Response, SubMsgResult,
 };
 
-use crate::state::{ChannelInfo, CHANNEL_INFO};
+use crate::state::{pending_packets, ChannelInfo, CHANNEL_INFO};
 
 const RECEIVE_ID: u64 = 1337;
 const ACK_FAILURE_ID: u64 = 0xfa17;
@@ -64,14 +67,14 @@ pub fn ibc_channel_connect(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
+/// refund funds locked on the closed channel and forget the channel
 pub fn ibc_channel_close(
-    _deps: DepsMut,
-    _env: Env,
-    _channel: IbcChannelCloseMsg,
+    deps: DepsMut,
+    env: Env,
+    msg: IbcChannelCloseMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
-    // TODO: what to do here?
-    // we will have locked funds that need to be returned somehow
-    unimplemented!();
+    let channel: IbcChannel = msg.into();
+    on_channel_close(deps, env, &channel.endpoint.channel_id)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -102,10 +105,12 @@ pub fn ibc_packet_ack(
     env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
+    let packet_data = decode_packet(&msg.original_packet.data)?;
+    pending_packets().remove(deps.storage, &pending_packet_id(&packet_data)?)?;
     if let Some(error) = try_get_ack_error(&msg.acknowledgement) {
         on_packet_failure(deps, msg.original_packet, error)
     } else {
-        on_packet_success(deps, msg.original_packet, env)
+        on_packet_success(deps, msg.original_packet, &msg.acknowledgement, env)
     }
 }
 
@@ -117,5 +122,7 @@ pub fn ibc_packet_timeout(
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     let packet = msg.packet;
+    let packet_data = decode_packet(&packet.data)?;
+    pending_packets().remove(deps.storage, &pending_packet_id(&packet_data)?)?;
     on_packet_failure(deps, packet, "timeout".to_string())
 }
@@ -1,4 +1,6 @@
+pub mod asset;
 pub mod atomic_swap_handler;
+pub mod codec;
 pub mod contract;
 mod error;
 pub mod ibc;
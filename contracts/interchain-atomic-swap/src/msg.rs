@@ -3,11 +3,15 @@ use std::fmt;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cw20::Cw20Coin;
+use cw20::{Cw20Coin, Cw20ReceiveMsg};
+use cw721::Cw721ReceiveMsg;
 
 use cosmwasm_std::{Binary, Coin, Timestamp, Uint128};
 
-use crate::state::{AtomicSwapOrder, Bid, BidStatus, Status, VestingDetail};
+use crate::state::{
+    AtomicSwapOrder, AuctionConfig, Bid, BidStatus, CounterOffer, CounterpartyChain,
+    DutchAuctionConfig, FailedPacket, Fill, PendingPacket, RemoteState, Status, VestingDetail,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Height {
@@ -26,17 +30,82 @@ pub struct InstantiateMsg {
     pub vesting_contract: String,
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum ExecuteMsg {
     MakeSwap(MakeSwapMsg),
     TakeSwap(TakeSwapMsg),
     CancelSwap(CancelSwapMsg),
+    /// Changes the price, expiration or bid settings of an untaken order, maker only.
+    /// Sent from the maker chain, both copies change once the taker chain accepts it.
+    UpdateSwap(UpdateSwapMsg),
     MakeBid(MakeBidMsg),
     TakeBid(TakeBidMsg),
     CancelBid(CancelBidMsg),
     UpdateBid(UpdateBidMsg),
+    /// Refunds and closes a placed bid, maker only. Sent from the chain holding the bid.
+    RejectBid(RejectBidMsg),
+    /// Answers a placed bid with a price, maker only. Sent from the chain holding the bid.
+    CounterOffer(CounterOfferMsg),
+    /// Tops up the bid to the counter offer price with an UpdateBid packet, bidder only.
+    AcceptCounterOffer(AcceptCounterOfferMsg),
+    /// Declines the counter offer, bidder only.
+    DeclineCounterOffer(DeclineCounterOfferMsg),
     PauseMarket,
     UnpauseMarket,
+    UpdateConfig(UpdateConfigMsg),
+    /// Registers or replaces the counterparty chain of a channel, admin only.
+    SetCounterpartyChain(CounterpartyChain),
+    /// Forgets the counterparty chain of a channel, admin only.
+    RemoveCounterpartyChain {
+        channel_id: String,
+    },
+    /// Refunds an expired order on both chains and archives it.
+    ExpireSwap(ExpireSwapMsg),
+    /// Takes the highest bid of an ended auction with a TakeBid packet. Sent from the
+    /// chain holding the bids, anyone can call it.
+    SettleAuction(SettleAuctionMsg),
+    /// Sends the last failed CancelSwap, TakeBid, CancelBid or UpdateBid packet of the
+    /// sender for an order again, with a new timeout. UpdateBid needs the addition again.
+    RetryPacket {
+        order_id: String,
+        kind: SwapMessageType,
+    },
+    /// Refunds the placed bids left over when an order was closed with more bids than
+    /// one transaction refunds. Anyone can call it.
+    RefundBids {
+        order_id: String,
+        limit: Option<u32>,
+    },
+    /// Refunds the orders left over when a channel closed with more orders than one
+    /// transaction looks at. Anyone can call it.
+    RefundChannel {
+        channel_id: String,
+        limit: Option<u32>,
+    },
+    /// Cw20 tokens sent with a `ReceiveMsg`, which pays the wrapped message with them.
+    Receive(Cw20ReceiveMsg),
+    /// NFT sent with a `ReceiveMsg::MakeSwap`, which makes an order selling it.
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+/// Messages that can be paid with cw20 tokens through `ExecuteMsg::Receive`. An NFT sent
+/// through `ExecuteMsg::ReceiveNft` only takes `MakeSwap`.
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub enum ReceiveMsg {
+    MakeSwap(MakeSwapMsg),
+    TakeSwap(TakeSwapMsg),
+    MakeBid(MakeBidMsg),
+    UpdateBid(UpdateBidMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UpdateConfigMsg {
+    /// Packet timeout in seconds used when the caller doesn't provide one
+    pub default_timeout: Option<u64>,
+    /// Maximum packet timeout in seconds from the current block time
+    pub max_timeout: Option<u64>,
 }
 
 pub fn is_valid_name(name: &str) -> bool {
@@ -68,6 +137,12 @@ pub enum SwapMessageType {
     CancelBid = 6,
     #[serde(rename = "TYPE_MSG_UPDATE_BID")]
     UpdateBid = 7,
+    #[serde(rename = "TYPE_MSG_EXPIRE_SWAP")]
+    ExpireSwap = 8,
+    #[serde(rename = "TYPE_MSG_REJECT_BID")]
+    RejectBid = 9,
+    #[serde(rename = "TYPE_MSG_UPDATE_SWAP")]
+    UpdateSwap = 10,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -76,6 +151,9 @@ pub struct AtomicSwapPacketData {
     pub data: Binary,
     pub order_id: Option<String>,
     pub path: Option<String>,
+    pub memo: String,
+    /// Sequence number of make, update and cancel bid packets, see `Bid::nonce`
+    pub nonce: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
@@ -101,6 +179,25 @@ pub struct MakeSwapMsg {
     pub min_bid_price: Option<Uint128>,
 
     pub vesting: Option<VestingDetail>,
+    /// Allow takers to fill a part of the order at its price
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Smallest buy_token amount accepted from a partial fill, except the one completing
+    /// the order
+    #[serde(default)]
+    pub min_fill_amount: Option<Uint128>,
+    /// More tokens sold together with `sell_token` in a basket order
+    #[serde(default)]
+    pub extra_sell_tokens: Vec<Coin>,
+    /// More tokens wanted together with `buy_token` in a basket order
+    #[serde(default)]
+    pub extra_buy_tokens: Vec<Coin>,
+    /// Sell the order to the highest bid once the auction ends
+    #[serde(default)]
+    pub auction: Option<AuctionConfig>,
+    /// Lower the price over time until the order is taken
+    #[serde(default)]
+    pub dutch_auction: Option<DutchAuctionConfig>,
 
     pub timeout_height: Height,
     pub timeout_timestamp: u64,
@@ -108,6 +205,26 @@ pub struct MakeSwapMsg {
     pub expiration_timestamp: u64,
 }
 
+impl MakeSwapMsg {
+    /// All the tokens sold, a basket's included
+    pub fn sell_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.sell_token.clone()];
+        tokens.extend(self.extra_sell_tokens.iter().cloned());
+        tokens
+    }
+
+    /// All the tokens wanted, a basket's included
+    pub fn buy_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.buy_token.clone()];
+        tokens.extend(self.extra_buy_tokens.iter().cloned());
+        tokens
+    }
+
+    pub fn is_basket(&self) -> bool {
+        !self.extra_sell_tokens.is_empty() || !self.extra_buy_tokens.is_empty()
+    }
+}
+
 impl fmt::Display for MakeSwapMsg {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{{\"source_port\":\"{}\",\"source_channel\":\"{}\",\"sell_token\":{{\"denom\":\"{}\",\"amount\":\"{}\"}},\"buy_token\":{{\"denom\":\"{}\",\"amount\":\"{}\"}},\"maker_address\":\"{}\",\"maker_receiving_address\":\"{}\",\"desired_taker\":\"{}\",\"timeout_height\":{{\"revision_number\":\"{}\",\"revision_height\":\"{}\"}},\"timeout_timestamp\":\"{}\",\"expiration_timestamp\":\"{}\"}}",
@@ -128,21 +245,6 @@ impl fmt::Display for MakeSwapMsg {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
-pub struct MakeSwapMsgOutput {
-    pub source_port: String,
-    pub source_channel: String,
-    pub sell_token: Coin,
-    pub buy_token: Coin,
-    pub maker_address: String,
-    pub maker_receiving_address: String,
-    pub desired_taker: String,
-    pub timeout_height: HeightOutput,
-    pub timeout_timestamp: String,
-    pub expiration_timestamp: String,
-    pub take_bids: bool,
-}
-
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct HeightOutput {
     pub revision_number: String,
@@ -169,16 +271,6 @@ pub struct TakeSwapMsg {
     pub timeout_timestamp: u64,
 }
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
-pub struct TakeSwapMsgOutput {
-    pub order_id: String,
-    pub sell_token: Coin,
-    pub taker_address: String,
-    pub taker_receiving_address: String,
-    pub timeout_height: HeightOutput,
-    pub timeout_timestamp: String,
-}
-
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct CancelSwapMsg {
     pub order_id: String,
@@ -190,6 +282,22 @@ pub struct CancelSwapMsg {
     pub timeout_timestamp: String,
 }
 
+/// Fields left out keep their value
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct UpdateSwapMsg {
+    pub order_id: String,
+    /// New price, in the denom the order already asks for
+    pub buy_token: Option<Coin>,
+    pub expiration_timestamp: Option<u64>,
+    pub min_bid_price: Option<Uint128>,
+    /// Placed bids are refunded when the order stops taking bids
+    pub take_bids: Option<bool>,
+    /// Empty to let anyone take the order
+    pub desired_taker: Option<String>,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct MakeBidMsg {
     pub order_id: String,
@@ -197,18 +305,57 @@ pub struct MakeBidMsg {
     pub taker_address: String,
     pub taker_receiving_address: String,
     pub expiration_timestamp: u64,
+    /// Optional packet timeout, the contract default is used when neither is set.
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct TakeBidMsg {
     pub order_id: String,
     pub bidder: String,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
 pub struct CancelBidMsg {
     pub order_id: String,
     pub bidder: String,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct RejectBidMsg {
+    pub order_id: String,
+    pub bidder: String,
+    /// Shown to the bidder in the reject events
+    pub reason: Option<String>,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct CounterOfferMsg {
+    pub order_id: String,
+    pub bidder: String,
+    /// Total bid amount the maker would accept
+    pub price: Uint128,
+    pub expiration_timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct AcceptCounterOfferMsg {
+    pub order_id: String,
+    /// Timeout of the UpdateBid packet
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct DeclineCounterOfferMsg {
+    pub order_id: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
@@ -216,6 +363,23 @@ pub struct UpdateBidMsg {
     pub order_id: String,
     pub addition: Uint128,
     pub bidder: String,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct SettleAuctionMsg {
+    pub order_id: String,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+/// Closes an order after its expiration on both chains, anyone can send it.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, JsonSchema)]
+pub struct ExpireSwapMsg {
+    pub order_id: String,
+    pub timeout_height: Option<Height>,
+    pub timeout_timestamp: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -307,6 +471,34 @@ pub enum QueryMsg {
         start_after: Option<String>, // order
         limit: Option<u32>,
     },
+    /// Counter offers are only kept on the chain holding the bid.
+    /// Return type: CounterOffersResponse.
+    CounterOfferDetails {
+        order: String,
+        bidder: String,
+    },
+    CounterOffersByOrder {
+        order: String,
+        start_after: Option<String>, // bidder
+        limit: Option<u32>,
+    },
+    CounterOffersByBidder {
+        bidder: String,
+        start_after: Option<String>, // order
+        limit: Option<u32>,
+    },
+    /// Partial fills of an order, oldest first.
+    /// Return type: FillsResponse.
+    Fills {
+        order_id: String,
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    /// Price a taker pays now by this chain's clock, falling for Dutch auctions.
+    /// Return type: CurrentPriceResponse.
+    CurrentPrice {
+        order_id: String,
+    },
     /// Inactive fields query
     InactiveList {
         start_after: Option<u64>,
@@ -348,6 +540,42 @@ pub enum QueryMsg {
         limit: Option<u32>,
         taker: String,
     },
+    /// Returns the counterparty chain registered for a channel.
+    CounterpartyChain {
+        channel_id: String,
+    },
+    /// Lists registered counterparty chains by channel id.
+    /// Return type: CounterpartyChainsResponse.
+    CounterpartyChains {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Failed packets of an order that can be retried.
+    /// Return type: FailedPacketsResponse.
+    FailedPackets {
+        order_id: String,
+    },
+    /// Packets of an order waiting for an acknowledgement or timeout, by packet id.
+    /// Return type: PendingPacketsResponse.
+    PendingPackets {
+        order_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Packets sent by an address waiting for an acknowledgement or timeout.
+    /// Return type: PendingPacketsResponse.
+    PendingPacketsBySender {
+        sender: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Packets sent over a channel waiting for an acknowledgement or timeout.
+    /// Return type: PendingPacketsResponse.
+    PendingPacketsByChannel {
+        channel_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -359,6 +587,7 @@ pub struct DetailsResponse {
     pub taker: Option<TakeSwapMsg>,
     pub cancel_timestamp: Option<Timestamp>,
     pub complete_timestamp: Option<Timestamp>,
+    pub remote: Option<RemoteState>,
 }
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct ListResponse {
@@ -372,3 +601,35 @@ pub struct ListResponse {
 pub struct BidsResponse {
     pub bids: Vec<Bid>,
 }
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterOffersResponse {
+    pub offers: Vec<CounterOffer>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FillsResponse {
+    pub fills: Vec<Fill>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CurrentPriceResponse {
+    pub price: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterpartyChainsResponse {
+    pub chains: Vec<CounterpartyChain>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingPacketsResponse {
+    pub packets: Vec<PendingPacket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FailedPacketsResponse {
+    pub packets: Vec<FailedPacket>,
+    /// Packets of the order retried so far
+    pub retries: u32,
+}
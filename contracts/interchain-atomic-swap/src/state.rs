@@ -1,13 +1,28 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::msg::{MakeSwapMsg, TakeSwapMsg};
-use cosmwasm_std::{Coin, IbcEndpoint, StdResult, Storage, Timestamp, Uint128};
+use crate::msg::{MakeSwapMsg, SwapMessageType, TakeSwapMsg};
+use cosmwasm_std::{
+    Binary, Coin, IbcEndpoint, IbcTimeout, Order, StdResult, Storage, Timestamp, Uint128,
+};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
 pub const CHANNEL_INFO: Map<&str, ChannelInfo> = Map::new("channel_info");
 pub const FEE_INFO: Item<FeeInfo> = Item::new("fee_info");
 pub const CONFIG: Item<Config> = Item::new("config");
+pub const COUNTERPARTY_CHAINS: Map<&str, CounterpartyChain> = Map::new("counterparty_chains");
+// (order id, sender, packet type) -> last failed packet
+pub const FAILED_PACKETS: Map<(&str, &str, u8), FailedPacket> = Map::new("failed_packets");
+// order id -> number of retried packets
+pub const PACKET_RETRIES: Map<&str, u32> = Map::new("packet_retries");
+// order id -> side of a closed order with placed bids left to refund
+pub const BID_REFUNDS: Map<&str, Side> = Map::new("bid_refunds");
+// Placed bids refunded per transaction when an order is closed
+pub const MAX_BID_REFUNDS: u32 = 30;
+// Closed channels with orders left to refund, by the count of the next order to look at
+pub const CHANNEL_REFUNDS: Map<&str, u64> = Map::new("channel_refunds");
+// Orders looked at when a channel closes, or by one RefundChannel
+pub const MAX_CHANNEL_REFUNDS: u32 = 30;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct FeeInfo {
@@ -22,6 +37,23 @@ pub struct Config {
     pub vesting_contract: String,
     pub admin: String,
     pub state: MarketState,
+    // In seconds, used when the caller doesn't specify any packet timeout
+    #[serde(default = "default_timeout")]
+    pub default_timeout: u64,
+    // In seconds, the furthest a packet timeout may be set from the current block time
+    #[serde(default = "max_timeout")]
+    pub max_timeout: u64,
+}
+
+pub const DEFAULT_TIMEOUT_TIMESTAMP_OFFSET: u64 = 600;
+pub const MAX_TIMEOUT_TIMESTAMP_OFFSET: u64 = 7 * 24 * 60 * 60;
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT_TIMESTAMP_OFFSET
+}
+
+fn max_timeout() -> u64 {
+    MAX_TIMEOUT_TIMESTAMP_OFFSET
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -34,6 +66,41 @@ pub struct ChannelInfo {
     pub connection_id: String,
 }
 
+/// Counterparty chain behind a channel, orders can only be made over registered channels.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterpartyChain {
+    /// id of the channel on this chain
+    pub channel_id: String,
+    pub chain_id: String,
+    /// bech32 prefix of addresses on the counterparty chain
+    pub bech32_prefix: String,
+    /// denoms that can leave this chain over the channel, empty allows any
+    pub sell_denoms: Vec<String>,
+    /// denoms that can be received over the channel, empty allows any
+    pub buy_denoms: Vec<String>,
+    pub enabled: bool,
+}
+
+/// Packet that failed or timed out and can be sent again with `RetryPacket`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct FailedPacket {
+    pub order_id: String,
+    pub kind: SwapMessageType,
+    /// address that made the original request
+    pub sender: String,
+    /// encoded packet as it was sent
+    pub data: Binary,
+    pub error: String,
+}
+
+pub fn failed_packet_key<'a>(
+    order_id: &'a str,
+    sender: &'a str,
+    kind: &SwapMessageType,
+) -> (&'a str, &'a str, u8) {
+    (order_id, sender, kind.clone() as u8)
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Status {
@@ -66,6 +133,139 @@ pub struct AtomicSwapOrder {
     pub complete_timestamp: Option<Timestamp>,
     pub min_bid_price: Option<Uint128>,
     pub vesting_details: Option<VestingDetail>,
+    // Last state reported by the counterparty chain
+    #[serde(default)]
+    pub remote: Option<RemoteState>,
+    // Cancel or take bid packet sent for this order and not acknowledged yet
+    #[serde(default)]
+    pub pending: Option<SwapMessageType>,
+    // Error of the last failed or timed out packet for this order
+    #[serde(default)]
+    pub last_error: Option<String>,
+    // Taken so far by partial fills, in sell and buy token amounts
+    #[serde(default)]
+    pub filled_sell_amount: Uint128,
+    #[serde(default)]
+    pub filled_buy_amount: Uint128,
+    #[serde(default)]
+    pub fill_count: u32,
+}
+
+impl AtomicSwapOrder {
+    /// Sell token left for takers
+    pub fn remaining_sell(&self) -> Coin {
+        Coin {
+            denom: self.maker.sell_token.denom.clone(),
+            amount: self.maker.sell_token.amount - self.filled_sell_amount,
+        }
+    }
+
+    /// Buy token still wanted by the maker
+    pub fn remaining_buy(&self) -> Coin {
+        Coin {
+            denom: self.maker.buy_token.denom.clone(),
+            amount: self.maker.buy_token.amount - self.filled_buy_amount,
+        }
+    }
+
+    /// Sell tokens still escrowed for the maker, the rest of a basket included
+    pub fn remaining_sell_tokens(&self) -> Vec<Coin> {
+        let mut tokens = vec![self.remaining_sell()];
+        tokens.extend(self.maker.extra_sell_tokens.iter().cloned());
+        tokens
+    }
+
+    /// Buy token a taker pays at `now` for the whole order
+    pub fn current_price(&self, now: u64) -> Coin {
+        let mut price = self.maker.buy_token.clone();
+        if let Some(dutch) = &self.maker.dutch_auction {
+            price.amount = dutch.price_at(price.amount, now);
+        }
+        price
+    }
+
+    /// Tokens a taker pays with `buy_token` of the order, the rest of a basket included
+    pub fn taker_tokens(&self, buy_token: &Coin) -> Vec<Coin> {
+        let mut tokens = vec![buy_token.clone()];
+        tokens.extend(self.maker.extra_buy_tokens.iter().cloned());
+        tokens
+    }
+}
+
+/// One partial take of an order, recorded on both chains
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Fill {
+    pub taker: String,
+    /// Slice of the maker's sell token sent to the taker
+    pub sell_token: Coin,
+    /// Paid by the taker in the maker's buy token
+    pub buy_token: Coin,
+    pub timestamp: u64,
+}
+
+// Fills history: (order_id, fill index)
+pub const FILLS: Map<(&str, u32), Fill> = Map::new("fills");
+
+/// Order or bid state reported by the counterparty chain in a packet acknowledgement.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RemoteState {
+    pub order_id: String,
+    pub status: Option<Status>,
+    pub bid_status: Option<BidStatus>,
+    /// Bid amount recorded by the counterparty
+    pub bid_amount: Option<Uint128>,
+    /// Price charged by the maker chain for a take of a Dutch auction
+    #[serde(default)]
+    pub price: Option<Uint128>,
+    /// Block time of the counterparty in seconds
+    pub timestamp: u64,
+}
+
+/// English auction, settled with `SettleAuction` on the taker chain
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AuctionConfig {
+    /// Smallest bid accepted
+    pub reserve_price: Uint128,
+    /// Bidding closes at this time, in seconds. Moved back by late bids
+    pub end_time: u64,
+    /// A bid must beat the highest other bid by at least this amount
+    pub min_increment: Uint128,
+    /// A bid made less than this many seconds before the end moves the end to as many
+    /// seconds after it
+    pub extension_window: u64,
+}
+
+/// Dutch auction, the price falls from the amount of `buy_token` to `floor_price`.
+/// Takes pay the price on the maker chain when it receives them.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct DutchAuctionConfig {
+    pub floor_price: Uint128,
+    /// The price starts falling at this time, in seconds
+    pub start_time: u64,
+    /// The price reaches the floor at this time, in seconds
+    pub end_time: u64,
+    /// The price drops every this many seconds, 0 for a linear decay
+    #[serde(default)]
+    pub step: u64,
+}
+
+impl DutchAuctionConfig {
+    /// Price at `now` of an auction starting at `start_price`
+    pub fn price_at(&self, start_price: Uint128, now: u64) -> Uint128 {
+        if now <= self.start_time {
+            return start_price;
+        }
+        if now >= self.end_time {
+            return self.floor_price;
+        }
+        let mut elapsed = now - self.start_time;
+        if self.step > 0 {
+            elapsed -= elapsed % self.step;
+        }
+        start_price
+            - (start_price - self.floor_price)
+                .multiply_ratio(elapsed, self.end_time - self.start_time)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -149,6 +349,17 @@ pub fn get_atomic_order(storage: &dyn Storage, order_id: &str) -> StdResult<Atom
     Ok(swap_order)
 }
 
+// get an active order, None if it doesn't exist or has been archived
+pub fn may_get_atomic_order(
+    storage: &dyn Storage,
+    order_id: &str,
+) -> StdResult<Option<AtomicSwapOrder>> {
+    match ORDER_TO_COUNT.may_load(storage, order_id)? {
+        Some(id) => SWAP_ORDERS.may_load(storage, id),
+        None => Ok(None),
+    }
+}
+
 // set specific order
 pub fn remove_atomic_order(storage: &mut dyn Storage, order_id: &str) -> StdResult<u64> {
     let id = ORDER_TO_COUNT.load(storage, order_id)?;
@@ -178,6 +389,10 @@ pub enum BidStatus {
     Cancelled,
     Executed,
     Placed,
+    // refunded when the order was closed
+    Refunded,
+    // refunded when the maker rejected it
+    Rejected,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
@@ -189,6 +404,17 @@ pub struct Bid {
     pub bidder_receiver: String,
     pub receive_timestamp: u64,
     pub expire_timestamp: u64,
+    #[serde(default)]
+    pub remote: Option<RemoteState>,
+    // Cancel bid packet sent and not acknowledged yet
+    #[serde(default)]
+    pub pending: Option<SwapMessageType>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    // Make, update and cancel bid packets both chains processed, sent as the nonce of the
+    // next one so a replayed packet is refused
+    #[serde(default)]
+    pub nonce: u64,
 }
 
 /// Primary key for asks: (collection, token_id)
@@ -201,6 +427,8 @@ pub fn bid_key(order: &String, bidder: &String) -> BidKey {
 pub struct BidIndicies<'a> {
     pub order: MultiIndex<'a, String, Bid, BidKey>,
     pub order_price: MultiIndex<'a, (String, u128), Bid, BidKey>,
+    // (order, status as u8), to page over the open bids of an order
+    pub order_status: MultiIndex<'a, (String, u8), Bid, BidKey>,
     pub timestamp: MultiIndex<'a, (String, u64), Bid, BidKey>,
     pub bidder: MultiIndex<'a, String, Bid, BidKey>,
 }
@@ -210,6 +438,7 @@ impl<'a> IndexList<Bid> for BidIndicies<'a> {
         let v: Vec<&dyn Index<Bid>> = vec![
             &self.order,
             &self.order_price,
+            &self.order_status,
             &self.timestamp,
             &self.bidder,
         ];
@@ -225,6 +454,11 @@ pub fn bids<'a>() -> IndexedMap<'a, BidKey, Bid, BidIndicies<'a>> {
             "bids",
             "bids__order_price",
         ),
+        order_status: MultiIndex::new(
+            |_pk: &[u8], d: &Bid| (d.order.clone(), d.status.clone() as u8),
+            "bids",
+            "bids__order_status",
+        ),
         timestamp: MultiIndex::new(
             |_pk: &[u8], d: &Bid| (d.order.clone(), d.receive_timestamp),
             "bids",
@@ -238,3 +472,115 @@ pub fn bids<'a>() -> IndexedMap<'a, BidKey, Bid, BidIndicies<'a>> {
     };
     IndexedMap::new("bids", indexes)
 }
+
+/// Highest bid of an order that passes `filter`
+pub fn top_bid(
+    storage: &dyn Storage,
+    order_id: &str,
+    filter: impl Fn(&Bid) -> bool,
+) -> StdResult<Option<(BidKey, Bid)>> {
+    bids()
+        .idx
+        .order_price
+        .sub_prefix(order_id.to_string())
+        .range(storage, None, None, Order::Descending)
+        .find(|item| !matches!(item, Ok((_, bid)) if !filter(bid)))
+        .transpose()
+}
+
+/// Packet sent by this contract that has not been acknowledged or timed out yet.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingPacket {
+    /// `<order_id>/<kind>/<bidder>`, the kind as its number and the bidder empty for order
+    /// packets
+    pub id: String,
+    pub order_id: String,
+    /// set for bid packets
+    pub bidder: Option<String>,
+    pub kind: SwapMessageType,
+    /// channel on this chain the packet was sent over
+    pub channel_id: String,
+    pub sender: String,
+    // In seconds
+    pub send_timestamp: u64,
+    pub timeout: IbcTimeout,
+}
+
+pub struct PendingPacketIndexes<'a> {
+    pub order: MultiIndex<'a, String, PendingPacket, String>,
+    pub sender: MultiIndex<'a, String, PendingPacket, String>,
+    pub channel: MultiIndex<'a, String, PendingPacket, String>,
+}
+
+impl<'a> IndexList<PendingPacket> for PendingPacketIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<PendingPacket>> + '_> {
+        let v: Vec<&dyn Index<PendingPacket>> = vec![&self.order, &self.sender, &self.channel];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn pending_packets<'a>() -> IndexedMap<'a, &'a str, PendingPacket, PendingPacketIndexes<'a>> {
+    let indexes = PendingPacketIndexes {
+        order: MultiIndex::new(
+            |_pk: &[u8], d: &PendingPacket| d.order_id.clone(),
+            "pending_packets",
+            "pending_packets__order",
+        ),
+        sender: MultiIndex::new(
+            |_pk: &[u8], d: &PendingPacket| d.sender.clone(),
+            "pending_packets",
+            "pending_packets__sender",
+        ),
+        channel: MultiIndex::new(
+            |_pk: &[u8], d: &PendingPacket| d.channel_id.clone(),
+            "pending_packets",
+            "pending_packets__channel",
+        ),
+    };
+    IndexedMap::new("pending_packets", indexes)
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub enum CounterOfferStatus {
+    Open,
+    Accepted,
+    Declined,
+}
+
+/// The maker's answer to a bid, one negotiation thread per (order, bidder). Only kept on
+/// the chain holding the bid deposits.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct CounterOffer {
+    pub order: String,
+    pub bidder: String,
+    /// Total bid amount asked by the maker, in the bid denom
+    pub price: Uint128,
+    pub expire_timestamp: u64,
+    pub status: CounterOfferStatus,
+    /// Number of counter offers the maker made in this thread
+    pub round: u32,
+    pub update_timestamp: u64,
+}
+
+pub struct CounterOfferIndicies<'a> {
+    pub bidder: MultiIndex<'a, String, CounterOffer, BidKey>,
+}
+
+impl<'a> IndexList<CounterOffer> for CounterOfferIndicies<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<CounterOffer>> + '_> {
+        let v: Vec<&dyn Index<CounterOffer>> = vec![&self.bidder];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Counter offers keyed like bids: (order, bidder)
+pub fn counter_offers<'a>() -> IndexedMap<'a, BidKey, CounterOffer, CounterOfferIndicies<'a>> {
+    let indexes = CounterOfferIndicies {
+        bidder: MultiIndex::new(
+            |_pk: &[u8], d: &CounterOffer| d.bidder.clone(),
+            "counter_offers",
+            "counter_offers__bidder",
+        ),
+    };
+    IndexedMap::new("counter_offers", indexes)
+}
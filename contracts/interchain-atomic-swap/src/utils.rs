@@ -1,14 +1,21 @@
 use cosmwasm_std::{
-    from_json, Addr, BankMsg, Binary, Coin, Deps, IbcAcknowledgement, IbcChannel, IbcOrder,
-    StdError, StdResult, SubMsg, Uint128,
+    from_json, Addr, Coin, Deps, Env, IbcAcknowledgement, IbcChannel, IbcEndpoint, IbcOrder,
+    IbcPacket, IbcTimeout, IbcTimeoutBlock, StdError, StdResult, Storage, Timestamp, Uint128,
 };
 
 use sha2::{Digest, Sha256};
 
 use crate::{
+    asset::{is_cw20_denom, is_cw721_denom},
     atomic_swap_handler::AtomicSwapPacketAcknowledgement,
-    msg::{Height, MakeSwapMsg, MakeSwapMsgOutput, TakeSwapMsg, TakeSwapMsgOutput},
-    state::FEE_INFO,
+    codec::{decode_ack_result, packet_order},
+    msg::{
+        AtomicSwapPacketData, Height, HeightOutput, MakeSwapMsg, SwapMessageType, UpdateSwapMsg,
+    },
+    state::{
+        bids, top_bid, AtomicSwapOrder, BidKey, BidStatus, Config, CounterpartyChain, RemoteState,
+        Side, CHANNEL_INFO, COUNTERPARTY_CHAINS, FEE_INFO,
+    },
     ContractError,
 };
 
@@ -26,6 +33,18 @@ pub fn generate_order_id(order_path: &str) -> StdResult<String> {
     Ok(id)
 }
 
+/// Identifies a sent packet by its order, kind and bidder. Orders and bids only have one
+/// packet in flight at a time, so packets with the same data still get their own entry.
+pub fn pending_packet_id(packet: &AtomicSwapPacketData) -> Result<String, ContractError> {
+    let (order_id, bidder) = packet_order(packet)?;
+    Ok(format!(
+        "{}/{}/{}",
+        order_id,
+        packet.r#type.clone() as u8,
+        bidder.unwrap_or_default()
+    ))
+}
+
 pub fn order_path(
     source_channel: String,
     source_port: String,
@@ -48,6 +67,215 @@ pub fn extract_source_channel_for_taker_msg(path: &str) -> StdResult<String> {
     Ok(parts[5].to_string())
 }
 
+/// Endpoints recorded in an order path.
+pub(crate) struct OrderPath {
+    /// endpoint on the maker chain
+    pub maker: IbcEndpoint,
+    /// endpoint on the taker chain
+    pub taker: IbcEndpoint,
+}
+
+pub(crate) fn parse_order_path(path: &str) -> Result<OrderPath, ContractError> {
+    let invalid = || ContractError::InvalidOrderPath {
+        path: path.to_string(),
+    };
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.len() != 9
+        || parts[0] != "channel"
+        || parts[2] != "port"
+        || parts[4] != "channel"
+        || parts[6] != "port"
+    {
+        return Err(invalid());
+    }
+    parts[8].parse::<u64>().map_err(|_| invalid())?;
+    Ok(OrderPath {
+        maker: IbcEndpoint {
+            port_id: parts[3].to_string(),
+            channel_id: parts[1].to_string(),
+        },
+        taker: IbcEndpoint {
+            port_id: parts[7].to_string(),
+            channel_id: parts[5].to_string(),
+        },
+    })
+}
+
+/// Checks that a packet for an order came over the path of that order.
+/// `side` is the side of the order on this chain, which decides which end of the
+/// path the packet has to arrive on. The channel must also be a known one connected
+/// to the packet source.
+pub(crate) fn authenticate_packet(
+    deps: Deps,
+    packet: &IbcPacket,
+    path: &str,
+    side: &Side,
+) -> Result<(), ContractError> {
+    let path = parse_order_path(path)?;
+    let (local, remote) = match side {
+        Side::Native => (path.maker, path.taker),
+        Side::Remote => (path.taker, path.maker),
+    };
+    if packet.dest != local || packet.src != remote {
+        return Err(ContractError::PacketPathMismatch {
+            channel: packet.dest.channel_id.clone(),
+        });
+    }
+
+    let channel = CHANNEL_INFO
+        .may_load(deps.storage, &packet.dest.channel_id)?
+        .ok_or_else(|| ContractError::UnknownChannel {
+            channel: packet.dest.channel_id.clone(),
+        })?;
+    if channel.counterparty_endpoint != packet.src {
+        return Err(ContractError::PacketPathMismatch {
+            channel: packet.dest.channel_id.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Fails while a cancel or take bid packet is waiting for its acknowledgement.
+pub(crate) fn ensure_not_pending(pending: &Option<SwapMessageType>) -> Result<(), ContractError> {
+    match pending {
+        Some(packet) => Err(ContractError::PacketInFlight {
+            packet: format!("{:?}", packet),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Fails for a bid packet the bid already went through. A retried packet keeps the nonce
+/// of the failed one, so it is only refused once the first one was processed.
+pub(crate) fn ensure_new_bid_operation(
+    storage: &dyn Storage,
+    key: &BidKey,
+    nonce: u64,
+) -> Result<(), ContractError> {
+    if let Some(bid) = bids().may_load(storage, key.clone())? {
+        if nonce < bid.nonce {
+            return Err(ContractError::DuplicatePacket {
+                order_id: key.0.clone(),
+                bidder: key.1.clone(),
+                nonce,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Counts a bid packet as processed, on the receiving chain and on the sending chain once
+/// it is acknowledged.
+pub(crate) fn record_bid_operation(
+    storage: &mut dyn Storage,
+    key: BidKey,
+    nonce: u64,
+) -> StdResult<()> {
+    if let Some(mut bid) = bids().may_load(storage, key.clone())? {
+        bid.nonce = nonce + 1;
+        bids().save(storage, key, &bid)?;
+    }
+    Ok(())
+}
+
+/// Checks that orders can be made over a channel. `sell_denom` is the token leaving
+/// this chain and `buy_denom` the one coming in.
+pub(crate) fn enforce_counterparty_chain(
+    deps: Deps,
+    channel_id: &str,
+    sell_denom: &str,
+    buy_denom: &str,
+) -> Result<CounterpartyChain, ContractError> {
+    let chain = COUNTERPARTY_CHAINS
+        .may_load(deps.storage, channel_id)?
+        .ok_or_else(|| ContractError::ChannelNotRegistered {
+            channel: channel_id.to_string(),
+        })?;
+    if !chain.enabled {
+        return Err(ContractError::ChannelDisabled {
+            channel: channel_id.to_string(),
+        });
+    }
+    for (denom, allowed) in [
+        (sell_denom, &chain.sell_denoms),
+        (buy_denom, &chain.buy_denoms),
+    ] {
+        if !allowed.is_empty() && !allowed.iter().any(|allowed| allowed == denom) {
+            return Err(ContractError::DenomNotAllowed {
+                denom: denom.to_string(),
+                channel: channel_id.to_string(),
+            });
+        }
+    }
+    Ok(chain)
+}
+
+/// Returns the bech32 prefix of the counterparty chain behind a channel.
+pub(crate) fn counterparty_prefix(deps: Deps, channel_id: &str) -> Result<String, ContractError> {
+    let chain = COUNTERPARTY_CHAINS
+        .may_load(deps.storage, channel_id)?
+        .ok_or_else(|| ContractError::ChannelNotRegistered {
+            channel: channel_id.to_string(),
+        })?;
+    Ok(chain.bech32_prefix)
+}
+
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: impl Iterator<Item = u8>) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ u32::from(value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+    checksum
+}
+
+/// Checks that an address of the counterparty chain is a bech32 string with the
+/// prefix of that chain. This chain can't check more than the encoding.
+pub(crate) fn validate_remote_address(address: &str, prefix: &str) -> Result<(), ContractError> {
+    let invalid = || ContractError::InvalidRemoteAddress {
+        address: address.to_string(),
+        prefix: prefix.to_string(),
+    };
+    let (hrp, data) = address.rsplit_once('1').ok_or_else(invalid)?;
+    // The data part holds at least the six checksum characters
+    if hrp != prefix || data.len() < 6 {
+        return Err(invalid());
+    }
+    let data = data
+        .chars()
+        .map(|c| BECH32_CHARSET.find(c).map(|i| i as u8))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(invalid)?;
+    let values = hrp
+        .bytes()
+        .map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(hrp.bytes().map(|b| b & 31))
+        .chain(data);
+    if bech32_polymod(values) != 1 {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// Returns the channel on this chain that an order travels through.
+/// The maker chain sends over the maker's source channel, the taker chain over
+/// the counterparty channel recorded in the order path.
+pub fn local_channel_for_order(order: &AtomicSwapOrder) -> StdResult<String> {
+    match order.side {
+        Side::Native => Ok(order.maker.source_channel.clone()),
+        Side::Remote => extract_source_channel_for_taker_msg(&order.path),
+    }
+}
+
 pub fn try_get_ack_error(ack: &IbcAcknowledgement) -> Option<String> {
     let ack: AtomicSwapPacketAcknowledgement =
 	// What we can not parse is an ACK fail.
@@ -58,6 +286,13 @@ pub fn try_get_ack_error(ack: &IbcAcknowledgement) -> Option<String> {
     }
 }
 
+pub fn try_get_ack_result(ack: &IbcAcknowledgement) -> Option<RemoteState> {
+    match from_json(&ack.data).ok()? {
+        AtomicSwapPacketAcknowledgement::Result(result) => decode_ack_result(&result),
+        AtomicSwapPacketAcknowledgement::Error(_) => None,
+    }
+}
+
 pub const ICS100_VERSION: &str = "ics100-1";
 pub const ICS100_ORDERING: IbcOrder = IbcOrder::Unordered;
 
@@ -83,111 +318,313 @@ pub(crate) fn enforce_order_and_version(
     Ok(())
 }
 
-pub(crate) fn decode_take_swap_msg(data: &Binary) -> TakeSwapMsg {
-    let msg_res: Result<TakeSwapMsg, StdError> = from_json(data);
-    let msg: TakeSwapMsg;
+/// Builds the timeout of an outgoing packet from the caller supplied height and timestamp.
+/// A zero height or timestamp disables that part, the configured default is used when both
+/// are disabled. The timestamp is never allowed past the configured maximum.
+pub(crate) fn packet_timeout(
+    cfg: &Config,
+    env: &Env,
+    timeout_height: Option<Height>,
+    timeout_timestamp: Option<u64>,
+) -> Result<IbcTimeout, ContractError> {
+    let block = timeout_height
+        .filter(|height| height.revision_height != 0)
+        .map(|height| IbcTimeoutBlock {
+            revision: height.revision_number,
+            height: height.revision_height,
+        });
+    let timestamp = timeout_timestamp
+        .filter(|timestamp| *timestamp != 0)
+        .map(Timestamp::from_nanos);
+    let max_timestamp = env.block.time.plus_seconds(cfg.max_timeout);
 
-    match msg_res {
-        Ok(value) => {
-            msg = value;
+    let timeout = match (block, timestamp) {
+        (None, None) => {
+            IbcTimeout::with_timestamp(env.block.time.plus_seconds(cfg.default_timeout))
         }
-        Err(_err) => {
-            let msg_output: TakeSwapMsgOutput = from_json(data).unwrap();
-            msg = TakeSwapMsg {
-                order_id: msg_output.order_id.clone(),
-                sell_token: msg_output.sell_token.clone(),
-                taker_address: msg_output.taker_address.clone(),
-                taker_receiving_address: msg_output.taker_receiving_address.clone(),
-                timeout_height: Height {
-                    revision_number: msg_output.timeout_height.revision_number.parse().unwrap(),
-                    revision_height: msg_output.timeout_height.revision_height.parse().unwrap(),
-                },
-                timeout_timestamp: msg_output.timeout_timestamp.parse().unwrap(),
+        // A height alone could lock funds for good, so cap it with the maximum timestamp.
+        (Some(block), None) => IbcTimeout::with_both(block, max_timestamp),
+        (block, Some(timestamp)) => {
+            if timestamp <= env.block.time {
+                return Err(ContractError::TimeoutInPast);
+            }
+            if timestamp > max_timestamp {
+                return Err(ContractError::TimeoutTooLong {
+                    max: cfg.max_timeout,
+                });
+            }
+            match block {
+                Some(block) => IbcTimeout::with_both(block, timestamp),
+                None => IbcTimeout::with_timestamp(timestamp),
             }
         }
+    };
+    Ok(timeout)
+}
+
+/// Parses a number the Go side encodes as string, an empty string is zero.
+pub(crate) fn parse_u64_field(value: &str) -> StdResult<u64> {
+    if value.is_empty() {
+        return Ok(0);
     }
-    msg
+    value
+        .parse()
+        .map_err(|_| StdError::generic_err(format!("Invalid number: {}", value)))
 }
 
-pub(crate) fn decode_make_swap_msg(data: &Binary) -> MakeSwapMsg {
-    let msg_res: Result<MakeSwapMsg, StdError> = from_json(data);
-    let msg: MakeSwapMsg;
+pub(crate) fn parse_height_output(height: &HeightOutput) -> StdResult<Height> {
+    Ok(Height {
+        revision_number: parse_u64_field(&height.revision_number)?,
+        revision_height: parse_u64_field(&height.revision_height)?,
+    })
+}
 
-    match msg_res {
-        Ok(value) => {
-            msg = value;
-        }
-        Err(_err) => {
-            let msg_output: MakeSwapMsgOutput = from_json(data).unwrap();
-            msg = MakeSwapMsg {
-                source_port: msg_output.source_port.clone(),
-                source_channel: msg_output.source_channel.clone(),
-                sell_token: msg_output.sell_token.clone(),
-                buy_token: msg_output.buy_token.clone(),
-                maker_address: msg_output.maker_address.clone(),
-                maker_receiving_address: msg_output.maker_receiving_address.clone(),
-                desired_taker: msg_output.desired_taker.clone(),
-                timeout_height: Height {
-                    revision_number: msg_output.timeout_height.revision_number.parse().unwrap(),
-                    revision_height: msg_output.timeout_height.revision_height.parse().unwrap(),
-                },
-                timeout_timestamp: msg_output.timeout_timestamp.parse().unwrap(),
-                expiration_timestamp: msg_output.expiration_timestamp.parse().unwrap(),
-                take_bids: msg_output.take_bids,
-                min_bid_price: None,
-                vesting: None,
-            }
-        }
+/// Slice of the sell token a partial fill pays for, at the order's price. The fill
+/// completing the order gets all the sell token left, so no dust stays behind.
+pub(crate) fn partial_fill(order: &AtomicSwapOrder, paid: &Coin) -> Result<Coin, ContractError> {
+    let remaining = order.remaining_buy();
+    if paid.denom != remaining.denom {
+        return Err(ContractError::InvalidSellToken);
+    }
+    if paid.amount.is_zero() || paid.amount > remaining.amount {
+        return Err(ContractError::InvalidFillAmount);
+    }
+    if paid.amount == remaining.amount {
+        return Ok(order.remaining_sell());
+    }
+    if order
+        .maker
+        .min_fill_amount
+        .is_some_and(|min| paid.amount < min)
+    {
+        return Err(ContractError::InvalidFillAmount);
     }
-    msg
-}
 
-pub(crate) fn send_tokens(to: &Addr, amount: Coin) -> StdResult<SubMsg> {
-    let msg = BankMsg::Send {
-        to_address: to.into(),
-        amount: vec![amount],
-    };
-    Ok(SubMsg::new(msg))
+    let amount = order
+        .maker
+        .sell_token
+        .amount
+        .multiply_ratio(paid.amount, order.maker.buy_token.amount);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidFillAmount);
+    }
+    Ok(Coin {
+        denom: order.maker.sell_token.denom.clone(),
+        amount,
+    })
 }
 
-/// Calculates taker fees and returns (fee, Value - fee)
-pub fn taker_fee(deps: Deps, amount: &Uint128, denom: String) -> (Coin, Coin, Addr) {
-    let fee_info = FEE_INFO.load(deps.storage).unwrap();
+/// Calculates taker fees and returns (fee, Value - fee). NFTs are not charged, their
+/// fee is zero.
+pub fn taker_fee(deps: Deps, amount: &Uint128, denom: String) -> StdResult<(Coin, Coin, Addr)> {
+    let fee_info = FEE_INFO.load(deps.storage)?;
     let mut fee = (amount * Uint128::from(fee_info.taker_fee)) / Uint128::from(FEE_BASIS_POINT);
-    if fee.is_zero() {
+    if is_cw721_denom(&denom) {
+        fee = Uint128::zero();
+    } else if fee.is_zero() {
         fee = Uint128::from(1u64);
     }
-    let treasury_address = deps.api.addr_validate(&fee_info.treasury).unwrap();
-    (
+    let treasury_address = deps.api.addr_validate(&fee_info.treasury)?;
+    Ok((
         Coin {
             denom: denom.clone(),
             amount: fee,
         },
         Coin {
             denom,
-            amount: amount - fee,
+            amount: amount.checked_sub(fee)?,
         },
         treasury_address,
-    )
+    ))
 }
 
 /// Calculates maker fees and returns (fee, Value - fee)
-pub fn maker_fee(deps: Deps, amount: &Uint128, denom: String) -> (Coin, Coin, Addr) {
-    let fee_info = FEE_INFO.load(deps.storage).unwrap();
+pub fn maker_fee(deps: Deps, amount: &Uint128, denom: String) -> StdResult<(Coin, Coin, Addr)> {
+    let fee_info = FEE_INFO.load(deps.storage)?;
     let mut fee = (amount * Uint128::from(fee_info.maker_fee)) / Uint128::from(FEE_BASIS_POINT);
     if fee.is_zero() {
         fee = Uint128::from(1u64);
     }
-    let treasury_address = deps.api.addr_validate(&fee_info.treasury).unwrap();
-    (
+    let treasury_address = deps.api.addr_validate(&fee_info.treasury)?;
+    Ok((
         Coin {
             denom: denom.clone(),
             amount: fee,
         },
         Coin {
             denom,
-            amount: amount - fee,
+            amount: amount.checked_sub(fee)?,
         },
         treasury_address,
-    )
+    ))
+}
+
+/// Whether `funds` are exactly `expected`, in any order
+pub fn is_exact_funds(funds: &[Coin], expected: &[Coin]) -> bool {
+    funds.len() == expected.len() && expected.iter().all(|coin| funds.contains(coin))
+}
+
+/// Basket orders settle whole, in native tokens with one coin per denom on each side
+pub fn validate_basket(msg: &MakeSwapMsg) -> StdResult<()> {
+    if !msg.is_basket() {
+        return Ok(());
+    }
+    if msg.allow_partial || msg.take_bids || msg.vesting.is_some() {
+        return Err(StdError::generic_err(
+            "Basket orders can't be filled in parts, take bids or vest",
+        ));
+    }
+    for tokens in [msg.sell_tokens(), msg.buy_tokens()] {
+        for (i, coin) in tokens.iter().enumerate() {
+            if coin.amount.is_zero()
+                || is_cw20_denom(&coin.denom)
+                || is_cw721_denom(&coin.denom)
+                || tokens[..i].iter().any(|other| other.denom == coin.denom)
+            {
+                return Err(StdError::generic_err(
+                    "Basket tokens must be native, not empty and of different denoms",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies an UpdateSwap to either copy of an order. The buy denom can't change, so placed
+/// bids stay valid, and a partly filled order keeps its price.
+pub fn update_order(order: &mut AtomicSwapOrder, msg: &UpdateSwapMsg) -> Result<(), ContractError> {
+    // Bidders and takers of an auction count on its terms
+    if order.maker.auction.is_some() || order.maker.dutch_auction.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Auctions can't be updated".to_string(),
+        )));
+    }
+    let mut maker = order.maker.clone();
+    if let Some(buy_token) = &msg.buy_token {
+        if buy_token.denom != maker.buy_token.denom || buy_token.amount.is_zero() {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Only the amount of the buy token can change".to_string(),
+            )));
+        }
+        if order.fill_count > 0 && buy_token.amount != maker.buy_token.amount {
+            return Err(ContractError::Std(StdError::generic_err(
+                "The price of a partly filled order can't change".to_string(),
+            )));
+        }
+        maker.buy_token = buy_token.clone();
+    }
+    if let Some(expiration_timestamp) = msg.expiration_timestamp {
+        maker.expiration_timestamp = expiration_timestamp;
+    }
+    if let Some(min_bid_price) = msg.min_bid_price {
+        maker.min_bid_price = Some(min_bid_price);
+    }
+    if let Some(take_bids) = msg.take_bids {
+        maker.take_bids = take_bids;
+    }
+    if let Some(desired_taker) = &msg.desired_taker {
+        maker.desired_taker = desired_taker.clone();
+    }
+
+    validate_basket(&maker)?;
+    if maker.allow_partial {
+        if maker.take_bids {
+            return Err(ContractError::Std(StdError::generic_err(
+                "Partial fills can't be combined with bids or vesting".to_string(),
+            )));
+        }
+        if maker
+            .min_fill_amount
+            .is_some_and(|min| min > maker.buy_token.amount)
+        {
+            return Err(ContractError::InvalidFillAmount);
+        }
+    }
+
+    order.min_bid_price = maker.min_bid_price;
+    order.maker = maker;
+    Ok(())
+}
+
+/// Auctions sell the whole order to the highest bid from anyone, before the order expires
+pub fn validate_auction(msg: &MakeSwapMsg, now: u64) -> StdResult<()> {
+    let Some(auction) = &msg.auction else {
+        return Ok(());
+    };
+    if !msg.take_bids || !msg.desired_taker.is_empty() {
+        return Err(StdError::generic_err("Auctions must take bids from anyone"));
+    }
+    if auction.end_time <= now || auction.end_time > msg.expiration_timestamp {
+        return Err(StdError::generic_err(
+            "Auctions must end after now and before the order expires",
+        ));
+    }
+    Ok(())
+}
+
+/// Dutch auctions are taken whole, at a price falling from `buy_token` to the floor
+pub fn validate_dutch_auction(msg: &MakeSwapMsg) -> StdResult<()> {
+    let Some(dutch) = &msg.dutch_auction else {
+        return Ok(());
+    };
+    if msg.take_bids || msg.allow_partial || msg.auction.is_some() {
+        return Err(StdError::generic_err(
+            "Dutch auctions can't take bids or partial fills",
+        ));
+    }
+    if dutch.floor_price.is_zero() || dutch.floor_price > msg.buy_token.amount {
+        return Err(StdError::generic_err(
+            "The floor price must be between zero and the buy token amount",
+        ));
+    }
+    if dutch.start_time >= dutch.end_time {
+        return Err(StdError::generic_err(
+            "Dutch auctions must end after they start",
+        ));
+    }
+    Ok(())
+}
+
+/// Auction bids are only taken by SettleAuction
+pub fn ensure_not_auction(order: &AtomicSwapOrder) -> Result<(), ContractError> {
+    if order.maker.auction.is_some() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Auction bids are settled with SettleAuction".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+/// Checks a bid of `amount` on an auction against its reserve and the highest bid of the
+/// others, including bids still waiting for their ack. A bid in the extension window moves
+/// the end back, but not past the expiration both chains agreed on.
+/// Returns whether the order is an auction and has to be saved.
+pub fn place_auction_bid(
+    storage: &dyn Storage,
+    now: u64,
+    order: &mut AtomicSwapOrder,
+    bidder: &str,
+    amount: Uint128,
+) -> Result<bool, ContractError> {
+    let Some(auction) = order.maker.auction.as_mut() else {
+        return Ok(false);
+    };
+    if now >= auction.end_time {
+        return Err(ContractError::AuctionEnded);
+    }
+    if amount < auction.reserve_price {
+        return Err(ContractError::InvalidBidAmount);
+    }
+    let highest = top_bid(storage, &order.id, |bid| {
+        bid.bidder != bidder && matches!(bid.status, BidStatus::Initial | BidStatus::Placed)
+    })?;
+    if let Some((_, highest)) = highest {
+        if amount <= highest.bid.amount || amount < highest.bid.amount + auction.min_increment {
+            return Err(ContractError::InvalidBidAmount);
+        }
+    }
+    if auction.end_time - now < auction.extension_window {
+        auction.end_time = (now + auction.extension_window).min(order.maker.expiration_timestamp);
+    }
+    Ok(true)
 }
@@ -1,9 +1,11 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_json_binary, Addr, BankMsg, Binary, Coin, Deps, DepsMut, Empty, Env, MessageInfo, Reply,
-    ReplyOn, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg, WasmQuery,
+    from_json, to_json_binary, Addr, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Empty, Env,
+    MessageInfo, Reply, ReplyOn, Response, StdError, StdResult, SubMsg, Uint128, WasmMsg,
+    WasmQuery,
 };
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw721_base::state::Approval;
 use cw721_base::{
     msg::ExecuteMsg as Cw721ExecuteMsg, msg::InstantiateMsg as Cw721InstantiateMsg,
@@ -23,6 +25,8 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 const INSTANTIATE_TOKEN_REPLY_ID: u64 = 1;
 
+const CW20_PREFIX: &str = "cw20:";
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -34,6 +38,7 @@ pub fn instantiate(
     let config = Config {
         admin: info.sender.into_string(),
         allowed_addresses: msg.allowed_addresses,
+        accepted_tokens: msg.accepted_tokens,
         cw721_address: None,
         extension: None,
     };
@@ -92,10 +97,42 @@ pub fn execute(
             execute_start_vesting(deps, env, info, vesting, order_id)
         }
         ExecuteMsg::SetAllowed { addresses } => execute_set_contract(deps, env, info, addresses),
+        ExecuteMsg::SetAcceptedTokens { tokens } => {
+            execute_set_accepted_tokens(deps, env, info, tokens)
+        }
         ExecuteMsg::Claim { nft_id } => execute_claim(deps, env, info, nft_id),
+        ExecuteMsg::Receive(wrapper) => execute_receive(deps, env, info, wrapper),
     }
 }
 
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let ExecuteMsg::StartVesting { vesting, order_id } = from_json::<ExecuteMsg>(&wrapper.msg)?
+    else {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Only StartVesting can be paid with cw20 tokens".to_string(),
+        )));
+    };
+    // Any contract can call Receive, so the sender it reports is only trusted from accepted tokens
+    let config = CONFIG.load(deps.storage)?;
+    if !config.accepted_tokens.contains(&info.sender.to_string()) {
+        return Err(ContractError::TokenNotAccepted {});
+    }
+    // Cw20 tokens are kept as coins with a `cw20:<contract>` denom, like the swap contracts do
+    let info = MessageInfo {
+        sender: deps.api.addr_validate(&wrapper.sender)?,
+        funds: vec![Coin {
+            denom: format!("{}{}", CW20_PREFIX, info.sender),
+            amount: wrapper.amount,
+        }],
+    };
+    execute_start_vesting(deps, env, info, vesting, order_id)
+}
+
 pub fn execute_start_vesting(
     deps: DepsMut,
     _env: Env,
@@ -186,6 +223,29 @@ pub fn execute_set_contract(
     Ok(res)
 }
 
+pub fn execute_set_accepted_tokens(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    tokens: Vec<String>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    if info.sender != config.admin {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Must be called by admin".to_string(),
+        )));
+    }
+
+    config.accepted_tokens = tokens
+        .iter()
+        .map(|token| deps.api.addr_validate(token).map(|addr| addr.into_string()))
+        .collect::<StdResult<_>>()?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let res = Response::new().add_attribute("action", "set_accepted_tokens");
+    Ok(res)
+}
+
 pub fn execute_claim(
     deps: DepsMut,
     env: Env,
@@ -243,13 +303,26 @@ pub fn execute_claim(
             return Err(ContractError::AlreadyClaimed {});
         }
 
-        send_msg.push(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: vec![Coin {
-                denom: vesting.token.denom.clone(),
-                amount: Uint128::from(final_amount),
-            }],
-        });
+        let send: CosmosMsg = match vesting.token.denom.strip_prefix(CW20_PREFIX) {
+            Some(token) => WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: info.sender.to_string(),
+                    amount: Uint128::from(final_amount),
+                })?,
+                funds: vec![],
+            }
+            .into(),
+            None => BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![Coin {
+                    denom: vesting.token.denom.clone(),
+                    amount: Uint128::from(final_amount),
+                }],
+            }
+            .into(),
+        };
+        send_msg.push(send);
 
         vesting.amount_claimed += Uint128::from(final_amount);
     }
@@ -306,3 +379,78 @@ fn query_config(deps: Deps) -> StdResult<Config> {
     let config = CONFIG.load(deps.storage)?;
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ReleaseInterval;
+    use cosmwasm_std::coin;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    #[test]
+    fn test_receive_only_accepts_listed_tokens() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            allowed_addresses: vec!["swap".to_string()],
+            accepted_tokens: vec!["token".to_string()],
+            token_code_id: 1,
+            name: "Vesting".to_string(),
+            symbol: "VEST".to_string(),
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("admin", &[]), msg).unwrap();
+
+        let start = ExecuteMsg::StartVesting {
+            vesting: VestingDetails {
+                start_time: 0,
+                schedules: vec![ReleaseInterval {
+                    interval: 10,
+                    amount: Uint128::new(10000),
+                }],
+                receiver: "receiver".to_string(),
+                token: coin(100, "cw20:rogue"),
+                amount_claimed: Uint128::zero(),
+            },
+            order_id: "0".to_string(),
+        };
+        let receive = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "swap".to_string(),
+            amount: Uint128::new(100),
+            msg: to_json_binary(&start).unwrap(),
+        });
+
+        // A rogue cw20 can't pass itself off as the swap contract
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("rogue", &[]),
+            receive.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::TokenNotAccepted {});
+
+        // An accepted token gets through to StartVesting
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("token", &[]),
+            receive.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Cw721NotLinked {});
+
+        // Only the admin lists tokens
+        let set = ExecuteMsg::SetAcceptedTokens {
+            tokens: vec!["rogue".to_string()],
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("swap", &[]),
+            set.clone(),
+        )
+        .unwrap_err();
+        execute(deps.as_mut(), mock_env(), mock_info("admin", &[]), set).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), mock_info("token", &[]), receive).unwrap_err();
+        assert_eq!(err, ContractError::TokenNotAccepted {});
+    }
+}
@@ -83,4 +83,7 @@ pub enum ContractError {
 
     #[error("AlreadyClaimed")]
     AlreadyClaimed {},
+
+    #[error("TokenNotAccepted")]
+    TokenNotAccepted {},
 }
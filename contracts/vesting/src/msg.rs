@@ -1,3 +1,4 @@
+use cw20::Cw20ReceiveMsg;
 use cw721_base::state::Approval;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -7,6 +8,9 @@ use crate::state::VestingDetails;
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct InstantiateMsg {
     pub allowed_addresses: Vec<String>,
+    /// cw20 token contracts that can pay for StartVesting
+    #[serde(default)]
+    pub accepted_tokens: Vec<String>,
     pub token_code_id: u64,
     pub name: String,
     pub symbol: String,
@@ -21,9 +25,14 @@ pub enum ExecuteMsg {
     SetAllowed {
         addresses: Vec<String>,
     },
+    SetAcceptedTokens {
+        tokens: Vec<String>,
+    },
     Claim {
         nft_id: String,
     },
+    /// StartVesting paid with cw20 tokens, only from accepted token contracts
+    Receive(Cw20ReceiveMsg),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
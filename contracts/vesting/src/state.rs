@@ -31,6 +31,9 @@ pub struct Config {
     pub admin: String,
     // allowed addresses which can enable vesting for receiver
     pub allowed_addresses: Vec<String>,
+    // cw20 tokens that can pay for StartVesting
+    #[serde(default)]
+    pub accepted_tokens: Vec<String>,
     // NFT address
     pub cw721_address: Option<Addr>,
     pub extension: Extension,